@@ -0,0 +1,255 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::AttackEvent;
+use crate::combat_stats::DamageType;
+use crate::creature::{Health, MaxHealth};
+use crate::faction::{Faction, FactionTable};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::{Grid, GRID_SIZE, TILE_SIZE};
+use crate::player::{Aim, Player, PlayerClass};
+use crate::position::Position;
+use crate::status_effects::{is_stunned, StatusEffects};
+
+//keys used for the class abilities, in the order of ClassDefinition::abilities
+pub const ABILITY_KEYS: [KeyCode; 2] = [KeyCode::Q, KeyCode::R];
+//distances are in tiles
+pub const CHARGE_DISTANCE: f32 = 3.0;
+pub const CHARGE_HIT_RADIUS: f32 = 1.5;
+pub const BLINK_DISTANCE: f32 = 5.0;
+//fraction of max health healed by second wind
+pub const SECOND_WIND_HEAL: f32 = 0.3;
+pub const MANA_SHIELD_TIME: f32 = 8.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ResourceKind {
+    Stamina,
+    Mana,
+}
+
+//The resource spent by the player's abilities, which one depends on the class
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct ClassResource {
+    pub kind: ResourceKind,
+    pub current: f32,
+    pub max: f32,
+    //regained per second
+    pub regen: f32,
+}
+
+impl ClassResource {
+    pub fn for_class(class : PlayerClass) -> Self {
+        let definition = class.definition();
+        ClassResource {
+            kind: definition.resource,
+            current: definition.max_resource,
+            max: definition.max_resource,
+            regen: definition.resource_regen,
+        }
+    }
+
+    //spends the amount if there is enough of it
+    pub fn spend(&mut self, amount : f32) -> bool {
+        if self.current < amount {
+            return false;
+        }
+        self.current -= amount;
+        true
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ClassAbility {
+    //dash forward and hit every hostile creature around the landing spot
+    Charge,
+    //heal part of the max health
+    SecondWind,
+    //teleport forward, walls stop it
+    Blink,
+    //damage is taken from mana instead of health for a while
+    ManaShield,
+}
+
+impl ClassAbility {
+    pub fn name(&self) -> &'static str {
+        match self {
+            ClassAbility::Charge => "Charge",
+            ClassAbility::SecondWind => "Second Wind",
+            ClassAbility::Blink => "Blink",
+            ClassAbility::ManaShield => "Mana Shield",
+        }
+    }
+    pub fn cost(&self) -> f32 {
+        match self {
+            ClassAbility::Charge => 30.0,
+            ClassAbility::SecondWind => 50.0,
+            ClassAbility::Blink => 15.0,
+            ClassAbility::ManaShield => 20.0,
+        }
+    }
+    //seconds before the ability can be used again
+    pub fn cooldown(&self) -> f32 {
+        match self {
+            ClassAbility::Charge => 4.0,
+            ClassAbility::SecondWind => 20.0,
+            ClassAbility::Blink => 3.0,
+            ClassAbility::ManaShield => 15.0,
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ClassAbilities {
+    pub abilities: Vec<ClassAbility>,
+    //seconds left before each ability is ready
+    pub cooldowns: HashMap<ClassAbility, f32>,
+}
+
+impl ClassAbilities {
+    pub fn for_class(class : PlayerClass) -> Self {
+        ClassAbilities {
+            abilities: class.definition().abilities.to_vec(),
+            cooldowns: HashMap::new(),
+        }
+    }
+    pub fn is_ready(&self, ability : ClassAbility) -> bool {
+        self.cooldowns.get(&ability).is_none_or(|cooldown| *cooldown <= 0.0)
+    }
+}
+
+//absorbs damage with mana while the timer runs
+#[derive(Component)]
+pub struct ManaShield(pub Timer);
+
+//moves along the direction until the distance (in pixels) is covered or a tile can't be walked on.
+//The result is snapped to the center of its tile so the mover never ends up inside a wall
+pub fn dash(grid : &Grid, from : &Position, direction : Vec2, distance : f32) -> Position {
+    let step = TILE_SIZE / 4.0;
+    let max = TILE_SIZE * (GRID_SIZE - 1) as f32;
+    let mut pos = from.clone();
+    let mut travelled = 0.0;
+    while travelled < distance {
+        let next = Position::from_vec2(pos.to_vec2() + direction * step, pos.2);
+        if next.0 < 0.0 || next.1 < 0.0 || next.0 > max || next.1 > max || !grid.get(next.to_tile()).is_walkable() {
+            break;
+        }
+        pos = next;
+        travelled += step;
+    }
+    Position::from_tile(pos.to_tile(), pos.2)
+}
+
+//The components of the player an ability needs, moves or spends
+type AbilityUser = (
+    Entity,
+    &'static mut Position,
+    &'static Aim,
+    &'static mut ClassAbilities,
+    &'static mut ClassResource,
+    &'static mut Health,
+    &'static MaxHealth,
+    Option<&'static StatusEffects>,
+);
+
+pub fn use_class_abilities(
+    keys: Res<Input<KeyCode>>,
+    mut players: Query<AbilityUser, With<Player>>,
+    others: Query<(Entity, &Position, &Faction), Without<Player>>,
+    faction_table: Res<FactionTable>,
+    game_world: Res<GameWorld>,
+    mut attack_events: EventWriter<AttackEvent>,
+    mut commands: Commands,
+) {
+    let Ok((player, mut pos, aim, mut abilities, mut resource, mut health, max_health, effects)) = players.get_single_mut() else {
+        return;
+    };
+    if is_stunned(effects) {
+        return;
+    }
+    for (index, key) in ABILITY_KEYS.iter().enumerate() {
+        if !keys.just_pressed(*key) {
+            continue;
+        }
+        let Some(ability) = abilities.abilities.get(index).copied() else {
+            continue;
+        };
+        if !abilities.is_ready(ability) || !resource.spend(ability.cost()) {
+            continue;
+        }
+        abilities.cooldowns.insert(ability, ability.cooldown());
+        let grid = game_world.grid(pos.2);
+        match ability {
+            ClassAbility::Charge => {
+                *pos = dash(grid, &pos, aim.direction, CHARGE_DISTANCE * TILE_SIZE);
+                for (other, other_pos, faction) in others.iter() {
+                    if other_pos.2 == pos.2
+                        && pos.distance(other_pos) <= CHARGE_HIT_RADIUS * TILE_SIZE
+                        && faction_table.is_hostile(Faction::Player, *faction)
+                    {
+                        attack_events.send(AttackEvent {
+                            attacker: player,
+                            target: other,
+                            damage: None,
+                            damage_type: DamageType::Physical,
+                        });
+                    }
+                }
+            }
+            ClassAbility::SecondWind => {
+                let heal = (max_health.0 as f32 * SECOND_WIND_HEAL).ceil() as u32;
+                health.0 = (health.0 + heal).min(max_health.0);
+            }
+            ClassAbility::Blink => {
+                *pos = dash(grid, &pos, aim.direction, BLINK_DISTANCE * TILE_SIZE);
+            }
+            ClassAbility::ManaShield => {
+                commands
+                    .entity(player)
+                    .insert(ManaShield(Timer::from_seconds(MANA_SHIELD_TIME, TimerMode::Once)));
+            }
+        }
+    }
+}
+
+//anything with a cooldown, a resource or a shield to tick
+type Ticking = (Entity, Option<&'static mut ClassAbilities>, Option<&'static mut ClassResource>, Option<&'static mut ManaShield>);
+type HasTicking = Or<(With<ClassAbilities>, With<ClassResource>, With<ManaShield>)>;
+
+pub fn tick_abilities(
+    time: Res<Time>,
+    mut creatures: Query<Ticking, HasTicking>,
+    mut commands: Commands,
+) {
+    let dt = time.delta_seconds();
+    for (entity, abilities, resource, shield) in creatures.iter_mut() {
+        if let Some(mut abilities) = abilities {
+            for cooldown in abilities.cooldowns.values_mut() {
+                *cooldown = (*cooldown - dt).max(0.0);
+            }
+        }
+        if let Some(mut resource) = resource {
+            resource.current = (resource.current + resource.regen * dt).min(resource.max);
+        }
+        if let Some(mut shield) = shield {
+            if shield.0.tick(time.delta()).finished() {
+                commands.entity(entity).remove::<ManaShield>();
+            }
+        }
+    }
+}
+
+pub struct AbilitiesPlugin;
+
+impl Plugin for AbilitiesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (tick_abilities, use_class_abilities)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
@@ -0,0 +1,439 @@
+use bevy::prelude::*;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::AttackEvent;
+use crate::combat_stats::{CombatStats, DamageType};
+use crate::creature::{Health, MaxHealth};
+use crate::faction::{Faction, FactionTable};
+use crate::game_plugin::game::handle_velocity;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::{Grid, TILE_SIZE};
+use crate::position::{Position, Velocity};
+use crate::projectiles::{FireProjectileEvent, RangedAttack};
+use crate::run_rng::RunRng;
+use crate::status_effects::{is_stunned, StatusEffects, StatusKind};
+
+//distance (in pixels) at which a path node counts as reached
+pub const NODE_REACHED_DISTANCE: f32 = 2.0;
+//seconds between two path computations while chasing
+pub const REPATH_INTERVAL: f32 = 0.5;
+//seconds spent wandering before going back to idle
+pub const WANDER_TIME: f32 = 4.0;
+//how far (in tiles) a wandering creature goes
+pub const WANDER_DISTANCE: i32 = 6;
+//how close (in tiles) a creature has to be to notice an invisible one
+pub const INVISIBLE_SIGHT_RANGE: f32 = 1.5;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum AiState {
+    #[default]
+    Idle,
+    Wander,
+    //going to where the target was last seen
+    Investigate,
+    Chase,
+    Attack,
+    Flee,
+}
+
+//Set per creature type in the bestiary
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default, Serialize, Deserialize)]
+pub enum Behavior {
+    #[default]
+    Balanced,
+    //runs away early
+    Cowardly,
+    //never flees and keeps tracking its target even without seeing it
+    Relentless,
+    //doesn't see far and forgets quickly
+    Territorial,
+    //sees far and moves fast
+    Hunter,
+}
+
+#[derive(Debug, Clone)]
+pub struct BehaviorProfile {
+    //how far the creature can see, in tiles
+    pub sight_range: f32,
+    //movement speed in pixels per second
+    pub speed: f32,
+    //distance (in tiles) from which it can attack
+    pub attack_range: f32,
+    //seconds between two attacks
+    pub attack_cooldown: f32,
+    //fraction of max health under which the creature runs away, 0 to never flee
+    pub flee_health: f32,
+    //seconds spent looking for a target it lost sight of before giving up
+    pub memory: f32,
+    //keeps following the target's real position while investigating
+    pub tracks_unseen: bool,
+    //chance per second of starting to wander when idle
+    pub wander_chance: f32,
+}
+
+impl Behavior {
+    pub fn profile(&self) -> BehaviorProfile {
+        match self {
+            Behavior::Balanced => BehaviorProfile {
+                sight_range: 8.0,
+                speed: 60.0,
+                attack_range: 1.2,
+                attack_cooldown: 1.0,
+                flee_health: 0.2,
+                memory: 5.0,
+                tracks_unseen: false,
+                wander_chance: 0.2,
+            },
+            Behavior::Cowardly => BehaviorProfile {
+                sight_range: 7.0,
+                speed: 75.0,
+                attack_range: 1.2,
+                attack_cooldown: 0.8,
+                flee_health: 0.5,
+                memory: 3.0,
+                tracks_unseen: false,
+                wander_chance: 0.4,
+            },
+            Behavior::Relentless => BehaviorProfile {
+                sight_range: 10.0,
+                speed: 40.0,
+                attack_range: 1.2,
+                attack_cooldown: 1.5,
+                flee_health: 0.0,
+                memory: 30.0,
+                tracks_unseen: true,
+                wander_chance: 0.1,
+            },
+            Behavior::Territorial => BehaviorProfile {
+                sight_range: 6.0,
+                speed: 50.0,
+                attack_range: 1.2,
+                attack_cooldown: 1.2,
+                flee_health: 0.0,
+                memory: 2.0,
+                tracks_unseen: false,
+                wander_chance: 0.05,
+            },
+            Behavior::Hunter => BehaviorProfile {
+                sight_range: 12.0,
+                speed: 80.0,
+                attack_range: 1.2,
+                attack_cooldown: 0.9,
+                flee_health: 0.15,
+                memory: 8.0,
+                tracks_unseen: false,
+                wander_chance: 0.3,
+            },
+        }
+    }
+}
+
+#[derive(Component, Debug, Clone)]
+pub struct Ai {
+    pub state: AiState,
+    pub behavior: Behavior,
+    pub profile: BehaviorProfile,
+    pub target: Option<Entity>,
+    //the last creature that attacked this one, it is fought back whatever its faction
+    pub aggressor: Option<Entity>,
+    //tile where the target was last seen
+    pub last_known_target_tile: Option<(usize, usize)>,
+    //tiles left to walk through, the next one first
+    pub path: Vec<(usize, usize)>,
+    pub time_in_state: f32,
+    pub repath_timer: f32,
+    pub attack_cooldown: f32,
+    //shoots at targets out of melee range but within this attack's range
+    pub ranged: Option<RangedAttack>,
+}
+
+impl Ai {
+    pub fn new(behavior : Behavior) -> Self {
+        Ai {
+            state: AiState::Idle,
+            behavior,
+            profile: behavior.profile(),
+            target: None,
+            aggressor: None,
+            last_known_target_tile: None,
+            path: Vec::new(),
+            time_in_state: 0.0,
+            repath_timer: 0.0,
+            attack_cooldown: 0.0,
+            ranged: None,
+        }
+    }
+
+    //distance (in tiles) from which the creature attacks, with its ranged attack if it has one
+    pub fn attack_range(&self) -> f32 {
+        self.ranged
+            .as_ref()
+            .map_or(self.profile.attack_range, |ranged| ranged.range.max(self.profile.attack_range))
+    }
+
+    pub fn set_state(&mut self, state : AiState) {
+        if self.state != state {
+            self.state = state;
+            self.time_in_state = 0.0;
+            self.path.clear();
+        }
+    }
+}
+
+//looks for hostile targets and picks the state of every creature
+pub fn ai_perception(
+    time: Res<Time>,
+    mut creatures: Query<(Entity, &mut Ai, &Position, &Health, &MaxHealth, &Faction)>,
+    targets: Query<(Entity, &Position, &Faction, Option<&StatusEffects>)>,
+    faction_table: Res<FactionTable>,
+    game_world: Res<GameWorld>,
+    mut run_rng: ResMut<RunRng>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut ai, pos, health, max_health, faction) in creatures.iter_mut() {
+        ai.time_in_state += dt;
+        ai.repath_timer -= dt;
+        ai.attack_cooldown = (ai.attack_cooldown - dt).max(0.0);
+        let grid = game_world.grid(pos.2);
+        let tile = pos.to_tile();
+        //the closest target in sight
+        let mut seen : Option<(Entity, f32, (usize, usize))> = None;
+        for (target, target_pos, target_faction, effects) in targets.iter() {
+            if target == entity || target_pos.2 != pos.2 {
+                continue;
+            }
+            if !faction_table.is_hostile(*faction, *target_faction) && ai.aggressor != Some(target) {
+                continue;
+            }
+            let distance = pos.distance(target_pos) / TILE_SIZE;
+            //invisible creatures are only noticed right next to them
+            let sight_range = if effects.is_some_and(|effects| effects.has(StatusKind::Invisible)) {
+                INVISIBLE_SIGHT_RANGE
+            } else {
+                ai.profile.sight_range
+            };
+            if distance > sight_range || !grid.has_line_of_sight(tile, target_pos.to_tile()) {
+                continue;
+            }
+            if seen.is_none_or(|(_, closest, _)| distance < closest) {
+                seen = Some((target, distance, target_pos.to_tile()));
+            }
+        }
+        let health_fraction = health.0 as f32 / max_health.0.max(1) as f32;
+        if let Some((target, distance, target_tile)) = seen {
+            ai.target = Some(target);
+            ai.last_known_target_tile = Some(target_tile);
+            if health_fraction < ai.profile.flee_health {
+                ai.set_state(AiState::Flee);
+            } else if distance <= ai.attack_range() {
+                ai.set_state(AiState::Attack);
+            } else {
+                ai.set_state(AiState::Chase);
+            }
+            continue;
+        }
+        let state = ai.state;
+        match state {
+            AiState::Chase | AiState::Attack => {
+                ai.set_state(AiState::Investigate);
+            }
+            AiState::Investigate => {
+                if ai.profile.tracks_unseen {
+                    if let Some(target_pos) = ai.target.and_then(|target| targets.get(target).ok()).map(|(_, p, _, _)| p) {
+                        if target_pos.2 == pos.2 {
+                            ai.last_known_target_tile = Some(target_pos.to_tile());
+                        }
+                    }
+                }
+                if ai.time_in_state > ai.profile.memory || ai.last_known_target_tile.is_none_or(|t| t == tile) {
+                    ai.target = None;
+                    ai.aggressor = None;
+                    ai.set_state(AiState::Wander);
+                }
+            }
+            AiState::Flee => {
+                if ai.time_in_state > ai.profile.memory {
+                    ai.set_state(AiState::Idle);
+                }
+            }
+            AiState::Wander => {
+                if ai.time_in_state > WANDER_TIME {
+                    ai.set_state(AiState::Idle);
+                }
+            }
+            AiState::Idle => {
+                if run_rng.ai.gen::<f32>() < ai.profile.wander_chance * dt {
+                    ai.set_state(AiState::Wander);
+                }
+            }
+        }
+    }
+}
+
+//the neighbouring tile that is the furthest from the threat
+fn flee_tile(grid : &Grid, tile : (usize, usize), threat : (usize, usize)) -> Option<(usize, usize)> {
+    let mut best = None;
+    let mut best_distance = 0;
+    for x_offset in -1..=1i32 {
+        for y_offset in -1..=1i32 {
+            let x = tile.0 as i32 + x_offset;
+            let y = tile.1 as i32 + y_offset;
+            if !Grid::in_bounds(x, y) || !grid.get((x as usize, y as usize)).is_walkable() {
+                continue;
+            }
+            let distance = (x - threat.0 as i32).pow(2) + (y - threat.1 as i32).pow(2);
+            if distance > best_distance {
+                best_distance = distance;
+                best = Some((x as usize, y as usize));
+            }
+        }
+    }
+    best
+}
+
+//The components of a creature the AI steers
+type AiMover = (
+    &'static mut Ai,
+    &'static Position,
+    &'static mut Velocity,
+    Option<&'static CombatStats>,
+    Option<&'static StatusEffects>,
+);
+
+//computes paths and sets the velocity of every creature, the movement itself goes through the MovementEvent pipeline
+pub fn ai_movement(
+    time: Res<Time>,
+    mut creatures: Query<AiMover>,
+    positions: Query<&Position>,
+    game_world: Res<GameWorld>,
+    mut run_rng: ResMut<RunRng>,
+) {
+    let dt = time.delta_seconds();
+    for (mut ai, pos, mut velocity, stats, effects) in creatures.iter_mut() {
+        if is_stunned(effects) {
+            velocity.0 = 0.0;
+            velocity.1 = 0.0;
+            continue;
+        }
+        let grid = game_world.grid(pos.2);
+        let tile = pos.to_tile();
+        let target_tile = ai
+            .target
+            .and_then(|target| positions.get(target).ok())
+            .filter(|target_pos| target_pos.2 == pos.2)
+            .map(|target_pos| target_pos.to_tile());
+        let state = ai.state;
+        match state {
+            AiState::Idle | AiState::Attack => {
+                ai.path.clear();
+            }
+            AiState::Chase | AiState::Investigate => {
+                let goal = if state == AiState::Chase { target_tile } else { ai.last_known_target_tile };
+                if ai.repath_timer <= 0.0 || ai.path.is_empty() {
+                    ai.repath_timer = REPATH_INTERVAL;
+                    ai.path = goal.and_then(|goal| grid.find_path(tile, goal)).unwrap_or_default();
+                }
+            }
+            AiState::Wander => {
+                if ai.path.is_empty() {
+                    let x = tile.0 as i32 + run_rng.ai.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE);
+                    let y = tile.1 as i32 + run_rng.ai.gen_range(-WANDER_DISTANCE..=WANDER_DISTANCE);
+                    if Grid::in_bounds(x, y) {
+                        ai.path = grid.find_path(tile, (x as usize, y as usize)).unwrap_or_default();
+                    }
+                }
+            }
+            AiState::Flee => {
+                if ai.path.is_empty() {
+                    if let Some(threat) = target_tile.or(ai.last_known_target_tile) {
+                        ai.path = flee_tile(grid, tile, threat).into_iter().collect();
+                    }
+                }
+            }
+        }
+        //walk towards the next tile of the path
+        let mut new_velocity = Vec2::ZERO;
+        while let Some(next) = ai.path.first().copied() {
+            let to_next = Position::from_tile(next, pos.2).to_vec2() - pos.to_vec2();
+            if to_next.length() < NODE_REACHED_DISTANCE {
+                ai.path.remove(0);
+                continue;
+            }
+            //slow down instead of overshooting the node
+            let max_speed = ai.profile.speed * stats.map_or(1.0, |stats| stats.speed);
+            let speed = max_speed.min(to_next.length() / dt.max(f32::EPSILON));
+            new_velocity = to_next.normalize() * speed;
+            break;
+        }
+        velocity.0 = new_velocity.x;
+        velocity.1 = new_velocity.y;
+    }
+}
+
+//creatures remember who attacked them
+pub fn ai_retaliate(mut attack_events: EventReader<AttackEvent>, mut creatures: Query<&mut Ai>) {
+    for event in attack_events.iter() {
+        //e.g. burning in lava
+        if event.attacker == event.target {
+            continue;
+        }
+        if let Ok(mut ai) = creatures.get_mut(event.target) {
+            ai.aggressor = Some(event.attacker);
+        }
+    }
+}
+
+//melee attacks when close enough, otherwise shoots with the ranged attack
+pub fn ai_attack(
+    mut creatures: Query<(Entity, &mut Ai, &Position, Option<&StatusEffects>)>,
+    positions: Query<&Position>,
+    mut attack_events: EventWriter<AttackEvent>,
+    mut fire_events: EventWriter<FireProjectileEvent>,
+) {
+    for (entity, mut ai, pos, effects) in creatures.iter_mut() {
+        if ai.state != AiState::Attack || ai.attack_cooldown > 0.0 || is_stunned(effects) {
+            continue;
+        }
+        let Some(target) = ai.target else {
+            continue;
+        };
+        let Ok(target_pos) = positions.get(target) else {
+            continue;
+        };
+        let to_target = target_pos.to_vec2() - pos.to_vec2();
+        if to_target.length() / TILE_SIZE <= ai.profile.attack_range {
+            attack_events.send(AttackEvent {
+                attacker: entity,
+                target,
+                damage: None,
+                damage_type: DamageType::Physical,
+            });
+        } else if let Some(ranged) = &ai.ranged {
+            fire_events.send(FireProjectileEvent {
+                owner: entity,
+                from: pos.clone(),
+                direction: to_target.normalize_or_zero(),
+                stats: ranged.projectile.clone(),
+                max_range: ranged.range,
+                damage: None,
+            });
+        }
+        ai.attack_cooldown = ai.profile.attack_cooldown;
+    }
+}
+
+pub struct AiPlugin;
+
+impl Plugin for AiPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (ai_retaliate, ai_perception, ai_movement, ai_attack)
+                .chain()
+                .before(handle_velocity)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
@@ -0,0 +1,101 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::Deserialize;
+
+use crate::creature::{effective_archetype, CreatureArchetype, CreatureType, Health, Level, MaxHealth};
+use crate::data_asset::{refresh_ron_resource, RonAsset, RonAssetLoader, RonResource};
+use crate::experience::StatAllocation;
+use crate::player::Player;
+
+pub const BESTIARY_PATH: &str = "data/bestiary.bestiary.ron";
+
+//The contents of the bestiary file, one archetype per creature type
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "5c3f0f0e-9a37-4d51-8f3b-2a6c1d7e4b10"]
+pub struct BestiaryAsset {
+    pub creatures: HashMap<CreatureType, CreatureArchetype>,
+}
+
+impl RonAsset for BestiaryAsset {
+    const EXTENSIONS: &'static [&'static str] = &["bestiary.ron"];
+}
+
+//Resource holding the currently loaded creature definitions.
+//It is refreshed every time the file changes on disk, so stats can be tuned while the game runs.
+#[derive(Resource)]
+pub struct Bestiary {
+    pub handle: Handle<BestiaryAsset>,
+    pub creatures: HashMap<CreatureType, CreatureArchetype>,
+    fallback: CreatureArchetype,
+}
+
+impl Bestiary {
+    pub fn get(&self, creature_type : CreatureType) -> &CreatureArchetype {
+        self.creatures.get(&creature_type).unwrap_or(&self.fallback)
+    }
+}
+
+pub fn load_bestiary(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Bestiary {
+        handle: asset_server.load(BESTIARY_PATH),
+        creatures: HashMap::new(),
+        fallback: CreatureArchetype::default(),
+    });
+}
+
+impl RonResource for Bestiary {
+    type Asset = BestiaryAsset;
+    fn handle(&self) -> &Handle<BestiaryAsset> {
+        &self.handle
+    }
+    fn refresh(&mut self, asset : &BestiaryAsset) {
+        self.creatures = asset.creatures.clone();
+        info!("bestiary loaded with {} creatures", self.creatures.len());
+    }
+}
+
+type BestiaryCreature = (
+    &'static CreatureType,
+    &'static mut Handle<Image>,
+    Option<(&'static Level, &'static mut MaxHealth, &'static mut Health, Option<&'static StatAllocation>)>,
+    Option<&'static Player>,
+);
+
+//the creatures already spawned take the sprites and health of the refreshed bestiary
+pub fn update_creatures_from_bestiary(
+    bestiary: Res<Bestiary>,
+    mut creatures: Query<BestiaryCreature>,
+    asset_server: Res<AssetServer>,
+) {
+    for (creature_type, mut texture, stats, player) in creatures.iter_mut() {
+        let archetype = effective_archetype(*creature_type, player, &bestiary);
+        *texture = asset_server.load(archetype.sprite.as_str());
+        //keep the same fraction of health when the max health changes
+        if let Some((level, mut max_health, mut health, allocation)) = stats {
+            let new_max_health = archetype.max_health_at_level(level.0)
+                + allocation.map_or(0, |allocation| allocation.bonus_health);
+            health.0 = (health.0 * new_max_health).checked_div(max_health.0).unwrap_or(new_max_health);
+            max_health.0 = new_max_health;
+        }
+    }
+}
+
+pub struct BestiaryPlugin;
+
+impl Plugin for BestiaryPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<BestiaryAsset>()
+            .init_asset_loader::<RonAssetLoader<BestiaryAsset>>()
+            .add_systems(Startup, load_bestiary)
+            .add_systems(
+                Update,
+                (
+                    refresh_ron_resource::<Bestiary>,
+                    update_creatures_from_bestiary.run_if(resource_changed::<Bestiary>()),
+                )
+                    .chain(),
+            );
+    }
+}
@@ -0,0 +1,191 @@
+use bevy::prelude::*;
+
+use crate::abilities::{ClassAbilities, ClassResource};
+use crate::bestiary::Bestiary;
+use crate::consumables::Identification;
+use crate::creature::{effective_archetype, CreatureType, Health, Level, MaxHealth};
+use crate::equipment::Equipment;
+use crate::game_over::RunStats;
+use crate::game_plugin::game::start_new_run;
+use crate::game_state::{GameState, PlayingState};
+use crate::gold::Gold;
+use crate::items::{Inventory, Items};
+use crate::message_log::MessageLog;
+use crate::player::{Player, PlayerClass};
+use crate::run_rng::RunRng;
+use crate::save_data::CurrentSave;
+use crate::shops::Trading;
+use crate::spells::Spellbook;
+
+pub const MAX_NAME_LENGTH: usize = 16;
+//a u64 always fits in 19 digits
+pub const MAX_SEED_LENGTH: usize = 19;
+
+//The choices made on the character creation screen, consumed when the run starts
+#[derive(Resource, Clone)]
+pub struct NewCharacter {
+    pub name: String,
+    pub class: PlayerClass,
+    pub ancestry: CreatureType,
+    //digits typed by the player, a random seed is used when empty
+    pub seed: String,
+}
+
+impl Default for NewCharacter {
+    fn default() -> Self {
+        NewCharacter {
+            name: "Adventurer".to_string(),
+            class: PlayerClass::default(),
+            ancestry: CreatureType::Human,
+            seed: String::new(),
+        }
+    }
+}
+
+impl NewCharacter {
+    pub fn seed(&self) -> Option<u64> {
+        self.seed.parse().ok()
+    }
+
+    //a short description of the stats the character will start with
+    pub fn preview(&self, bestiary : &Bestiary) -> String {
+        let player = Player {
+            class: self.class,
+            name: self.name.clone(),
+        };
+        let archetype = effective_archetype(self.ancestry, Some(&player), bestiary);
+        let class = self.class.definition();
+        let level = Level::default().0;
+        format!(
+            "{}\nHealth: {}  Attack: {}d{}\n{:?}: {}  Abilities: {}",
+            archetype.type_name,
+            archetype.max_health_at_level(level),
+            archetype.attack_rolls,
+            archetype.attack_die_at_level(level),
+            class.resource,
+            class.max_resource,
+            class
+                .abilities
+                .iter()
+                .map(|ability| ability.name())
+                .collect::<Vec<_>>()
+                .join(", "),
+        )
+    }
+}
+
+//ancestries the player can pick, the ones tagged "playable" in the bestiary
+pub fn playable_ancestries(bestiary : &Bestiary) -> Vec<CreatureType> {
+    let ancestries : Vec<CreatureType> = CreatureType::ALL
+        .into_iter()
+        .filter(|creature_type| creature_type.get_stats(bestiary).has_tag("playable"))
+        .collect();
+    if ancestries.is_empty() {
+        vec![CreatureType::Human]
+    } else {
+        ancestries
+    }
+}
+
+//A choice on the character creation screen, used as a component on the button that makes it
+pub trait CharacterChoice: Component + Copy + PartialEq {
+    fn apply(&self, new_character : &mut NewCharacter);
+    fn is_selected(&self, new_character : &NewCharacter) -> bool;
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct ClassChoice(pub PlayerClass);
+
+impl CharacterChoice for ClassChoice {
+    fn apply(&self, new_character : &mut NewCharacter) {
+        new_character.class = self.0;
+    }
+    fn is_selected(&self, new_character : &NewCharacter) -> bool {
+        new_character.class == self.0
+    }
+}
+
+#[derive(Component, Clone, Copy, PartialEq)]
+pub struct AncestryChoice(pub CreatureType);
+
+impl CharacterChoice for AncestryChoice {
+    fn apply(&self, new_character : &mut NewCharacter) {
+        new_character.ancestry = self.0;
+    }
+    fn is_selected(&self, new_character : &NewCharacter) -> bool {
+        new_character.ancestry == self.0
+    }
+}
+
+//The components of the player the created character replaces
+type CreatedPlayer = (
+    Entity,
+    &'static mut Player,
+    &'static mut CreatureType,
+    &'static mut Handle<Image>,
+    &'static Level,
+    &'static mut Health,
+    &'static mut MaxHealth,
+);
+
+//turn the player into the character that was created
+pub fn apply_new_character(
+    new_character: Res<NewCharacter>,
+    mut players: Query<CreatedPlayer>,
+    bestiary: Res<Bestiary>,
+    asset_server: Res<AssetServer>,
+    mut items: ResMut<Items>,
+    mut commands: Commands,
+) {
+    for (entity, mut player, mut creature_type, mut texture, level, mut health, mut max_health) in players.iter_mut() {
+        player.class = new_character.class;
+        player.name = new_character.name.clone();
+        *creature_type = new_character.ancestry;
+        let archetype = effective_archetype(*creature_type, Some(&*player), &bestiary);
+        *texture = asset_server.load(archetype.sprite.as_str());
+        max_health.0 = archetype.max_health_at_level(level.0);
+        health.0 = max_health.0;
+        commands.entity(entity).insert((
+            ClassResource::for_class(player.class),
+            ClassAbilities::for_class(player.class),
+            Spellbook::for_class(player.class),
+            Inventory::for_class(player.class),
+            Equipment::for_class(player.class),
+            Gold::default(),
+        ));
+    }
+    //nothing is identified at the start of a run, even with the seed of the previous one
+    commands.insert_resource(Identification::default());
+    //the run timer, the log and the stats of the run start over
+    commands.insert_resource(PlayingState::default());
+    commands.insert_resource(MessageLog::default());
+    commands.insert_resource(RunStats::default());
+    commands.insert_resource(Trading::default());
+    //not saved yet
+    commands.remove_resource::<CurrentSave>();
+    //the items rolled during the previous run are forgotten
+    items.clear_generated();
+    commands.remove_resource::<NewCharacter>();
+}
+
+//the floors are generated from the seed, so it is set before game::start_new_run
+pub fn seed_run(new_character: Res<NewCharacter>, mut commands: Commands) {
+    commands.insert_resource(match new_character.seed() {
+        Some(seed) => RunRng::from_seed(seed),
+        None => RunRng::random(),
+    });
+}
+
+pub struct CharacterCreationPlugin;
+
+impl Plugin for CharacterCreationPlugin {
+    fn build(&self, app: &mut App) {
+        //a character was just created (or the game over screen started a new run), resuming a paused run doesn't
+        app.add_systems(
+            OnEnter(GameState::Playing),
+            (seed_run, apply_deferred, start_new_run, apply_deferred, apply_new_character)
+                .chain()
+                .run_if(resource_exists::<NewCharacter>()),
+        );
+    }
+}
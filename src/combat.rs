@@ -0,0 +1,124 @@
+use bevy::prelude::*;
+use rand::Rng;
+
+use crate::abilities::{ClassResource, ManaShield};
+use crate::combat_stats::{CombatStats, DamageType};
+use crate::creature::Health;
+use crate::game_state::GameState;
+use crate::player::Player;
+use crate::run_rng::RunRng;
+
+//sent when a creature attacks another one, the damage is rolled when the event is handled
+#[derive(Event)]
+pub struct AttackEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    //damage already rolled by the source (e.g. a spell), the attacker's melee damage is rolled when None
+    pub damage: Option<u32>,
+    pub damage_type: DamageType,
+}
+
+//sent for every attack that lands, with the damage left after resistances and shields
+#[derive(Event)]
+pub struct DamageEvent {
+    pub attacker: Entity,
+    pub target: Entity,
+    pub damage: u32,
+    pub damage_type: DamageType,
+}
+
+//sent once when a creature's health reaches 0
+#[derive(Event)]
+pub struct DeathEvent {
+    pub entity: Entity,
+    pub killer: Option<Entity>,
+}
+
+//roll attack_rolls dice, each with as many sides as the attacker's attack at its level
+pub fn roll_damage(rolls : u32, die : u32, rng : &mut impl Rng) -> u32 {
+    (0..rolls).map(|_| rng.gen_range(1..=die.max(1))).sum()
+}
+
+type AttackTarget = (&'static mut Health, Option<&'static CombatStats>, Option<&'static mut ClassResource>, Option<&'static ManaShield>);
+
+pub fn resolve_attacks(
+    mut attack_events: EventReader<AttackEvent>,
+    attackers: Query<&CombatStats>,
+    mut targets: Query<AttackTarget>,
+    mut run_rng: ResMut<RunRng>,
+    mut damage_events: EventWriter<DamageEvent>,
+    mut death_events: EventWriter<DeathEvent>,
+) {
+    for event in attack_events.iter() {
+        let Ok((mut health, target_stats, resource, shield)) = targets.get_mut(event.target) else {
+            continue;
+        };
+        //already dead, waiting to be despawned
+        if health.0 == 0 {
+            continue;
+        }
+        let mut damage = match event.damage {
+            Some(damage) => damage,
+            None => {
+                let Ok(stats) = attackers.get(event.attacker) else {
+                    continue;
+                };
+                roll_damage(stats.attack_rolls, stats.attack_die, &mut run_rng.rng) + stats.damage_bonus
+            }
+        };
+        if let Some(target_stats) = target_stats {
+            damage = target_stats.mitigate(damage, event.damage_type);
+        }
+        //a mana shield takes as much of the damage as the mana left allows
+        if let (Some(mut resource), Some(_)) = (resource, shield) {
+            let absorbed = damage.min(resource.current as u32);
+            resource.current -= absorbed as f32;
+            damage -= absorbed;
+        }
+        health.0 = health.0.saturating_sub(damage);
+        damage_events.send(DamageEvent {
+            attacker: event.attacker,
+            target: event.target,
+            damage,
+            damage_type: event.damage_type,
+        });
+        if health.0 == 0 {
+            death_events.send(DeathEvent {
+                entity: event.target,
+                killer: Some(event.attacker),
+            });
+        }
+    }
+}
+
+pub fn handle_deaths(
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    mut commands: Commands,
+) {
+    for event in death_events.iter() {
+        //the player stays, see message_log.rs
+        if players.contains(event.entity) {
+            continue;
+        }
+        if let Some(entity) = commands.get_entity(event.entity) {
+            entity.despawn_recursive();
+        }
+    }
+}
+
+pub struct CombatPlugin;
+
+impl Plugin for CombatPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<AttackEvent>()
+            .add_event::<DamageEvent>()
+            .add_event::<DeathEvent>()
+            .add_systems(
+                Update,
+                (resolve_attacks, handle_deaths)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
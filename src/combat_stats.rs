@@ -0,0 +1,234 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bestiary::Bestiary;
+use crate::combat::resolve_attacks;
+use crate::creature::{effective_archetype, CreatureType, Level};
+use crate::equipment::Equipment;
+use crate::experience::StatAllocation;
+use crate::game_state::GameState;
+use crate::items::{ItemKind, Items};
+use crate::melee::{Weapon, WeaponStats};
+use crate::player::Player;
+use crate::status_effects::StatusEffects;
+
+//resistances can't make a creature immune
+pub const MAX_RESISTANCE: f32 = 0.9;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum DamageType {
+    #[default]
+    Physical,
+    Fire,
+    Cold,
+    Poison,
+}
+
+impl DamageType {
+    pub fn name(&self) -> &'static str {
+        match self {
+            DamageType::Physical => "physical",
+            DamageType::Fire => "fire",
+            DamageType::Cold => "cold",
+            DamageType::Poison => "poison",
+        }
+    }
+}
+
+//What a piece of equipment adds to the stats of whoever wears it
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct StatModifiers {
+    //added to the size of the attack dice
+    #[serde(default)]
+    pub attack_bonus: i32,
+    #[serde(default)]
+    pub extra_attack_rolls: u32,
+    //taken off every physical hit
+    #[serde(default)]
+    pub defense: u32,
+    //fraction added to the movement speed, e.g. 0.1 for 10% faster
+    #[serde(default)]
+    pub speed: f32,
+    //fraction of the damage of each type that is ignored, negative values make the damage worse
+    #[serde(default)]
+    pub resistances: HashMap<DamageType, f32>,
+}
+
+//The stats combat and movement use, aggregated from the archetype, the level, the spent stat points
+//and the equipment. Recomputed by aggregate_combat_stats whenever one of them changes
+#[derive(Component, Debug, Clone)]
+pub struct CombatStats {
+    pub attack_rolls: u32,
+    pub attack_die: u32,
+    //added to every melee hit
+    pub damage_bonus: u32,
+    pub defense: u32,
+    //multiplier of the movement speed
+    pub speed: f32,
+    pub resistances: HashMap<DamageType, f32>,
+}
+
+impl Default for CombatStats {
+    fn default() -> Self {
+        CombatStats {
+            attack_rolls: 1,
+            attack_die: 1,
+            damage_bonus: 0,
+            defense: 0,
+            speed: 1.0,
+            resistances: HashMap::new(),
+        }
+    }
+}
+
+impl CombatStats {
+    pub fn resistance(&self, damage_type : DamageType) -> f32 {
+        self.resistances
+            .get(&damage_type)
+            .copied()
+            .unwrap_or(0.0)
+            .min(MAX_RESISTANCE)
+    }
+
+    //the damage actually taken from a hit
+    pub fn mitigate(&self, damage : u32, damage_type : DamageType) -> u32 {
+        if damage == 0 {
+            return 0;
+        }
+        let mut taken = (damage as f32 * (1.0 - self.resistance(damage_type))).round().max(0.0) as u32;
+        if damage_type == DamageType::Physical {
+            //armor never stops a hit completely
+            taken = taken.saturating_sub(self.defense).max(1);
+        }
+        taken
+    }
+}
+
+//everything the combat stats are computed from, as Ref so only the changed creatures are recomputed
+type StatSources = (
+    &'static mut CombatStats,
+    Ref<'static, CreatureType>,
+    Ref<'static, Level>,
+    Option<Ref<'static, StatAllocation>>,
+    Option<Ref<'static, Equipment>>,
+    Option<Ref<'static, Player>>,
+    Option<Ref<'static, StatusEffects>>,
+    Option<&'static mut Weapon>,
+);
+
+pub fn aggregate_combat_stats(
+    mut creatures: Query<StatSources>,
+    bestiary: Res<Bestiary>,
+    items: Res<Items>,
+) {
+    let data_changed = bestiary.is_changed() || items.is_changed();
+    for (mut stats, creature_type, level, allocation, equipment, player, effects, weapon) in creatures.iter_mut() {
+        let changed = data_changed
+            || stats.is_added()
+            || effects.as_ref().is_some_and(|effects| effects.is_changed())
+            || creature_type.is_changed()
+            || level.is_changed()
+            || allocation.as_ref().is_some_and(|allocation| allocation.is_changed())
+            || equipment.as_ref().is_some_and(|equipment| equipment.is_changed())
+            || player.as_ref().is_some_and(|player| player.is_changed());
+        if !changed {
+            continue;
+        }
+        let archetype = effective_archetype(*creature_type, player.as_deref(), &bestiary);
+        let mut attack_die = archetype.attack_die_at_level(level.0) as i32;
+        let mut new_stats = CombatStats {
+            attack_rolls: archetype.attack_rolls,
+            damage_bonus: allocation.as_ref().map_or(0, |allocation| allocation.bonus_attack),
+            ..Default::default()
+        };
+        let mut weapon_stats = WeaponStats::default();
+        for id in equipment.iter().flat_map(|equipment| equipment.slots.values()) {
+            let Some(ItemKind::Equipment { weapon, modifiers, .. }) = items.get(id).map(|item| &item.kind) else {
+                continue;
+            };
+            attack_die += modifiers.attack_bonus;
+            new_stats.attack_rolls += modifiers.extra_attack_rolls;
+            new_stats.defense += modifiers.defense;
+            new_stats.speed += modifiers.speed;
+            for (damage_type, resistance) in modifiers.resistances.iter() {
+                *new_stats.resistances.entry(*damage_type).or_insert(0.0) += resistance;
+            }
+            if let Some(weapon) = weapon {
+                weapon_stats = weapon.clone();
+            }
+        }
+        if let Some(effects) = effects {
+            new_stats.speed += effects.speed_bonus();
+        }
+        new_stats.attack_die = attack_die.max(1) as u32;
+        new_stats.speed = new_stats.speed.max(0.1);
+        *stats = new_stats;
+        if let Some(mut weapon) = weapon {
+            if weapon.stats != weapon_stats {
+                weapon.stats = weapon_stats;
+            }
+        }
+    }
+}
+
+pub struct CombatStatsPlugin;
+
+impl Plugin for CombatStatsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            aggregate_combat_stats
+                .before(resolve_attacks)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn stats(defense : u32, resistances : &[(DamageType, f32)]) -> CombatStats {
+        CombatStats {
+            defense,
+            resistances: resistances.iter().copied().collect(),
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn defense_is_taken_off_physical_hits() {
+        assert_eq!(stats(3, &[]).mitigate(10, DamageType::Physical), 7);
+        //but not off the other types
+        assert_eq!(stats(3, &[]).mitigate(10, DamageType::Fire), 10);
+    }
+
+    #[test]
+    fn physical_hits_always_do_some_damage() {
+        assert_eq!(stats(50, &[]).mitigate(10, DamageType::Physical), 1);
+        assert_eq!(stats(50, &[]).mitigate(0, DamageType::Physical), 0);
+    }
+
+    #[test]
+    fn resistances_scale_the_damage() {
+        let resistant = stats(0, &[(DamageType::Fire, 0.5), (DamageType::Cold, -0.5)]);
+        assert_eq!(resistant.mitigate(10, DamageType::Fire), 5);
+        //negative resistances make it worse
+        assert_eq!(resistant.mitigate(10, DamageType::Cold), 15);
+    }
+
+    #[test]
+    fn resistances_are_capped() {
+        let immune = stats(0, &[(DamageType::Poison, 2.0)]);
+        assert_eq!(immune.resistance(DamageType::Poison), MAX_RESISTANCE);
+        assert_eq!(immune.mitigate(100, DamageType::Poison), 10);
+    }
+
+    #[test]
+    fn resistance_is_applied_before_defense() {
+        let armored = stats(2, &[(DamageType::Physical, 0.5)]);
+        assert_eq!(armored.mitigate(10, DamageType::Physical), 3);
+    }
+}
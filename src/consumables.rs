@@ -0,0 +1,305 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::ClassResource;
+use crate::combat::{resolve_attacks, roll_damage, AttackEvent};
+use crate::combat_stats::DamageType;
+use crate::creature::{Health, MaxHealth};
+use crate::faction::{Faction, FactionTable};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::TILE_SIZE;
+use crate::items::{Inventory, ItemKind, Items};
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::Player;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::save_data::SaveData;
+use crate::status_effects::{apply_status_effects, ApplyStatusEvent, StatusApplication};
+
+//mixed into the run seed so shuffling the appearances doesn't depend on the other rolls of the run
+const APPEARANCE_SEED_SALT: u64 = 0x5eed_a99e_a2a9_ce00;
+
+//Consumables of the same category share a pool of appearances, shuffled between their items every run
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum ConsumableCategory {
+    Potion,
+    Scroll,
+}
+
+//What an unidentified consumable looks like, e.g. a "Murky Potion"
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub struct Appearance {
+    pub name: String,
+    pub sprite: String,
+}
+
+//What happens when a consumable is used, distances are in tiles
+#[derive(Debug, Clone, PartialEq, Deserialize)]
+pub enum ConsumableEffect {
+    Heal { amount: u32 },
+    //mana or stamina, whichever the class uses
+    RestoreResource { amount: f32 },
+    //to a random tile of the same floor
+    Teleport,
+    //explores the whole floor
+    Mapping,
+    //puts a status effect on the user, e.g. haste
+    Status(StatusApplication),
+    //burns every hostile creature around the user
+    Fire { rolls: u32, die: u32, radius: f32 },
+    //identifies a random unidentified item the user carries
+    Identify,
+}
+
+//What the player knows about the consumables of the current run
+#[derive(Resource, Default)]
+pub struct Identification {
+    //seed of the run the appearances were shuffled for
+    pub seed: Option<u64>,
+    //the appearance of every consumable that has one this run, by item id
+    pub appearances: HashMap<String, Appearance>,
+    //ids of the consumables identified this run
+    pub known: HashSet<String>,
+}
+
+impl Identification {
+    pub fn is_known(&self, id : &str) -> bool {
+        !self.appearances.contains_key(id) || self.known.contains(id)
+    }
+    //returns whether the item wasn't known yet
+    pub fn identify(&mut self, id : &str) -> bool {
+        !self.is_known(id) && self.known.insert(id.to_string())
+    }
+    pub fn name(&self, items : &Items, id : &str) -> String {
+        match self.appearances.get(id) {
+            Some(appearance) if !self.is_known(id) => appearance.name.clone(),
+            _ => items.name(id),
+        }
+    }
+    pub fn sprite<'a>(&'a self, items : &'a Items, id : &str) -> &'a str {
+        match self.appearances.get(id) {
+            Some(appearance) if !self.is_known(id) => appearance.sprite.as_str(),
+            _ => items.sprite(id),
+        }
+    }
+    pub fn save(&self, save_data : &mut SaveData) {
+        save_data.identified = self.known.iter().cloned().collect();
+        save_data.identified.sort();
+    }
+    //the appearances themselves come back from the run seed
+    pub fn load(save_data : &SaveData) -> Self {
+        Identification {
+            known: save_data.identified.iter().cloned().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+//gives every consumable with a category one of the category's appearances, the same seed always gives the same result
+pub fn shuffle_appearances(items : &Items, seed : u64) -> HashMap<String, Appearance> {
+    let mut rng = ChaCha8Rng::seed_from_u64(seed ^ APPEARANCE_SEED_SALT);
+    let mut appearances = HashMap::new();
+    //sorted so the result doesn't depend on the order of the hash maps
+    let mut categories : Vec<_> = items.appearances.iter().collect();
+    categories.sort_by_key(|(category, _)| **category);
+    for (category, pool) in categories {
+        let mut ids : Vec<&String> = items
+            .items
+            .iter()
+            .filter(|(_, item)| matches!(&item.kind, ItemKind::Consumable { category: Some(c), .. } if c == category))
+            .map(|(id, _)| id)
+            .collect();
+        ids.sort();
+        let mut pool = pool.clone();
+        pool.shuffle(&mut rng);
+        //items left without an appearance are always known
+        for (id, appearance) in ids.into_iter().zip(pool) {
+            appearances.insert(id.clone(), appearance);
+        }
+    }
+    appearances
+}
+
+//reshuffles the appearances when a run starts or the items file changes. What the player starts with is known
+pub fn assign_appearances(
+    run_rng: Res<RunRng>,
+    items: Res<Items>,
+    mut identification: ResMut<Identification>,
+    players: Query<&Inventory, With<Player>>,
+) {
+    let new_run = identification.seed != Some(run_rng.seed);
+    if items.items.is_empty() || (!new_run && !items.is_changed()) {
+        return;
+    }
+    identification.appearances = shuffle_appearances(&items, run_rng.seed);
+    identification.seed = Some(run_rng.seed);
+    if new_run {
+        identification.known = players
+            .iter()
+            .flat_map(|inventory| inventory.stacks.iter().map(|stack| stack.id.clone()))
+            .collect();
+    }
+}
+
+//sent to use one item of an inventory slot
+#[derive(Event)]
+pub struct UseItemEvent {
+    pub entity: Entity,
+    pub inventory_slot: usize,
+}
+
+//The components of the player a consumable can change
+type ConsumableUser = (
+    &'static mut Inventory,
+    &'static mut Position,
+    &'static Faction,
+    &'static mut Health,
+    &'static MaxHealth,
+    Option<&'static mut ClassResource>,
+);
+
+//every creature but the player
+type OtherCreatures = (With<Health>, Without<Player>);
+
+//The creatures a consumable can hurt
+#[derive(SystemParam)]
+pub struct ConsumableTargets<'w, 's> {
+    creatures: Query<'w, 's, (Entity, &'static Position, &'static Faction), OtherCreatures>,
+    faction_table: Res<'w, FactionTable>,
+}
+
+//The item definitions and which of them the player knows
+#[derive(SystemParam)]
+pub struct KnownItems<'w> {
+    items: Res<'w, Items>,
+    identification: ResMut<'w, Identification>,
+}
+
+//What a used consumable sends out
+#[derive(SystemParam)]
+pub struct ConsumableOutcomes<'w> {
+    attack_events: EventWriter<'w, AttackEvent>,
+    status_events: EventWriter<'w, ApplyStatusEvent>,
+    log: ResMut<'w, MessageLog>,
+}
+
+pub fn use_consumables(
+    mut use_events: EventReader<UseItemEvent>,
+    mut users: Query<ConsumableUser, With<Player>>,
+    targets: ConsumableTargets,
+    known_items: KnownItems,
+    outcomes: ConsumableOutcomes,
+    mut game_world: ResMut<GameWorld>,
+    mut run_rng: ResMut<RunRng>,
+) {
+    let ConsumableTargets {
+        creatures: targets,
+        faction_table,
+    } = targets;
+    let KnownItems {
+        items,
+        mut identification,
+    } = known_items;
+    let ConsumableOutcomes {
+        mut attack_events,
+        mut status_events,
+        mut log,
+    } = outcomes;
+    for event in use_events.iter() {
+        let Ok((mut inventory, mut pos, faction, mut health, max_health, resource)) = users.get_mut(event.entity) else {
+            continue;
+        };
+        let Some(id) = inventory.stacks.get(event.inventory_slot).map(|stack| stack.id.clone()) else {
+            continue;
+        };
+        let Some(ItemKind::Consumable { effect, .. }) = items.get(&id).map(|item| &item.kind) else {
+            continue;
+        };
+        let name = identification.name(&items, &id);
+        inventory.take(event.inventory_slot, 1);
+        match effect {
+            ConsumableEffect::Heal { amount } => {
+                health.0 = (health.0 + amount).min(max_health.0);
+            }
+            ConsumableEffect::RestoreResource { amount } => {
+                if let Some(mut resource) = resource {
+                    resource.current = (resource.current + amount).min(resource.max);
+                }
+            }
+            ConsumableEffect::Teleport => {
+                if let Some(tile) = game_world.grid(pos.2).walkable_tiles().choose(&mut run_rng.rng) {
+                    *pos = Position::from_tile(*tile, pos.2);
+                }
+            }
+            ConsumableEffect::Mapping => {
+                game_world.floors[pos.2].explore_all();
+            }
+            ConsumableEffect::Status(status) => {
+                status_events.send(ApplyStatusEvent {
+                    target: event.entity,
+                    source: Some(event.entity),
+                    status: *status,
+                });
+            }
+            ConsumableEffect::Fire { rolls, die, radius } => {
+                for (target, target_pos, target_faction) in targets.iter() {
+                    if target_pos.2 == pos.2
+                        && pos.distance(target_pos) <= *radius * TILE_SIZE
+                        && faction_table.is_hostile(*faction, *target_faction)
+                    {
+                        attack_events.send(AttackEvent {
+                            attacker: event.entity,
+                            target,
+                            damage: Some(roll_damage(*rolls, *die, &mut run_rng.rng)),
+                            damage_type: DamageType::Fire,
+                        });
+                    }
+                }
+            }
+            ConsumableEffect::Identify => {
+                let unknown : Vec<String> = inventory
+                    .stacks
+                    .iter()
+                    .map(|stack| stack.id.clone())
+                    .filter(|id| !identification.is_known(id))
+                    .collect();
+                if let Some(identified) = unknown.choose(&mut run_rng.rng) {
+                    log.add(
+                        MessageKind::Info,
+                        format!("The {} is a {}", identification.name(&items, identified), items.name(identified)),
+                    );
+                    identification.identify(identified);
+                }
+            }
+        }
+        if identification.identify(&id) {
+            log.add(MessageKind::Info, format!("The {} was a {}", name, items.name(&id)));
+        } else {
+            log.add(MessageKind::Info, format!("You use the {}", name));
+        }
+    }
+}
+
+pub struct ConsumablesPlugin;
+
+impl Plugin for ConsumablesPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Identification>()
+            .add_event::<UseItemEvent>()
+            .add_systems(
+                Update,
+                (assign_appearances, use_consumables)
+                    .chain()
+                    .before(apply_status_effects)
+                    .before(resolve_attacks)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
@@ -1,13 +1,22 @@
 use std::default;
 
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-use crate::{position::Position, actors::Actor};
+use crate::{position::Position, actors::Actor, bestiary::Bestiary, ai::Behavior, faction::Faction, player::Player, projectiles::RangedAttack, equipment::EquipmentSlot};
 
 #[derive(Component,Default)]
 //Corresponds to the creature's current health
 pub struct Health(pub u32);
 
+#[derive(Component,Default)]
+//Corresponds to the creature's max health, computed from its archetype and level
+pub struct MaxHealth(pub u32);
+
+#[derive(Component,Default)]
+//Marks creatures that are controlled by the game rather than the player
+pub struct Monster;
+
 #[derive(Component)]
 //Corresponds to the creature's level
 pub struct Level(
@@ -22,7 +31,7 @@ impl default::Default for Level {
 
 
 
-#[derive(Component,Default)]
+#[derive(Component,Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum CreatureType {
     #[default]
     Human,
@@ -44,6 +53,8 @@ pub enum CreatureType {
     DeepDwarf,
 }
 
+//Loaded from assets/data/bestiary.bestiary.ron, see bestiary.rs
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct CreatureArchetype {
     //The creature's max health
     pub max_health: u32,
@@ -56,26 +67,95 @@ pub struct CreatureArchetype {
     //Calculate the max health based on the creature's level on the formula: actual_max_health = floor(max_health * hp_scaling ^ level)
     pub hp_scaling: f32,
     //String for the creature's type's name
-    pub type_name: &'static str,
+    pub type_name: String,
     //String for the creature's type's sprite. Temporary until I figure out how to animated sprites
-    pub sprite: &'static str,
+    pub sprite: String,
+    //Free form tags used by other systems, e.g. "undead", "flying", "playable"
+    #[serde(default)]
+    pub tags: Vec<String>,
+    //Names of the abilities the creature can use
+    #[serde(default)]
+    pub abilities: Vec<String>,
+    //How the creature's AI acts, see ai.rs
+    #[serde(default)]
+    pub behavior: Behavior,
+    //Who the creature sides with, see faction.rs
+    #[serde(default)]
+    pub faction: Faction,
+    //Attack from afar used by the creature's AI, see projectiles.rs
+    #[serde(default)]
+    pub ranged: Option<RangedAttack>,
+    //Items worn by humanoid creatures, by id from the items file
+    #[serde(default)]
+    pub equipment: Vec<(EquipmentSlot, String)>,
+}
+
+//The old hard coded archetype, used until the bestiary is loaded or when a creature is missing from it
+impl default::Default for CreatureArchetype {
+    fn default() -> Self {
+        CreatureArchetype {
+            max_health: 10,
+            attack: 1,
+            attack_rolls: 1,
+            attack_scaling: 1.1,
+            hp_scaling: 1.1,
+            type_name: "Human".to_string(),
+            sprite: "player/base/demigod_male.png".to_string(),
+            tags: Vec::new(),
+            abilities: Vec::new(),
+            behavior: Behavior::default(),
+            faction: Faction::Townsfolk,
+            ranged: None,
+            equipment: Vec::new(),
+        }
+    }
+}
 
+impl CreatureArchetype {
+    pub fn max_health_at_level(&self, level : u32) -> u32 {
+        (self.max_health as f32 * self.hp_scaling.powi(level as i32)).floor() as u32
+    }
+    //the size of a single attack die at the given level
+    pub fn attack_die_at_level(&self, level : u32) -> u32 {
+        (self.attack as f32 * self.attack_scaling.powi(level as i32)).floor() as u32
+    }
+    pub fn has_tag(&self, tag : &str) -> bool {
+        self.tags.iter().any(|t| t == tag)
+    }
 }
 
 impl CreatureType {
-    pub fn get_stats(&self) -> &'static CreatureArchetype {
-        match self {
-            _ => &CreatureArchetype {
-                max_health: 10,
-                attack: 1,
-                attack_rolls: 1,
-                attack_scaling: 1.1,
-                hp_scaling: 1.1,
-                type_name: "Human",
-                sprite: "player/base/demigod_male.png",
-            },
-        }
-        
+    pub const ALL: [CreatureType; 17] = [
+        CreatureType::Human,
+        CreatureType::Elf,
+        CreatureType::Dwarf,
+        CreatureType::Orc,
+        CreatureType::Troll,
+        CreatureType::Goblin,
+        CreatureType::Skeleton,
+        CreatureType::Zombie,
+        CreatureType::Vampire,
+        CreatureType::Werewolf,
+        CreatureType::Dragon,
+        CreatureType::Demon,
+        CreatureType::Angel,
+        CreatureType::Devil,
+        CreatureType::God,
+        CreatureType::DeepElf,
+        CreatureType::DeepDwarf,
+    ];
+
+    pub fn get_stats<'a>(&self, bestiary : &'a Bestiary) -> &'a CreatureArchetype {
+        bestiary.get(*self)
+    }
+}
+
+//the archetype a creature actually uses: its bestiary entry, changed by the player's class for the player
+pub fn effective_archetype(creature_type : CreatureType, player : Option<&Player>, bestiary : &Bestiary) -> CreatureArchetype {
+    let archetype = creature_type.get_stats(bestiary);
+    match player {
+        Some(player) => player.class.apply(archetype),
+        None => archetype.clone(),
     }
 }
 
@@ -87,4 +167,3 @@ pub struct CreatureBundle {
     pub sp_bundle: SpriteBundle,
     pub actor: Actor,
 }
-
@@ -0,0 +1,77 @@
+use std::marker::PhantomData;
+
+use bevy::asset::{Asset, AssetLoader, LoadContext, LoadedAsset};
+use bevy::prelude::*;
+use bevy::utils::BoxedFuture;
+use serde::de::DeserializeOwned;
+
+//Game data (creatures, spells, ...) lives in ron files under assets/data so it can be tuned without recompiling.
+//Every kind of data file gets its own double extension (e.g. "bestiary.ron") so bevy knows which loader to use.
+pub trait RonAsset: Asset + DeserializeOwned {
+    const EXTENSIONS: &'static [&'static str];
+}
+
+//A single loader that works for any RonAsset, register it with app.init_asset_loader::<RonAssetLoader<T>>()
+pub struct RonAssetLoader<T: RonAsset> {
+    _marker: PhantomData<T>,
+}
+
+impl<T: RonAsset> Default for RonAssetLoader<T> {
+    fn default() -> Self {
+        RonAssetLoader {
+            _marker: PhantomData,
+        }
+    }
+}
+
+impl<T: RonAsset> AssetLoader for RonAssetLoader<T> {
+    fn load<'a>(
+        &'a self,
+        bytes: &'a [u8],
+        load_context: &'a mut LoadContext,
+    ) -> BoxedFuture<'a, Result<(), bevy::asset::Error>> {
+        Box::pin(async move {
+            let asset = ron::de::from_bytes::<T>(bytes)?;
+            load_context.set_default_asset(LoadedAsset::new(asset));
+            Ok(())
+        })
+    }
+
+    fn extensions(&self) -> &[&str] {
+        T::EXTENSIONS
+    }
+}
+
+//A resource holding the contents of a data file, kept up to date by refresh_ron_resource
+pub trait RonResource: Resource {
+    type Asset: RonAsset;
+    fn handle(&self) -> &Handle<Self::Asset>;
+    //copy the (re)loaded file into the resource
+    fn refresh(&mut self, asset : &Self::Asset);
+}
+
+//refreshes the resource every time its file is (re)loaded, so the data can be tuned while the game runs.
+//Register it with app.add_systems(Update, refresh_ron_resource::<R>)
+pub fn refresh_ron_resource<R: RonResource>(
+    mut asset_events: EventReader<AssetEvent<R::Asset>>,
+    assets: Res<Assets<R::Asset>>,
+    mut resource: ResMut<R>,
+) {
+    let mut changed = false;
+    for event in asset_events.iter() {
+        match event {
+            AssetEvent::Created { handle } | AssetEvent::Modified { handle } => {
+                if handle == resource.handle() {
+                    changed = true;
+                }
+            }
+            AssetEvent::Removed { .. } => {}
+        }
+    }
+    if !changed {
+        return;
+    }
+    if let Some(asset) = assets.get(resource.handle()) {
+        resource.refresh(asset);
+    }
+}
@@ -0,0 +1,193 @@
+use std::collections::HashMap;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::faction::ReputationEvent;
+use crate::game_state::GameState;
+use crate::items::{Inventory, ItemKind, ItemStack, Items};
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::{Player, PlayerClass};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum EquipmentSlot {
+    Weapon,
+    OffHand,
+    Armor,
+    Helm,
+    LeftRing,
+    RightRing,
+    Amulet,
+}
+
+impl EquipmentSlot {
+    pub const ALL: [EquipmentSlot; 7] = [
+        EquipmentSlot::Weapon,
+        EquipmentSlot::OffHand,
+        EquipmentSlot::Armor,
+        EquipmentSlot::Helm,
+        EquipmentSlot::LeftRing,
+        EquipmentSlot::RightRing,
+        EquipmentSlot::Amulet,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            EquipmentSlot::Weapon => "Weapon",
+            EquipmentSlot::OffHand => "Off-hand",
+            EquipmentSlot::Armor => "Armor",
+            EquipmentSlot::Helm => "Helm",
+            EquipmentSlot::LeftRing => "Left ring",
+            EquipmentSlot::RightRing => "Right ring",
+            EquipmentSlot::Amulet => "Amulet",
+        }
+    }
+}
+
+//Where an item can be worn, set per item in the items file
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SlotKind {
+    Weapon,
+    OffHand,
+    Armor,
+    Helm,
+    //fits either ring slot
+    Ring,
+    Amulet,
+}
+
+impl SlotKind {
+    //the slots the item can go in, the first empty one is used
+    pub fn slots(&self) -> &'static [EquipmentSlot] {
+        match self {
+            SlotKind::Weapon => &[EquipmentSlot::Weapon],
+            SlotKind::OffHand => &[EquipmentSlot::OffHand],
+            SlotKind::Armor => &[EquipmentSlot::Armor],
+            SlotKind::Helm => &[EquipmentSlot::Helm],
+            SlotKind::Ring => &[EquipmentSlot::LeftRing, EquipmentSlot::RightRing],
+            SlotKind::Amulet => &[EquipmentSlot::Amulet],
+        }
+    }
+}
+
+//The items worn by the player or a humanoid monster, by id. Their stats are added in combat_stats.rs
+#[derive(Component, Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct Equipment {
+    pub slots: HashMap<EquipmentSlot, String>,
+}
+
+impl Equipment {
+    pub fn from_list(list : &[(EquipmentSlot, String)]) -> Self {
+        Equipment {
+            slots: list.iter().cloned().collect(),
+        }
+    }
+    pub fn for_class(class : PlayerClass) -> Self {
+        Equipment {
+            slots: class
+                .definition()
+                .starting_equipment
+                .iter()
+                .map(|(slot, id)| (*slot, id.to_string()))
+                .collect(),
+        }
+    }
+}
+
+//sent to wear the item in an inventory slot
+#[derive(Event)]
+pub struct EquipEvent {
+    pub entity: Entity,
+    pub inventory_slot: usize,
+}
+
+//sent to put a worn item back in the inventory
+#[derive(Event)]
+pub struct UnequipEvent {
+    pub entity: Entity,
+    pub slot: EquipmentSlot,
+}
+
+//wearing a faction's items changes the player's reputation with it, taking them off undoes it
+fn send_item_reputation(items : &Items, id : &str, sign : i32, reputation_events : &mut EventWriter<ReputationEvent>) {
+    if let Some(ItemKind::Equipment { reputation, .. }) = items.get(id).map(|item| &item.kind) {
+        for (faction, amount) in reputation.iter() {
+            reputation_events.send(ReputationEvent {
+                faction: *faction,
+                amount: amount * sign,
+            });
+        }
+    }
+}
+
+pub fn equip_items(
+    mut equip_events: EventReader<EquipEvent>,
+    mut unequip_events: EventReader<UnequipEvent>,
+    mut holders: Query<(&mut Inventory, &mut Equipment, Option<&Player>)>,
+    items: Res<Items>,
+    mut reputation_events: EventWriter<ReputationEvent>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in equip_events.iter() {
+        let Ok((mut inventory, mut equipment, player)) = holders.get_mut(event.entity) else {
+            continue;
+        };
+        let Some(id) = inventory.stacks.get(event.inventory_slot).map(|stack| stack.id.clone()) else {
+            continue;
+        };
+        let Some(ItemKind::Equipment { slot: kind, .. }) = items.get(&id).map(|item| &item.kind) else {
+            continue;
+        };
+        let slots = kind.slots();
+        let slot = *slots
+            .iter()
+            .find(|slot| !equipment.slots.contains_key(slot))
+            .unwrap_or(&slots[0]);
+        inventory.take(event.inventory_slot, 1);
+        if let Some(previous) = equipment.slots.insert(slot, id.clone()) {
+            if let Some(left) = inventory.add(ItemStack::new(&previous, 1), items.max_stack(&previous)) {
+                //no room for what was worn, undo
+                equipment.slots.insert(slot, left.id);
+                inventory.add(ItemStack::new(&id, 1), items.max_stack(&id));
+                if player.is_some() {
+                    log.add(MessageKind::Warning, "Your inventory is full");
+                }
+                continue;
+            }
+            if player.is_some() {
+                send_item_reputation(&items, &previous, -1, &mut reputation_events);
+            }
+        }
+        if player.is_some() {
+            send_item_reputation(&items, &id, 1, &mut reputation_events);
+        }
+    }
+    for event in unequip_events.iter() {
+        let Ok((mut inventory, mut equipment, player)) = holders.get_mut(event.entity) else {
+            continue;
+        };
+        let Some(id) = equipment.slots.get(&event.slot).cloned() else {
+            continue;
+        };
+        if inventory.add(ItemStack::new(&id, 1), items.max_stack(&id)).is_some() {
+            if player.is_some() {
+                log.add(MessageKind::Warning, "Your inventory is full");
+            }
+            continue;
+        }
+        equipment.slots.remove(&event.slot);
+        if player.is_some() {
+            send_item_reputation(&items, &id, -1, &mut reputation_events);
+        }
+    }
+}
+
+pub struct EquipmentPlugin;
+
+impl Plugin for EquipmentPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<EquipEvent>()
+            .add_event::<UnequipEvent>()
+            .add_systems(Update, equip_items.run_if(in_state(GameState::Playing)));
+    }
+}
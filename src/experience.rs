@@ -0,0 +1,309 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bestiary::Bestiary;
+use crate::combat::{handle_deaths, resolve_attacks, DeathEvent};
+use crate::creature::{effective_archetype, CreatureType, Health, Level, MaxHealth};
+use crate::game_state::GameState;
+use crate::main_menu_plugin::menu::{HOVERED_BUTTON, NORMAL_BUTTON, TEXT_COLOR};
+use crate::player::Player;
+use crate::util::despawn_screen;
+
+//xp for killing a creature is XP_PER_KILL_LEVEL times its level
+pub const XP_PER_KILL_LEVEL: u32 = 10;
+//going from level n to n + 1 takes XP_CURVE_BASE * n^2 xp
+pub const XP_CURVE_BASE: u32 = 20;
+//what a stat point is worth when spent
+pub const HEALTH_PER_POINT: u32 = 5;
+pub const ATTACK_PER_POINT: u32 = 1;
+//seconds the level up notification stays on screen
+pub const NOTIFICATION_TIME: f32 = 3.0;
+
+//Experience towards the next level, only creatures with this component gain xp
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Experience {
+    pub xp: u32,
+    //stat points earned on level up and not spent yet
+    pub unspent_points: u32,
+}
+
+//Stats bought with the points earned on level up, added on top of the archetype's
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct StatAllocation {
+    pub bonus_health: u32,
+    pub bonus_attack: u32,
+}
+
+#[derive(Event)]
+pub struct LevelUpEvent {
+    pub entity: Entity,
+    pub new_level: u32,
+}
+
+pub fn xp_for_kill(victim_level : u32) -> u32 {
+    XP_PER_KILL_LEVEL * victim_level.max(1)
+}
+
+//never 0, level_up takes levels while the player has this much
+pub fn xp_to_next_level(level : u32) -> u32 {
+    let level = level.max(1);
+    XP_CURVE_BASE * level * level
+}
+
+pub fn award_experience(
+    mut death_events: EventReader<DeathEvent>,
+    victims: Query<&Level>,
+    mut killers: Query<&mut Experience>,
+) {
+    for event in death_events.iter() {
+        let Some(killer) = event.killer.filter(|killer| *killer != event.entity) else {
+            continue;
+        };
+        let Ok(victim_level) = victims.get(event.entity) else {
+            continue;
+        };
+        if let Ok(mut experience) = killers.get_mut(killer) {
+            experience.xp += xp_for_kill(victim_level.0);
+        }
+    }
+}
+
+type Leveling = (Entity, &'static CreatureType, &'static mut Level, &'static mut Experience, &'static mut MaxHealth, &'static mut Health, Option<&'static StatAllocation>, Option<&'static Player>);
+
+//level up every creature with enough xp, max health is recomputed through the archetype's hp_scaling.
+//Attack doesn't need to be stored, combat reads it from the archetype at the creature's level
+pub fn level_up(
+    mut creatures: Query<Leveling>,
+    bestiary: Res<Bestiary>,
+    mut level_up_events: EventWriter<LevelUpEvent>,
+) {
+    for (entity, creature_type, mut level, mut experience, mut max_health, mut health, allocation, player) in creatures.iter_mut() {
+        if experience.xp < xp_to_next_level(level.0) {
+            continue;
+        }
+        while experience.xp >= xp_to_next_level(level.0) {
+            experience.xp -= xp_to_next_level(level.0);
+            level.0 += 1;
+            experience.unspent_points += 1;
+            level_up_events.send(LevelUpEvent {
+                entity,
+                new_level: level.0,
+            });
+        }
+        let bonus_health = allocation.map_or(0, |allocation| allocation.bonus_health);
+        let new_max_health = effective_archetype(*creature_type, player, &bestiary).max_health_at_level(level.0) + bonus_health;
+        health.0 += new_max_health.saturating_sub(max_health.0);
+        max_health.0 = new_max_health;
+    }
+}
+
+// Tag component used to tag the level up notification text
+#[derive(Component)]
+pub struct LevelUpNotification(pub Timer);
+
+// Tag component used to tag the stat allocation panel
+#[derive(Component)]
+pub struct StatAllocationPanel;
+
+// The stat a button of the allocation panel spends a point on
+#[derive(Component, Clone, Copy)]
+pub enum StatChoice {
+    Health,
+    Attack,
+}
+
+pub fn show_level_up_notification(
+    mut level_up_events: EventReader<LevelUpEvent>,
+    players: Query<(), With<Player>>,
+    notifications: Query<Entity, With<LevelUpNotification>>,
+    mut commands: Commands,
+) {
+    for event in level_up_events.iter() {
+        if !players.contains(event.entity) {
+            continue;
+        }
+        //only keep the latest notification
+        for entity in notifications.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        commands.spawn((
+            TextBundle::from_section(
+                format!("Level up! You are now level {}", event.new_level),
+                TextStyle {
+                    font_size: 40.0,
+                    color: Color::GOLD,
+                    ..default()
+                },
+            )
+            .with_style(Style {
+                position_type: PositionType::Absolute,
+                top: Val::Percent(20.0),
+                left: Val::Percent(35.0),
+                ..default()
+            }),
+            LevelUpNotification(Timer::from_seconds(NOTIFICATION_TIME, TimerMode::Once)),
+        ));
+    }
+}
+
+pub fn fade_level_up_notification(
+    time: Res<Time>,
+    mut notifications: Query<(Entity, &mut LevelUpNotification, &mut Text)>,
+    mut commands: Commands,
+) {
+    for (entity, mut notification, mut text) in notifications.iter_mut() {
+        notification.0.tick(time.delta());
+        if notification.0.finished() {
+            commands.entity(entity).despawn_recursive();
+        } else {
+            text.sections[0].style.color.set_a(notification.0.percent_left());
+        }
+    }
+}
+
+//show the allocation panel while the player has points to spend
+pub fn update_stat_allocation_panel(
+    players: Query<&Experience, With<Player>>,
+    panels: Query<Entity, With<StatAllocationPanel>>,
+    mut commands: Commands,
+) {
+    let Ok(experience) = players.get_single() else {
+        return;
+    };
+    let panel = panels.iter().next();
+    if experience.unspent_points == 0 {
+        if let Some(panel) = panel {
+            commands.entity(panel).despawn_recursive();
+        }
+        return;
+    }
+    if panel.is_some() {
+        return;
+    }
+    let button_style = Style {
+        width: Val::Px(180.0),
+        height: Val::Px(50.0),
+        margin: UiRect::all(Val::Px(10.0)),
+        justify_content: JustifyContent::Center,
+        align_items: AlignItems::Center,
+        ..default()
+    };
+    let button_text_style = TextStyle {
+        font_size: 25.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(20.0),
+                    left: Val::Percent(30.0),
+                    flex_direction: FlexDirection::Column,
+                    align_items: AlignItems::Center,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            StatAllocationPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                "Spend a stat point",
+                button_text_style.clone(),
+            ));
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        align_items: AlignItems::Center,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for (choice, text) in [
+                        (StatChoice::Health, format!("+{} Health", HEALTH_PER_POINT)),
+                        (StatChoice::Attack, format!("+{} Attack", ATTACK_PER_POINT)),
+                    ] {
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                choice,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section(text, button_text_style.clone()));
+                            });
+                    }
+                });
+        });
+}
+
+pub fn stat_choice_buttons(
+    mut interaction_query: Query<(&Interaction, &StatChoice, &mut BackgroundColor), Changed<Interaction>>,
+    mut players: Query<(&mut Experience, &mut StatAllocation, &mut MaxHealth, &mut Health), With<Player>>,
+) {
+    for (interaction, choice, mut color) in interaction_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            _ => NORMAL_BUTTON.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Ok((mut experience, mut allocation, mut max_health, mut health)) = players.get_single_mut() else {
+            continue;
+        };
+        if experience.unspent_points == 0 {
+            continue;
+        }
+        experience.unspent_points -= 1;
+        match choice {
+            StatChoice::Health => {
+                allocation.bonus_health += HEALTH_PER_POINT;
+                max_health.0 += HEALTH_PER_POINT;
+                health.0 += HEALTH_PER_POINT;
+            }
+            StatChoice::Attack => {
+                allocation.bonus_attack += ATTACK_PER_POINT;
+            }
+        }
+    }
+}
+
+pub struct ExperiencePlugin;
+
+impl Plugin for ExperiencePlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<LevelUpEvent>()
+            .add_systems(
+                Update,
+                award_experience
+                    //the victims' level needs to be read before they are despawned
+                    .after(resolve_attacks)
+                    .before(handle_deaths)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (
+                    level_up,
+                    show_level_up_notification,
+                    fade_level_up_notification,
+                    update_stat_allocation_panel,
+                    stat_choice_buttons,
+                )
+                    .chain()
+                    .after(award_experience)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<LevelUpNotification>)
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<StatAllocationPanel>);
+    }
+}
@@ -0,0 +1,86 @@
+use bevy::prelude::*;
+
+use crate::game_plugin::game::RunStarted;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::Grid;
+use crate::player::Player;
+use crate::position::Position;
+use crate::tiles::Tile;
+
+//how far (in tiles) the player sees to explore the floor
+pub const SIGHT_RADIUS: i32 = 8;
+
+//marks the tiles the player can see as explored, only when the player moves to another tile
+pub fn explore_around_player(
+    players: Query<&Position, With<Player>>,
+    mut game_world: ResMut<GameWorld>,
+    mut run_started: EventReader<RunStarted>,
+    mut last_tile: Local<Option<((usize, usize), usize)>>,
+) {
+    //a new run has a new world to explore, even from the same tile
+    if !run_started.is_empty() {
+        run_started.clear();
+        *last_tile = None;
+    }
+    let Ok(pos) = players.get_single() else {
+        return;
+    };
+    let tile = pos.to_tile();
+    if *last_tile == Some((tile, pos.2)) {
+        return;
+    }
+    *last_tile = Some((tile, pos.2));
+    let grid = game_world.grid(pos.2).clone();
+    let floor = &mut game_world.bypass_change_detection().floors[pos.2];
+    let mut newly_explored = false;
+    for dy in -SIGHT_RADIUS..=SIGHT_RADIUS {
+        for dx in -SIGHT_RADIUS..=SIGHT_RADIUS {
+            let (x, y) = (tile.0 as i32 + dx, tile.1 as i32 + dy);
+            if dx * dx + dy * dy > SIGHT_RADIUS * SIGHT_RADIUS || !Grid::in_bounds(x, y) {
+                continue;
+            }
+            let seen = (x as usize, y as usize);
+            if !floor.is_explored(seen) && grid.has_line_of_sight(tile, seen) {
+                newly_explored |= floor.explore(seen);
+            }
+        }
+    }
+    //only flag the world as changed when something new was seen
+    if newly_explored {
+        game_world.set_changed();
+    }
+}
+
+//unexplored tiles stay hidden
+pub fn update_tile_visibility(game_world: Res<GameWorld>, mut tiles: Query<(Ref<Tile>, &mut Visibility)>) {
+    for (tile, mut visibility) in tiles.iter_mut() {
+        if !game_world.is_changed() && !tile.is_added() {
+            continue;
+        }
+        let Some(floor) = game_world.floors.get(tile.level) else {
+            continue;
+        };
+        let wanted = if floor.is_explored((tile.x as usize, tile.y as usize)) {
+            Visibility::Inherited
+        } else {
+            Visibility::Hidden
+        };
+        if *visibility != wanted {
+            *visibility = wanted;
+        }
+    }
+}
+
+pub struct ExplorationPlugin;
+
+impl Plugin for ExplorationPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            (explore_around_player, update_tile_visibility)
+                .chain()
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
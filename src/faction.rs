@@ -0,0 +1,282 @@
+use std::collections::{HashMap, HashSet};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::{handle_deaths, resolve_attacks, AttackEvent, DeathEvent};
+use crate::game_state::GameState;
+use crate::player::Player;
+
+//the player's reputation goes from MIN_REPUTATION to MAX_REPUTATION
+pub const MIN_REPUTATION: i32 = -100;
+pub const MAX_REPUTATION: i32 = 100;
+//under this the faction attacks the player on sight, over FRIENDLY_REPUTATION it won't even if attacked
+pub const HOSTILE_REPUTATION: i32 = -20;
+pub const FRIENDLY_REPUTATION: i32 = 20;
+//reputation lost when attacking or killing a member of a faction
+pub const ATTACK_REPUTATION_LOSS: i32 = 5;
+pub const KILL_REPUTATION_LOSS: i32 = 10;
+//reputation gained with the enemies of a faction when killing one of its members
+pub const KILL_REPUTATION_GAIN: i32 = 2;
+
+//Set per creature type in the bestiary
+#[derive(Component, Debug, Clone, Copy, PartialEq, Eq, Hash, Default, Serialize, Deserialize)]
+pub enum Faction {
+    Player,
+    //humans, elves and dwarves living in towns
+    Townsfolk,
+    //goblins, orcs and trolls
+    Greenskins,
+    Undead,
+    #[default]
+    Beasts,
+    //demons and devils
+    Hell,
+    //angels and gods
+    Heaven,
+    //deep elves and deep dwarves
+    Underdark,
+}
+
+impl Faction {
+    pub const ALL: [Faction; 8] = [
+        Faction::Player,
+        Faction::Townsfolk,
+        Faction::Greenskins,
+        Faction::Undead,
+        Faction::Beasts,
+        Faction::Hell,
+        Faction::Heaven,
+        Faction::Underdark,
+    ];
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stance {
+    Hostile,
+    Neutral,
+    Friendly,
+}
+
+//sent to change the player's reputation with a faction, positive amounts improve it
+#[derive(Event)]
+pub struct ReputationEvent {
+    pub faction: Faction,
+    pub amount: i32,
+}
+
+#[derive(Resource)]
+pub struct FactionTable {
+    //pairs of factions that fight each other on sight, stored both ways
+    hostile_pairs: HashSet<(Faction, Faction)>,
+    //the player's standing with every faction
+    pub reputation: HashMap<Faction, i32>,
+}
+
+impl Default for FactionTable {
+    fn default() -> Self {
+        use Faction::*;
+        let mut table = FactionTable {
+            hostile_pairs: HashSet::new(),
+            reputation: HashMap::new(),
+        };
+        for (a, b) in [
+            (Townsfolk, Greenskins),
+            (Townsfolk, Undead),
+            (Townsfolk, Beasts),
+            (Townsfolk, Hell),
+            (Greenskins, Underdark),
+            (Greenskins, Beasts),
+            (Undead, Heaven),
+            (Hell, Heaven),
+            (Heaven, Underdark),
+        ] {
+            table.set_hostile(a, b);
+        }
+        //monsters start out hating the player, angels and gods wait to see what they do
+        for (faction, reputation) in [
+            (Townsfolk, 50),
+            (Greenskins, -50),
+            (Undead, -80),
+            (Beasts, -50),
+            (Hell, -50),
+            (Heaven, 0),
+            (Underdark, -30),
+        ] {
+            table.reputation.insert(faction, reputation);
+        }
+        table
+    }
+}
+
+impl FactionTable {
+    pub fn set_hostile(&mut self, a : Faction, b : Faction) {
+        self.hostile_pairs.insert((a, b));
+        self.hostile_pairs.insert((b, a));
+    }
+
+    pub fn reputation(&self, faction : Faction) -> i32 {
+        *self.reputation.get(&faction).unwrap_or(&0)
+    }
+
+    pub fn shift_reputation(&mut self, faction : Faction, amount : i32) {
+        if faction == Faction::Player {
+            return;
+        }
+        let reputation = self.reputation.entry(faction).or_insert(0);
+        *reputation = (*reputation + amount).clamp(MIN_REPUTATION, MAX_REPUTATION);
+    }
+
+    pub fn stance(&self, a : Faction, b : Faction) -> Stance {
+        if a == b {
+            return Stance::Friendly;
+        }
+        //the player's relations depend on their reputation
+        if a == Faction::Player || b == Faction::Player {
+            let other = if a == Faction::Player { b } else { a };
+            let reputation = self.reputation(other);
+            return if reputation < HOSTILE_REPUTATION {
+                Stance::Hostile
+            } else if reputation > FRIENDLY_REPUTATION {
+                Stance::Friendly
+            } else {
+                Stance::Neutral
+            };
+        }
+        if self.hostile_pairs.contains(&(a, b)) {
+            Stance::Hostile
+        } else {
+            Stance::Neutral
+        }
+    }
+
+    pub fn is_hostile(&self, a : Faction, b : Faction) -> bool {
+        self.stance(a, b) == Stance::Hostile
+    }
+
+    //every faction that fights the given one
+    pub fn enemies_of(&self, faction : Faction) -> Vec<Faction> {
+        Faction::ALL
+            .into_iter()
+            .filter(|other| *other != Faction::Player && self.hostile_pairs.contains(&(faction, *other)))
+            .collect()
+    }
+}
+
+//attacking or killing a faction's members makes it angry, killing its members pleases its enemies
+pub fn reputation_from_combat(
+    mut attack_events: EventReader<AttackEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    factions: Query<&Faction>,
+    faction_table: Res<FactionTable>,
+    mut reputation_events: EventWriter<ReputationEvent>,
+) {
+    for event in attack_events.iter() {
+        if !players.contains(event.attacker) || event.attacker == event.target {
+            continue;
+        }
+        if let Ok(faction) = factions.get(event.target) {
+            reputation_events.send(ReputationEvent {
+                faction: *faction,
+                amount: -ATTACK_REPUTATION_LOSS,
+            });
+        }
+    }
+    for event in death_events.iter() {
+        if !event.killer.is_some_and(|killer| players.contains(killer)) {
+            continue;
+        }
+        if let Ok(faction) = factions.get(event.entity) {
+            reputation_events.send(ReputationEvent {
+                faction: *faction,
+                amount: -KILL_REPUTATION_LOSS,
+            });
+            for enemy in faction_table.enemies_of(*faction) {
+                reputation_events.send(ReputationEvent {
+                    faction: enemy,
+                    amount: KILL_REPUTATION_GAIN,
+                });
+            }
+        }
+    }
+}
+
+pub fn apply_reputation_events(
+    mut reputation_events: EventReader<ReputationEvent>,
+    mut faction_table: ResMut<FactionTable>,
+) {
+    for event in reputation_events.iter() {
+        faction_table.shift_reputation(event.faction, event.amount);
+    }
+}
+
+pub struct FactionPlugin;
+
+impl Plugin for FactionPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<FactionTable>()
+            .add_event::<ReputationEvent>()
+            .add_systems(
+                Update,
+                (reputation_from_combat, apply_reputation_events)
+                    .chain()
+                    //the dead need to be looked at before they are despawned
+                    .after(resolve_attacks)
+                    .before(handle_deaths)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_faction_is_friendly_with_itself() {
+        let table = FactionTable::default();
+        for faction in Faction::ALL {
+            assert_eq!(table.stance(faction, faction), Stance::Friendly);
+        }
+    }
+
+    #[test]
+    fn hostility_goes_both_ways() {
+        let table = FactionTable::default();
+        assert_eq!(table.stance(Faction::Townsfolk, Faction::Undead), Stance::Hostile);
+        assert_eq!(table.stance(Faction::Undead, Faction::Townsfolk), Stance::Hostile);
+        assert_eq!(table.stance(Faction::Undead, Faction::Hell), Stance::Neutral);
+    }
+
+    #[test]
+    fn the_players_stance_follows_reputation() {
+        let mut table = FactionTable::default();
+        table.reputation.insert(Faction::Heaven, HOSTILE_REPUTATION - 1);
+        assert_eq!(table.stance(Faction::Player, Faction::Heaven), Stance::Hostile);
+        assert_eq!(table.stance(Faction::Heaven, Faction::Player), Stance::Hostile);
+        table.reputation.insert(Faction::Heaven, HOSTILE_REPUTATION);
+        assert_eq!(table.stance(Faction::Player, Faction::Heaven), Stance::Neutral);
+        table.reputation.insert(Faction::Heaven, FRIENDLY_REPUTATION);
+        assert_eq!(table.stance(Faction::Player, Faction::Heaven), Stance::Neutral);
+        table.reputation.insert(Faction::Heaven, FRIENDLY_REPUTATION + 1);
+        assert_eq!(table.stance(Faction::Player, Faction::Heaven), Stance::Friendly);
+    }
+
+    #[test]
+    fn default_reputation_makes_towns_friendly_and_monsters_hostile() {
+        let table = FactionTable::default();
+        assert_eq!(table.stance(Faction::Player, Faction::Townsfolk), Stance::Friendly);
+        assert_eq!(table.stance(Faction::Player, Faction::Greenskins), Stance::Hostile);
+        assert_eq!(table.stance(Faction::Player, Faction::Heaven), Stance::Neutral);
+    }
+
+    #[test]
+    fn reputation_stays_in_bounds() {
+        let mut table = FactionTable::default();
+        table.shift_reputation(Faction::Townsfolk, 1000);
+        assert_eq!(table.reputation(Faction::Townsfolk), MAX_REPUTATION);
+        table.shift_reputation(Faction::Townsfolk, -1000);
+        assert_eq!(table.reputation(Faction::Townsfolk), MIN_REPUTATION);
+    }
+}
@@ -1,17 +1,108 @@
-use crate::grid::Grid;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
 
+use crate::grid::{Grid, GRID_SIZE};
 
-#[derive(Debug, Clone, PartialEq, Eq, Hash)]
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum FloorType {
     Dungeon,
     Town,
     BossRoom,
 }
 
+impl FloorType {
+    //every fifth floor is a town and every tenth a boss room. The first floor is always a dungeon
+    pub fn for_depth(depth : usize) -> FloorType {
+        if depth % 10 == 9 {
+            FloorType::BossRoom
+        } else if depth % 5 == 4 {
+            FloorType::Town
+        } else {
+            FloorType::Dungeon
+        }
+    }
+    pub fn name(&self) -> &'static str {
+        match self {
+            FloorType::Dungeon => "Dungeon",
+            FloorType::Town => "Town",
+            FloorType::BossRoom => "Boss Room",
+        }
+    }
+}
+
 #[derive(Clone, PartialEq, Eq, Hash)]
 pub struct Floor {
     pub grid : Grid,
     pub floor_type : FloorType,
     pub level : u32,
+    //tiles the player has seen (or mapped), indexed like the grid's tiles
+    pub explored : [[bool; GRID_SIZE]; GRID_SIZE],
 }
 
+impl Floor {
+    //see RunRng::floor_rng for the generator
+    pub fn generate(depth : usize, rng : &mut impl Rng) -> Self {
+        let floor_type = FloorType::for_depth(depth);
+        let mut grid = match floor_type {
+            FloorType::Dungeon => {
+                //alternate between caves and rooms to keep things varied
+                if depth.is_multiple_of(2) {
+                    Grid::new_cell_automata_grid(rng, 0.5, 5)
+                } else {
+                    Grid::new_room_based_grid(rng, 8, (5, 5), (20, 10))
+                }
+            }
+            FloorType::Town => Grid::new_room_based_grid(rng, 4, (8, 8), (16, 16)),
+            FloorType::BossRoom => Grid::new_room_based_grid(rng, 1, (30, 30), (40, 40)),
+        };
+        grid.place_stairs(rng);
+        Floor {
+            grid,
+            floor_type,
+            level: depth as u32,
+            explored: [[false; GRID_SIZE]; GRID_SIZE],
+        }
+    }
+
+    pub fn is_explored(&self, tile : (usize, usize)) -> bool {
+        self.explored[tile.1][tile.0]
+    }
+
+    //returns whether the tile wasn't explored yet
+    pub fn explore(&mut self, tile : (usize, usize)) -> bool {
+        let newly_explored = !self.explored[tile.1][tile.0];
+        self.explored[tile.1][tile.0] = true;
+        newly_explored
+    }
+
+    //reveals the whole floor, e.g. after reading a scroll of magic mapping
+    pub fn explore_all(&mut self) {
+        self.explored = [[true; GRID_SIZE]; GRID_SIZE];
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::run_rng::RunRng;
+
+    #[test]
+    fn same_seed_generates_the_same_floors() {
+        let first = RunRng::from_seed(1234);
+        let second = RunRng::from_seed(1234);
+        //a cave, rooms, a town and a boss room
+        for depth in [0, 1, 4, 9] {
+            let floor = Floor::generate(depth, &mut first.floor_rng(depth));
+            let again = Floor::generate(depth, &mut second.floor_rng(depth));
+            assert!(floor.grid == again.grid, "floor {} differs", depth);
+        }
+    }
+
+    #[test]
+    fn other_seeds_generate_other_floors() {
+        let floor = Floor::generate(0, &mut RunRng::from_seed(1).floor_rng(0));
+        let other = Floor::generate(0, &mut RunRng::from_seed(2).floor_rng(0));
+        assert!(floor.grid != other.grid);
+    }
+}
@@ -0,0 +1,339 @@
+use std::collections::BTreeMap;
+use std::fs::OpenOptions;
+use std::io::Write;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bestiary::Bestiary;
+use crate::character_creation::NewCharacter;
+use crate::combat::{handle_deaths, resolve_attacks, DamageEvent, DeathEvent};
+use crate::creature::{CreatureType, Level};
+use crate::game_state::{GameState, PlayingState};
+use crate::gold::Gold;
+use crate::hud::format_play_time;
+use crate::main_menu_plugin::menu::{HOVERED_BUTTON, NORMAL_BUTTON, TEXT_COLOR};
+use crate::player::Player;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::save_data::{delete_slot, CurrentSave};
+use crate::util::despawn_screen;
+
+//every finished run is appended to this file, one line each
+pub const RUN_HISTORY_PATH: &str = "saves/run_history.ron";
+
+//What the player did during the run, shown when they die
+#[derive(Resource, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct RunStats {
+    //kills by creature name
+    pub kills: BTreeMap<String, u32>,
+    //deepest floor reached, 0 for the first
+    pub max_depth: usize,
+    //gold and items picked up, see gold::pickup_gold and items::pickup_items
+    pub gold_found: u32,
+    pub items_found: u32,
+    //e.g. "Killed by the Goblin", set when the player dies
+    pub cause_of_death: String,
+}
+
+//A finished run, as written to the run history
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct RunRecord {
+    pub name: String,
+    pub class: String,
+    pub ancestry: String,
+    pub level: u32,
+    pub seed: u64,
+    pub cause_of_death: String,
+    pub depth: usize,
+    pub play_time_secs: u64,
+    pub gold: u32,
+    pub gold_found: u32,
+    pub items_found: u32,
+    pub kills: BTreeMap<String, u32>,
+    //seconds since the unix epoch
+    pub ended_at: u64,
+}
+
+pub fn track_depth(players: Query<&Position, With<Player>>, mut run_stats: ResMut<RunStats>) {
+    if let Ok(pos) = players.get_single() {
+        if pos.2 > run_stats.max_depth {
+            run_stats.max_depth = pos.2;
+        }
+    }
+}
+
+//read before the dead are despawned
+pub fn track_kills(
+    mut death_events: EventReader<DeathEvent>,
+    creatures: Query<&CreatureType>,
+    players: Query<(), With<Player>>,
+    bestiary: Res<Bestiary>,
+    mut run_stats: ResMut<RunStats>,
+) {
+    for event in death_events.iter() {
+        let killed_by_player = event.killer.is_some_and(|killer| players.contains(killer));
+        if !killed_by_player || players.contains(event.entity) {
+            continue;
+        }
+        let Ok(creature_type) = creatures.get(event.entity) else {
+            continue;
+        };
+        let name = creature_type.get_stats(&bestiary).type_name.clone();
+        *run_stats.kills.entry(name).or_insert(0) += 1;
+    }
+}
+
+//ends the run when the player dies, remembering what killed them
+pub fn detect_player_death(
+    mut damage_events: EventReader<DamageEvent>,
+    mut death_events: EventReader<DeathEvent>,
+    players: Query<(), With<Player>>,
+    creatures: Query<&CreatureType>,
+    bestiary: Res<Bestiary>,
+    mut run_stats: ResMut<RunStats>,
+    mut game_state: ResMut<NextState<GameState>>,
+) {
+    //the hit that killed the player is the last one they took
+    let last_hit = damage_events
+        .iter()
+        .filter(|event| players.contains(event.target))
+        .last()
+        .map(|event| event.damage_type);
+    for event in death_events.iter() {
+        if !players.contains(event.entity) {
+            continue;
+        }
+        let killer = event
+            .killer
+            .filter(|killer| *killer != event.entity)
+            .and_then(|killer| creatures.get(killer).ok());
+        run_stats.cause_of_death = match (killer, last_hit) {
+            (Some(creature_type), _) => format!("Killed by the {}", creature_type.get_stats(&bestiary).type_name),
+            //traps, lava and status effects hurt the player on their own behalf
+            (None, Some(damage_type)) => format!("Died of {} damage", damage_type.name()),
+            (None, None) => "Died".to_string(),
+        };
+        game_state.set(GameState::GameOver);
+    }
+}
+
+pub fn write_run_record(record : &RunRecord, path : &str) -> Result<(), String> {
+    let line = ron::ser::to_string(record).map_err(|error| format!("could not serialize the run: {}", error))?;
+    if let Some(folder) = std::path::Path::new(path).parent() {
+        std::fs::create_dir_all(folder).map_err(|error| format!("could not create {:?}: {}", folder, error))?;
+    }
+    let mut file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(|error| format!("could not open {}: {}", path, error))?;
+    writeln!(file, "{}", line).map_err(|error| format!("could not write {}: {}", path, error))
+}
+
+// Tag component used to tag entities added on the game over screen
+#[derive(Component)]
+pub struct OnGameOverScreen;
+
+// What a button of the game over screen does
+#[derive(Component, Clone, Copy)]
+pub enum GameOverButton {
+    //the same character again, with a new seed
+    NewRun,
+    MainMenu,
+}
+
+//shows the summary of the run and appends it to the run history
+pub fn game_over_setup(
+    players: Query<(&Player, &CreatureType, &Level, Option<&Gold>)>,
+    run_stats: Res<RunStats>,
+    playing_state: Res<PlayingState>,
+    run_rng: Res<RunRng>,
+    bestiary: Res<Bestiary>,
+    current_save: Option<Res<CurrentSave>>,
+    mut commands: Commands,
+) {
+    let Ok((player, creature_type, level, gold)) = players.get_single() else {
+        return;
+    };
+    let record = RunRecord {
+        name: player.name.clone(),
+        class: player.class.definition().name.to_string(),
+        ancestry: creature_type.get_stats(&bestiary).type_name.clone(),
+        level: level.0,
+        seed: run_rng.seed,
+        cause_of_death: run_stats.cause_of_death.clone(),
+        depth: run_stats.max_depth + 1,
+        play_time_secs: playing_state.timer.as_secs(),
+        gold: gold.map_or(0, |gold| gold.0),
+        gold_found: run_stats.gold_found,
+        items_found: run_stats.items_found,
+        kills: run_stats.kills.clone(),
+        ended_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+    };
+    //shown under the summary, the player would never find out otherwise
+    let mut problems = Vec::new();
+    if let Err(error) = write_run_record(&record, RUN_HISTORY_PATH) {
+        problems.push(format!("The run could not be added to the run history: {}", error));
+    }
+    //a dead character can't be continued
+    if let Some(current_save) = current_save {
+        if let Err(error) = delete_slot(current_save.0) {
+            problems.push(format!("The save in slot {} could not be deleted: {}", current_save.0 + 1, error));
+        }
+        commands.remove_resource::<CurrentSave>();
+    }
+    //the next run starts with the same character
+    commands.insert_resource(NewCharacter {
+        name: player.name.clone(),
+        class: player.class,
+        ancestry: *creature_type,
+        seed: String::new(),
+    });
+
+    let text_style = TextStyle {
+        font_size: 24.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    let total_kills : u32 = record.kills.values().sum();
+    let mut lines = vec![
+        format!("{}, level {} {} {}", record.name, record.level, record.ancestry, record.class),
+        record.cause_of_death.clone(),
+        format!("Reached depth {}", record.depth),
+        format!("Time played: {}", format_play_time(record.play_time_secs)),
+        format!("Gold found: {} ({} left)", record.gold_found, record.gold),
+        format!("Items found: {}", record.items_found),
+        format!("Kills: {}", total_kills),
+    ];
+    lines.extend(record.kills.iter().map(|(name, count)| format!("    {} x{}", name, count)));
+    lines.extend(problems);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    width: Val::Percent(100.0),
+                    height: Val::Percent(100.0),
+                    align_items: AlignItems::Center,
+                    justify_content: JustifyContent::Center,
+                    ..default()
+                },
+                background_color: Color::rgba(0.0, 0.0, 0.0, 0.7).into(),
+                ..default()
+            },
+            OnGameOverScreen,
+        ))
+        .with_children(|parent| {
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        flex_direction: FlexDirection::Column,
+                        align_items: AlignItems::Center,
+                        padding: UiRect::all(Val::Px(20.0)),
+                        ..default()
+                    },
+                    background_color: Color::CRIMSON.into(),
+                    ..default()
+                })
+                .with_children(|parent| {
+                    parent.spawn(
+                        TextBundle::from_section(
+                            "Game Over",
+                            TextStyle {
+                                font_size: 60.0,
+                                color: TEXT_COLOR,
+                                ..default()
+                            },
+                        )
+                        .with_style(Style {
+                            margin: UiRect::bottom(Val::Px(20.0)),
+                            ..default()
+                        }),
+                    );
+                    for line in lines {
+                        parent.spawn(TextBundle::from_section(line, text_style.clone()));
+                    }
+                    parent
+                        .spawn(NodeBundle {
+                            style: Style {
+                                margin: UiRect::top(Val::Px(20.0)),
+                                ..default()
+                            },
+                            ..default()
+                        })
+                        .with_children(|parent| {
+                            for (action, text) in [(GameOverButton::NewRun, "New Run"), (GameOverButton::MainMenu, "Main Menu")] {
+                                parent
+                                    .spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(200.0),
+                                                height: Val::Px(50.0),
+                                                margin: UiRect::all(Val::Px(10.0)),
+                                                justify_content: JustifyContent::Center,
+                                                align_items: AlignItems::Center,
+                                                ..default()
+                                            },
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        action,
+                                    ))
+                                    .with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section(
+                                            text,
+                                            TextStyle {
+                                                font_size: 30.0,
+                                                ..text_style.clone()
+                                            },
+                                        ));
+                                    });
+                            }
+                        });
+                });
+        });
+}
+
+pub fn game_over_buttons(
+    mut interaction_query: Query<(&Interaction, &GameOverButton, &mut BackgroundColor), Changed<Interaction>>,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut commands: Commands,
+) {
+    for (interaction, action, mut color) in interaction_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            _ => NORMAL_BUTTON.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        match action {
+            GameOverButton::NewRun => game_state.set(GameState::Playing),
+            GameOverButton::MainMenu => {
+                commands.remove_resource::<NewCharacter>();
+                game_state.set(GameState::Menu);
+            }
+        }
+    }
+}
+
+pub struct GameOverPlugin;
+
+impl Plugin for GameOverPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<RunStats>()
+            .add_systems(
+                Update,
+                (
+                    track_depth,
+                    track_kills.after(resolve_attacks).before(handle_deaths),
+                    detect_player_death.after(resolve_attacks),
+                )
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::GameOver), game_over_setup)
+            .add_systems(Update, game_over_buttons.run_if(in_state(GameState::GameOver)))
+            .add_systems(OnExit(GameState::GameOver), despawn_screen::<OnGameOverScreen>);
+    }
+}
@@ -1,9 +1,21 @@
 pub mod game {
 
     use crate::actors::Actor;
+    use crate::experience::{Experience, StatAllocation};
+    use crate::faction::Faction;
     use crate::game_world::GameWorld;
     use crate::grid::GRID_SIZE;
-    use crate::player::Player;
+    use crate::run_rng::RunRng;
+    use crate::tiles::TileType;
+    use crate::abilities::{ClassAbilities, ClassResource};
+    use crate::player::{Aim, Player};
+    use crate::spells::Spellbook;
+    use crate::melee::Weapon;
+    use crate::items::Inventory;
+    use crate::equipment::Equipment;
+    use crate::combat_stats::CombatStats;
+    use crate::gold::Gold;
+    use crate::status_effects::{is_stunned, StatusEffects};
     use crate::position::{Position, Velocity};
     use crate::tiles::Tile;
     use bevy::input::mouse::MouseMotion;
@@ -20,9 +32,28 @@ pub mod game {
         pub new_pos: Position,
     }
 
+    //sent when an actor's move was blocked by a wall or the edge of the grid
+    #[derive(Event)]
+    pub struct WallCollisionEvent {
+        pub actor: Entity,
+    }
+
     #[derive(Event)]
     pub struct RenderGrid;
 
+    //sent when a floor is generated for the first time, so it can be populated
+    #[derive(Event)]
+    pub struct FloorGenerated {
+        pub level: usize,
+    }
+
+    //sent when a run is started or loaded, anything remembered about the previous run is stale
+    #[derive(Event)]
+    pub struct RunStarted;
+
+    //what a run leaves behind, despawned when the next one starts
+    pub type RunEntities = Or<(With<Actor>, With<Tile>)>;
+
     pub fn setup(mut commands: Commands) {
         //reset the camera
         //commands.spawn(Camera2dBundle::default());
@@ -33,8 +64,9 @@ pub mod game {
         mut camera: Query<&mut Transform, With<Camera>>,
     ) {
         //set the camera to the player position
-        let player_pos = player.iter().next().unwrap().1;
-        let mut camera_transform = camera.iter_mut().next().unwrap();
+        let (Some((_, player_pos, _)), Some(mut camera_transform)) = (player.iter().next(), camera.iter_mut().next()) else {
+            return;
+        };
         camera_transform.translation.x = player_pos.0;
         camera_transform.translation.y = player_pos.1;
     }
@@ -44,13 +76,15 @@ pub mod game {
         mut actors: Query<(Entity, &Position, &Sprite, &mut Transform)>,
         mut player: Query<(&Player, &Position, &Sprite)>,
     ) {
+        let Some((_, player_pos, _)) = player.iter().next() else {
+            return;
+        };
+        let player_level = player_pos.2;
         for (_, pos, _, mut transform) in actors.iter_mut() {
             transform.translation.x = pos.0;
             transform.translation.y = pos.1;
             //set invisible if not on the same level as the player
-            let player_pos = player.iter().next().unwrap().1;
-            println!("player level: {}, actor level: {}", player_pos.2, pos.2);
-            if player_pos.2 != pos.2 {
+            if player_level != pos.2 {
                 transform.scale = Vec3::new(0.0, 0.0, 0.0);
             } else {
                 transform.scale = Vec3::new(1.0, 1.0, 1.0);
@@ -59,45 +93,70 @@ pub mod game {
     }
 
 
+    //the components of the player the keys steer
+    type ControlledPlayer = (
+        Entity,
+        &'static Player,
+        &'static mut Velocity,
+        Option<&'static CombatStats>,
+        Option<&'static StatusEffects>,
+    );
+
     fn control_player(
         keys: Res<Input<KeyCode>>,
         mut movement_event_writer: EventWriter<MovementEvent>,
-        mut player: Query<(Entity, &Player, &mut Velocity)>,
+        mut player: Query<ControlledPlayer>,
         time: Res<Time>,
         grid: Res<GameWorld>,
     ) {
-        let mut new_velocity =  player.iter_mut().next().unwrap().2.clone();
+        let Some(mut new_velocity) = player.iter().next().map(|(_, _, velocity, _, _)| velocity.clone()) else {
+            return;
+        };
+        //equipment and status effects can make the player faster or slower
+        let acceleration = 100.0 * player.iter().next().and_then(|(_, _, _, stats, _)| stats).map_or(1.0, |stats| stats.speed);
+        //a stunned player can't steer
+        if player.iter().next().is_some_and(|(_, _, _, _, effects)| is_stunned(effects)) {
+            return;
+        }
         if keys.pressed(KeyCode::W) {
-            new_velocity.1 += 100.0 * time.delta_seconds();
+            new_velocity.1 += acceleration * time.delta_seconds();
         }
         if keys.pressed(KeyCode::S) {
-            new_velocity.1 -= 100.0 * time.delta_seconds();
+            new_velocity.1 -= acceleration * time.delta_seconds();
         }
         if keys.pressed(KeyCode::A) {
-            new_velocity.0 -= 100.0 * time.delta_seconds();
+            new_velocity.0 -= acceleration * time.delta_seconds();
         }
         if keys.pressed(KeyCode::D) {
-            new_velocity.0 += 100.0 * time.delta_seconds();
+            new_velocity.0 += acceleration * time.delta_seconds();
         }
         //set the player's velocity
-        for (_, _, mut velocity) in player.iter_mut() {
+        for (_, _, mut velocity, _, _) in player.iter_mut() {
             velocity.0 = new_velocity.0;
             velocity.1 = new_velocity.1;
         }
 
     }
 
-    fn handle_movement_event_with_collisions(
+    pub fn handle_movement_event_with_collisions(
         mut event_reader: EventReader<MovementEvent>,
         mut actors: Query<(Entity, &mut Position, &Actor, Option<&mut Velocity>)>,
         game_world: Res<GameWorld>,
+        mut wall_collision_writer: EventWriter<WallCollisionEvent>,
     ) {
         for event in event_reader.iter() {
-            let mut actor_pos = actors.get_mut(event.actor).unwrap().1;
+            //the actor may have been despawned since the event was sent
+            let Ok((_, mut actor_pos, _, _)) = actors.get_mut(event.actor) else {
+                continue;
+            };
+            //the actor changed floors since the event was sent
+            if event.new_pos.2 != actor_pos.2 {
+                continue;
+            }
             let mut x = 0;
             let mut y = 0;
             let mut was_colliding = None;
-            for row in game_world.grids.get(actor_pos.2).unwrap().tiles {
+            for row in game_world.grid(actor_pos.2).tiles {
                 for tile_type in row {
                     if !tile_type.is_walkable() {
                         let box_size = Vec2::new(32.0, 32.0);
@@ -127,8 +186,10 @@ pub mod game {
             {
                 was_colliding = Some(sprite::collide_aabb::Collision::Inside);
             }
+            if was_colliding.is_some() {
+                wall_collision_writer.send(WallCollisionEvent { actor: event.actor });
+            }
             if was_colliding.is_none() {
-                println!("moving actor");
                 actor_pos.0 = event.new_pos.0;
                 actor_pos.1 = event.new_pos.1;
             } else if  let Some(mut vel) = actors.get_mut(event.actor).unwrap().3 {
@@ -173,22 +234,23 @@ pub mod game {
     }
 
     pub fn rotate_player_sprite_based_on_mouse(
-        mut player: Query<(&Player, &mut Transform)>,
+        mut player: Query<(&Player, &mut Transform, &mut Aim)>,
         q_windows: Query<&Window, With<PrimaryWindow>>,
         ) {
             if let Some(position) = q_windows.single().cursor_position() {
-                let player_pos = player.iter_mut().next().unwrap().1.clone();
-                let mut player_transform = player.iter_mut().next().unwrap().1;
+                let Some((_, mut player_transform, mut aim)) = player.iter_mut().next() else {
+                    return;
+                };
                 let center_of_screen = Vec2::new(q_windows.single().width() / 2.0, q_windows.single().height() / 2.0);
                 let mouse_pos = Vec2::new(position.x, position.y) - center_of_screen;
-                println!("mouse pos: {:?}", mouse_pos);
                 let direction = mouse_pos;
                 //normalize the direction
                 let direction = direction.normalize();
                 let angle = direction.y.atan2(direction.x);
                 player_transform.rotation = Quat::from_rotation_z(-angle);
-            } else {
-                println!("Cursor is not in the game window.");
+                //the window's y axis points down, the world's points up
+                aim.direction = Vec2::new(direction.x, -direction.y);
+                aim.offset = Vec2::new(mouse_pos.x, -mouse_pos.y);
             }
     }
 
@@ -201,9 +263,10 @@ pub mod game {
         game_world: Res<GameWorld>,
         asset_server: Res<AssetServer>,
     ) {
-        //set the camera to the player position
-        let player_pos = player.iter().next().unwrap().1;
-        let mut camera_transform = camera.iter_mut().next().unwrap();
+        //set the camera to the player position, there is no player while a run is torn down and set up
+        let (Some((_, player_pos)), Some(mut camera_transform)) = (player.iter().next(), camera.iter_mut().next()) else {
+            return;
+        };
         camera_transform.translation.x = player_pos.0;
         camera_transform.translation.y = player_pos.1;
 
@@ -214,8 +277,11 @@ pub mod game {
         //draw the tiles
         let mut x = 0;
         let mut y = 0;
-        let level = player.iter().next().unwrap().1 .2;
-        for row in game_world.grids.get(level).unwrap().tiles {
+        let level = player_pos.2;
+        let Some(floor) = game_world.floors.get(level) else {
+            return;
+        };
+        for row in floor.grid.tiles {
             for tile_type in row {
                 let tile = Tile {
                     tile_type: tile_type,
@@ -239,58 +305,121 @@ pub mod game {
             y += 1;
         }
     }
+    //take the stairs the player is standing on, generating the next floor if it doesn't exist yet
+    fn use_stairs(
+        keys: Res<Input<KeyCode>>,
+        mut player: Query<&mut Position, With<Player>>,
+        mut game_world: ResMut<GameWorld>,
+        run_rng: Res<RunRng>,
+        mut ev_render_grid: EventWriter<RenderGrid>,
+        mut ev_floor_generated: EventWriter<FloorGenerated>,
+    ) {
+        if !keys.just_pressed(KeyCode::E) {
+            return;
+        }
+        let Ok(mut player_pos) = player.get_single_mut() else {
+            return;
+        };
+        let level = player_pos.2;
+        let (new_level, arrival_stairs) = match game_world.grid(level).get(player_pos.to_tile()) {
+            TileType::DownStairs => (level + 1, TileType::UpStairs),
+            TileType::UpStairs if level > 0 => (level - 1, TileType::DownStairs),
+            _ => return,
+        };
+        for generated in game_world.generate_floors_to(new_level, &run_rng) {
+            ev_floor_generated.send(FloorGenerated { level: generated });
+        }
+        let arrival = game_world.grid(new_level).find_tile(arrival_stairs).unwrap_or((0, 0));
+        *player_pos = Position::from_tile(arrival, new_level);
+        ev_render_grid.send(RenderGrid {});
+    }
+
+    pub fn spawn_player(commands : &mut Commands, asset_server : &AssetServer, player_pos : Position) -> Entity {
+        let player_class = Player::default().class;
+        let player_stats = player_class.apply(&CreatureArchetype::default());
+        let player_max_health = player_stats.max_health_at_level(Level::default().0);
+        commands
+            .spawn((
+                CreatureBundle {
+                    position: player_pos.clone(),
+                    sp_bundle: SpriteBundle {
+                        texture: asset_server.load(player_stats.sprite.as_str()),
+                        transform: Transform::from_xyz(player_pos.0, player_pos.1, 1.0),
+                        visibility: Visibility::Visible,
+                        ..Default::default()
+                    },
+                    ..Default::default()
+                },
+                Player {
+                    class: player_class,
+                    ..Default::default()
+                },
+                Velocity(0.0, 0.0),
+                Level::default(),
+                Health(player_max_health),
+                MaxHealth(player_max_health),
+                Faction::Player,
+                Experience::default(),
+                StatAllocation::default(),
+                //bundles are limited to 15 components, so the class and gear related ones are grouped
+                (
+                    ClassResource::for_class(player_class),
+                    ClassAbilities::for_class(player_class),
+                    Spellbook::for_class(player_class),
+                ),
+                (
+                    Weapon::default(),
+                    Inventory::for_class(player_class),
+                    Equipment::for_class(player_class),
+                    CombatStats::default(),
+                    Gold::default(),
+                ),
+                Aim::default(),
+                StatusEffects::default(),
+            ))
+            .id()
+    }
+
+    //a fresh world with a new player on the up stairs of the first floor, what is left of the
+    //previous run (after a game over) is despawned first. The character is applied after this,
+    //see character_creation::apply_new_character
+    pub fn start_new_run(
+        mut commands: Commands,
+        asset_server: Res<AssetServer>,
+        leftovers: Query<Entity, RunEntities>,
+        run_rng: Res<RunRng>,
+        mut ev_floor_generated: EventWriter<FloorGenerated>,
+        mut ev_run_started: EventWriter<RunStarted>,
+    ) {
+        for entity in leftovers.iter() {
+            commands.entity(entity).despawn_recursive();
+        }
+        let mut game_world = GameWorld::new();
+        //generate the first level
+        game_world.generate_floors_to(0, &run_rng);
+        //find the player spawn point on the up stairs
+        let player_spawn = game_world
+            .grid(0)
+            .find_tile(TileType::UpStairs)
+            .unwrap_or((0, 0));
+        let pid = spawn_player(&mut commands, &asset_server, Position::from_tile(player_spawn, 0));
+        //insert the player into the game world
+        game_world.player = Some(pid);
+        commands.insert_resource(game_world);
+        //populate the first level
+        ev_floor_generated.send(FloorGenerated { level: 0 });
+        ev_run_started.send(RunStarted);
+    }
+
     pub fn send_render_grid_event(mut ev_render_grid: EventWriter<RenderGrid>) {
         ev_render_grid.send(RenderGrid {});
     }
     impl Plugin for GamePlugin {
         fn build(&self, app: &mut App) {
             app.add_systems(Startup, setup);
-            let asset_server = app.world.get_resource::<AssetServer>().unwrap().clone();
-
-            let mut game_world = GameWorld::new();
-            //generate the first level
-            let mut grid = crate::grid::Grid::new_cell_automata_grid(0.5, 5);
-            grid.place_stairs();
-            //find the player spawn point on the up stairs
-            let mut player_spawn = (0, 0);
-            for (y, row) in grid.tiles.iter().enumerate() {
-                for (x, tile) in row.iter().enumerate() {
-                    if *tile == crate::tiles::TileType::UpStairs {
-                        player_spawn = (x, y);
-                    }
-                }
-            }
-            game_world.grids.push(grid);
-            let pid = app
-                .world
-                .spawn((
-                    CreatureBundle {
-                        position: Position(
-                            player_spawn.0 as f32 * 32.0,
-                            player_spawn.1 as f32 * 32.0,
-                            0,
-                        ),
-                        sp_bundle: SpriteBundle {
-                            texture: asset_server.load(CreatureType::Human.get_stats().sprite),
-                            transform: Transform::from_xyz(
-                                (player_spawn.0 as f32 * 32.0),
-                                (player_spawn.1 as f32 * 32.0),
-                                1.0,
-                            ),
-                            visibility: Visibility::Visible,
-                            ..Default::default()
-                        },
-                        ..Default::default()
-                    },
-                    Player {
-                        ..Default::default()
-                    },
-                    Velocity(0.0, 0.0),
-                ))
-                .id();
-            //insert the player into the game world
-            game_world.player = Some(pid);
-            app.world.insert_resource(game_world);
+            //the floors and the player are created when a run starts, see start_new_run
+            app.insert_resource(GameWorld::new());
+            app.insert_resource(RunRng::random());
             //ambient light
             app.insert_resource(AmbientLight {
                 color: Color::rgb(0.0, 0.5, 0.0),
@@ -300,6 +429,9 @@ pub mod game {
             //render the grid
             app.add_event::<RenderGrid>();
             app.add_event::<MovementEvent>();
+            app.add_event::<WallCollisionEvent>();
+            app.add_event::<FloorGenerated>();
+            app.add_event::<RunStarted>();
             app.add_systems(Update, render_grid.run_if(on_event::<RenderGrid>()));
             app.add_systems(
                 Update,
@@ -312,14 +444,15 @@ pub mod game {
                 handle_movement_event_with_collisions.run_if(in_state(GameState::Playing)),
             );
             app.add_systems(Update, handle_velocity.run_if(in_state(GameState::Playing)));
+            app.add_systems(Update, use_stairs.run_if(in_state(GameState::Playing)));
             app.add_systems(
                 Update,
                 set_player_camera.run_if(in_state(GameState::Playing)),
             );
-            //despawn tiles OnExit(Playing)
+            //despawn the run when going back to the menu, pausing keeps it
             app.add_systems(OnEnter(GameState::Playing), setup);
-            app.add_systems(OnExit(GameState::Playing), despawn_screen::<Tile>);
-            app.add_systems(OnExit(GameState::Playing), despawn_screen::<Actor>);
+            app.add_systems(OnEnter(GameState::Menu), despawn_screen::<Tile>);
+            app.add_systems(OnEnter(GameState::Menu), despawn_screen::<Actor>);
             //spawn a render grid event on enter playing
             app.add_systems(OnEnter(GameState::Playing), send_render_grid_event);
         }
@@ -1,13 +1,12 @@
 
-use std::default;
+use std::time::Duration;
 
-use bevy::prelude::{State, States, default};
+use bevy::prelude::{Resource, States};
 
-use crate::floor::Floor;
+#[derive(Resource, Default)]
 pub struct PlayingState {
-    level : u32,
-    timer : u64,
-    levels : Vec<Floor>,
+    //time played this run, only counts while in GameState::Playing, see hud.rs
+    pub timer : Duration,
 }
 #[derive(States, Debug, Hash, PartialEq, Eq, Default, Clone)]
 pub enum GameState {
@@ -1,11 +1,13 @@
 use bevy::prelude::*;
+use crate::floor::Floor;
 use crate::grid::Grid;
+use crate::run_rng::RunRng;
 
 
 #[derive(Resource)]
 pub struct GameWorld {
-    //list of grids loaded in the game
-    pub grids: Vec<Grid>,
+    //list of floors loaded in the game, indexed by depth
+    pub floors: Vec<Floor>,
     //the entity that is the player
     pub player: Option<Entity>,
 }
@@ -13,8 +15,23 @@ pub struct GameWorld {
 impl GameWorld {
     pub fn new() -> Self {
         GameWorld {
-            grids: Vec::new(),
+            floors: Vec::new(),
             player: None,
         }
     }
-}
\ No newline at end of file
+
+    pub fn grid(&self, level : usize) -> &Grid {
+        &self.floors[level].grid
+    }
+
+    //generates every floor up to and including depth, returns the depths that were newly generated
+    pub fn generate_floors_to(&mut self, depth : usize, run_rng : &RunRng) -> Vec<usize> {
+        let mut generated = Vec::new();
+        while self.floors.len() <= depth {
+            let new_depth = self.floors.len();
+            self.floors.push(Floor::generate(new_depth, &mut run_rng.floor_rng(new_depth)));
+            generated.push(new_depth);
+        }
+        generated
+    }
+}
@@ -0,0 +1,146 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::actors::Actor;
+use crate::combat::{handle_deaths, DeathEvent};
+use crate::creature::Monster;
+use crate::floor::FloorType;
+use crate::game_over::RunStats;
+use crate::game_plugin::game::FloorGenerated;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::TILE_SIZE;
+use crate::items::PICKUP_RADIUS;
+use crate::loot::{drop_monster_loot, place_chests};
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::Player;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::spawning::{spawn_candidates, MIN_DISTANCE_FROM_STAIRS};
+use crate::tiles::TileType;
+
+//gold piles on the first floor, one more every three floors
+pub const BASE_GOLD_PILES_PER_FLOOR: usize = 2;
+pub const MAX_GOLD_PILES_PER_FLOOR: usize = 8;
+//chance of a monster dropping gold where it dies
+pub const MONSTER_GOLD_CHANCE: f32 = 0.3;
+pub const GOLD_SPRITE: &str = "item/gold/gold_pile.png";
+
+//The gold a creature carries, spent in the shops of the towns
+#[derive(Component, Debug, Clone, Copy, Default)]
+pub struct Gold(pub u32);
+
+//Gold lying on a floor, picked up by walking over it
+#[derive(Component, Debug, Clone, Copy)]
+pub struct GoldPile(pub u32);
+
+//how much gold a pile found at this depth holds
+pub fn roll_gold(depth : usize, rng : &mut impl Rng) -> u32 {
+    rng.gen_range(5..=15) * (depth as u32 + 2) / 2
+}
+
+pub fn spawn_gold_pile(commands : &mut Commands, asset_server : &AssetServer, amount : u32, position : Position) -> Entity {
+    commands
+        .spawn((
+            Actor::default(),
+            SpriteBundle {
+                texture: asset_server.load(GOLD_SPRITE),
+                //with the items
+                transform: Transform::from_xyz(position.0, position.1, 0.5),
+                ..default()
+            },
+            position,
+            GoldPile(amount),
+        ))
+        .id()
+}
+
+//leave some gold lying around every newly generated floor except towns
+pub fn scatter_gold(
+    mut ev_floor_generated: EventReader<FloorGenerated>,
+    game_world: Res<GameWorld>,
+    asset_server: Res<AssetServer>,
+    mut run_rng: ResMut<RunRng>,
+    mut commands: Commands,
+) {
+    for event in ev_floor_generated.iter() {
+        let floor = &game_world.floors[event.level];
+        if floor.floor_type == FloorType::Town {
+            continue;
+        }
+        let mut avoid = Vec::new();
+        if let Some(upstairs) = floor.grid.find_tile(TileType::UpStairs) {
+            avoid.push((upstairs, MIN_DISTANCE_FROM_STAIRS));
+        }
+        let candidates = spawn_candidates(&floor.grid, &avoid);
+        let count = (BASE_GOLD_PILES_PER_FLOOR + event.level / 3).min(MAX_GOLD_PILES_PER_FLOOR);
+        let tiles : Vec<(usize, usize)> = candidates.choose_multiple(&mut run_rng.loot, count).cloned().collect();
+        for tile in tiles {
+            let amount = roll_gold(event.level, &mut run_rng.loot);
+            spawn_gold_pile(&mut commands, &asset_server, amount, Position::from_tile(tile, event.level));
+        }
+    }
+}
+
+//monsters sometimes drop gold where they die
+pub fn drop_monster_gold(
+    mut death_events: EventReader<DeathEvent>,
+    monsters: Query<&Position, With<Monster>>,
+    mut run_rng: ResMut<RunRng>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for event in death_events.iter() {
+        let Ok(pos) = monsters.get(event.entity) else {
+            continue;
+        };
+        if run_rng.loot.gen::<f32>() < MONSTER_GOLD_CHANCE {
+            let amount = roll_gold(pos.2, &mut run_rng.loot);
+            spawn_gold_pile(&mut commands, &asset_server, amount, pos.clone());
+        }
+    }
+}
+
+//the player picks up the gold they walk over
+pub fn pickup_gold(
+    mut players: Query<(&Position, &mut Gold), With<Player>>,
+    piles: Query<(Entity, &Position, &GoldPile)>,
+    mut log: ResMut<MessageLog>,
+    mut run_stats: ResMut<RunStats>,
+    mut commands: Commands,
+) {
+    let Ok((player_pos, mut gold)) = players.get_single_mut() else {
+        return;
+    };
+    for (entity, pos, pile) in piles.iter() {
+        if pos.2 != player_pos.2 || player_pos.distance(pos) > PICKUP_RADIUS * TILE_SIZE {
+            continue;
+        }
+        gold.0 += pile.0;
+        run_stats.gold_found += pile.0;
+        log.add(MessageKind::Loot, format!("You pick up {} gold", pile.0));
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct GoldPlugin;
+
+impl Plugin for GoldPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(
+            Update,
+            scatter_gold
+                .after(place_chests)
+                .run_if(on_event::<FloorGenerated>()),
+        )
+        .add_systems(
+            Update,
+            (
+                pickup_gold,
+                drop_monster_gold.after(drop_monster_loot).before(handle_deaths),
+            )
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
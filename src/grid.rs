@@ -3,8 +3,12 @@
 use crate::tiles::TileType;
 
 use rand::{Rng, seq::SliceRandom};
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
 
 pub const GRID_SIZE: usize = 64;
+//size of a tile in world units (pixels)
+pub const TILE_SIZE: f32 = 32.0;
 
 
 #[derive( Clone, PartialEq, Eq, Hash)]
@@ -15,6 +19,69 @@ pub struct Grid {
 
 
 impl Grid {
+    //tiles are rendered with the first index as the row, so the tile at world tile coordinates (x, y) is tiles[y][x]
+    pub fn get(&self, tile : (usize, usize)) -> TileType {
+        self.tiles[tile.1][tile.0]
+    }
+
+    //one string per row, see TileType::to_char
+    pub fn to_rows(&self) -> Vec<String> {
+        self.tiles
+            .iter()
+            .map(|row| row.iter().map(|tile_type| tile_type.to_char()).collect())
+            .collect()
+    }
+
+    //the opposite of to_rows, None if a row or a tile is missing or unknown
+    pub fn from_rows(rows : &[String]) -> Option<Self> {
+        if rows.len() != GRID_SIZE {
+            return None;
+        }
+        let mut tiles = [[TileType::Floor; GRID_SIZE]; GRID_SIZE];
+        for (y, row) in rows.iter().enumerate() {
+            let row : Vec<TileType> = row.chars().map(TileType::from_char).collect::<Option<_>>()?;
+            if row.len() != GRID_SIZE {
+                return None;
+            }
+            tiles[y].copy_from_slice(&row);
+        }
+        Some(Self { tiles })
+    }
+
+    #[cfg(test)]
+    pub fn set(&mut self, tile : (usize, usize), tile_type : TileType) {
+        self.tiles[tile.1][tile.0] = tile_type;
+    }
+
+    pub fn in_bounds(x : i32, y : i32) -> bool {
+        x >= 0 && x < GRID_SIZE as i32 && y >= 0 && y < GRID_SIZE as i32
+    }
+
+    //world tile coordinates of the first tile of the given type
+    pub fn find_tile(&self, tile_type : TileType) -> Option<(usize, usize)> {
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                if self.get((x, y)) == tile_type {
+                    return Some((x, y));
+                }
+            }
+        }
+        None
+    }
+
+    //world tile coordinates of every walkable tile
+    pub fn walkable_tiles(&self) -> Vec<(usize, usize)> {
+        let mut tiles = Vec::new();
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                if self.get((x, y)).is_walkable() {
+                    tiles.push((x, y));
+                }
+            }
+        }
+        tiles
+    }
+
     pub fn new_floor_grid() -> Self {
         let tiles = [[TileType::Floor; GRID_SIZE]; GRID_SIZE];
         Self { tiles: tiles }
@@ -46,7 +113,7 @@ impl Grid {
 
     }
 
-    pub fn new_cell_automata_grid(initial_density_prob : f32, iterations : u32) -> Self {
+    pub fn new_cell_automata_grid(rng : &mut impl Rng, initial_density_prob : f32, iterations : u32) -> Self {
         //It is an old and fairly well documented trick to use cellular automata to generate cave-like structures.
         // The basic idea is to fill the first map randomly, then repeatedly create new maps using the 4-5 rule: 
         //a tile becomes a wall if it was a wall and 4 or more of its eight neighbors were walls, or if it was not a wall and 5 or more neighbors were. Put more succinctly,
@@ -56,7 +123,6 @@ impl Grid {
         //first, we need to create a grid of tiles
         let mut tiles = [[TileType::Floor; GRID_SIZE]; GRID_SIZE];
         //then, we need to fill the grid with random walls based on the initial density probability
-        let mut rng = rand::thread_rng();
         for x in 0..GRID_SIZE {
             for y in 0..GRID_SIZE {
                 let random_number = rng.gen_range(0.0..1.0);
@@ -107,6 +173,7 @@ impl Grid {
     }
 
     pub fn new_room_based_grid(
+        rng : &mut impl Rng,
         room_count : u32, 
         min_room_size : (usize, usize), 
         max_room_size: (usize, usize),
@@ -116,7 +183,6 @@ impl Grid {
         //then, we need to create a list of rooms, which are tuples of tuples 
         let mut rooms : Vec<((usize, usize),(usize, usize))> = Vec::new();
         //randomly place rooms, making sure they don't overlap
-        let mut rng = rand::thread_rng();
         let mut rooms_planned = 0;
         let mut attempts = 0;
         while rooms_planned < room_count && attempts < 1000 {
@@ -152,7 +218,7 @@ impl Grid {
         let mut queue = Vec::new();
         queue.push(0);
         //shuffle the rooms list
-        rooms.shuffle(&mut rng);
+        rooms.shuffle(rng);
         //crate the spanning tree
         while queue.len() > 0 {
             let current = queue.pop().unwrap();
@@ -176,7 +242,6 @@ impl Grid {
         for edge in &stree {
             let room1 = &rooms[edge.0];
             let room2 = &rooms[edge.1];
-            let mut rng = rand::thread_rng();
             let mut room1_target = (rng.gen_range(room1.0.0..room1.0.0 + room1.1.0), rng.gen_range(room1.0.1..room1.0.1 + room1.1.1));
             let mut room2_target = (rng.gen_range(room2.0.0..room2.0.0 + room2.1.0), rng.gen_range(room2.0.1..room2.0.1 + room2.1.1));
             //go sideways first
@@ -206,9 +271,8 @@ impl Grid {
     }
 
 
-    pub fn place_stairs(&mut self) {
+    pub fn place_stairs(&mut self, rng : &mut impl Rng) {
         //place up stairs randomly, and down stairs randomly. Replace down stairs if the up stairs are not reachable from the down stairs
-        let mut rng = rand::thread_rng();
         //place up stairs
         let mut upstairs_coords = (rng.gen_range(0..GRID_SIZE), rng.gen_range(0..GRID_SIZE));
         //make sure it's on a floor tile
@@ -272,4 +336,187 @@ impl Grid {
         }
         return false;
     }
-}
\ No newline at end of file
+
+    //A* over walkable tiles (world tile coordinates), diagonal moves can't cut corners.
+    //The returned path doesn't include the start tile
+    pub fn find_path(&self, start : (usize, usize), goal : (usize, usize)) -> Option<Vec<(usize, usize)>> {
+        if start == goal {
+            return Some(Vec::new());
+        }
+        let in_grid = |tile : (usize, usize)| tile.0 < GRID_SIZE && tile.1 < GRID_SIZE;
+        if !in_grid(start) || !in_grid(goal) || !self.get(goal).is_walkable() {
+            return None;
+        }
+        let index = |tile : (usize, usize)| tile.1 * GRID_SIZE + tile.0;
+        //octile distance, straight moves cost 10 and diagonal ones 14
+        let heuristic = |tile : (usize, usize)| {
+            let dx = (tile.0 as i32 - goal.0 as i32).unsigned_abs();
+            let dy = (tile.1 as i32 - goal.1 as i32).unsigned_abs();
+            10 * dx.max(dy) + 4 * dx.min(dy)
+        };
+        let mut cost = vec![u32::MAX; GRID_SIZE * GRID_SIZE];
+        let mut came_from : Vec<Option<(usize, usize)>> = vec![None; GRID_SIZE * GRID_SIZE];
+        let mut open = BinaryHeap::new();
+        cost[index(start)] = 0;
+        open.push(Reverse((heuristic(start), start)));
+        while let Some(Reverse((_, current))) = open.pop() {
+            if current == goal {
+                let mut path = vec![goal];
+                let mut tile = goal;
+                while let Some(previous) = came_from[index(tile)] {
+                    if previous == start {
+                        break;
+                    }
+                    path.push(previous);
+                    tile = previous;
+                }
+                path.reverse();
+                return Some(path);
+            }
+            for x_offset in -1..=1i32 {
+                for y_offset in -1..=1i32 {
+                    if x_offset == 0 && y_offset == 0 {
+                        continue;
+                    }
+                    let x = current.0 as i32 + x_offset;
+                    let y = current.1 as i32 + y_offset;
+                    if !Grid::in_bounds(x, y) || !self.get((x as usize, y as usize)).is_walkable() {
+                        continue;
+                    }
+                    let diagonal = x_offset != 0 && y_offset != 0;
+                    if diagonal
+                        && (!self.get((x as usize, current.1)).is_walkable()
+                            || !self.get((current.0, y as usize)).is_walkable())
+                    {
+                        continue;
+                    }
+                    let next = (x as usize, y as usize);
+                    let new_cost = cost[index(current)] + if diagonal { 14 } else { 10 };
+                    if new_cost < cost[index(next)] {
+                        cost[index(next)] = new_cost;
+                        came_from[index(next)] = Some(current);
+                        open.push(Reverse((new_cost + heuristic(next), next)));
+                    }
+                }
+            }
+        }
+        None
+    }
+
+    //walks a line between the two tiles and checks that every tile in between lets light through
+    pub fn has_line_of_sight(&self, a : (usize, usize), b : (usize, usize)) -> bool {
+        //nothing is seen off the map
+        if a.0 >= GRID_SIZE || a.1 >= GRID_SIZE || b.0 >= GRID_SIZE || b.1 >= GRID_SIZE {
+            return false;
+        }
+        let (mut x, mut y) = (a.0 as i32, a.1 as i32);
+        let (x1, y1) = (b.0 as i32, b.1 as i32);
+        let dx = (x1 - x).abs();
+        let dy = -(y1 - y).abs();
+        let step_x = if x < x1 { 1 } else { -1 };
+        let step_y = if y < y1 { 1 } else { -1 };
+        let mut error = dx + dy;
+        while (x, y) != (x1, y1) {
+            if (x, y) != (a.0 as i32, a.1 as i32) && !self.get((x as usize, y as usize)).is_transparent() {
+                return false;
+            }
+            let error2 = 2 * error;
+            if error2 >= dy {
+                error += dy;
+                x += step_x;
+            }
+            if error2 <= dx {
+                error += dx;
+                y += step_y;
+            }
+        }
+        true
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //an open floor with a wall along x from y0 to y1 included
+    fn grid_with_wall(x : usize, y0 : usize, y1 : usize) -> Grid {
+        let mut grid = Grid::new_floor_grid();
+        for y in y0..=y1 {
+            grid.set((x, y), TileType::Wall);
+        }
+        grid
+    }
+
+    fn is_adjacent(a : (usize, usize), b : (usize, usize)) -> bool {
+        a != b && (a.0 as i32 - b.0 as i32).abs() <= 1 && (a.1 as i32 - b.1 as i32).abs() <= 1
+    }
+
+    #[test]
+    fn straight_path_on_open_floor() {
+        let grid = Grid::new_floor_grid();
+        let path = grid.find_path((2, 3), (7, 3)).unwrap();
+        assert_eq!(path, vec![(3, 3), (4, 3), (5, 3), (6, 3), (7, 3)]);
+    }
+
+    #[test]
+    fn path_goes_around_a_wall() {
+        let grid = grid_with_wall(5, 0, 10);
+        let path = grid.find_path((2, 2), (8, 2)).unwrap();
+        assert_eq!(*path.last().unwrap(), (8, 2));
+        let mut previous = (2, 2);
+        for tile in path {
+            assert!(is_adjacent(previous, tile));
+            assert!(grid.get(tile).is_walkable());
+            previous = tile;
+        }
+    }
+
+    #[test]
+    fn blocked_path_is_none() {
+        //the wall cuts the map in two
+        let grid = grid_with_wall(5, 0, GRID_SIZE - 1);
+        assert_eq!(grid.find_path((2, 2), (8, 2)), None);
+        //and walls can't be walked to
+        assert_eq!(grid.find_path((2, 2), (5, 2)), None);
+    }
+
+    #[test]
+    fn path_to_where_you_stand_is_empty() {
+        let grid = Grid::new_floor_grid();
+        assert_eq!(grid.find_path((4, 4), (4, 4)), Some(Vec::new()));
+    }
+
+    #[test]
+    fn straight_line_of_sight() {
+        let grid = grid_with_wall(5, 4, 4);
+        assert!(grid.has_line_of_sight((1, 2), (9, 2)));
+        assert!(grid.has_line_of_sight((3, 0), (3, 9)));
+        assert!(!grid.has_line_of_sight((1, 4), (9, 4)));
+        //the wall itself can be seen, just not what is behind it
+        assert!(grid.has_line_of_sight((1, 4), (5, 4)));
+    }
+
+    #[test]
+    fn diagonal_line_of_sight() {
+        let mut grid = Grid::new_floor_grid();
+        assert!(grid.has_line_of_sight((0, 0), (6, 6)));
+        assert!(grid.has_line_of_sight((6, 0), (0, 6)));
+        grid.set((3, 3), TileType::Wall);
+        assert!(!grid.has_line_of_sight((0, 0), (6, 6)));
+        assert!(!grid.has_line_of_sight((6, 6), (0, 0)));
+        //water doesn't block sight, lava does
+        grid.set((3, 3), TileType::Water);
+        assert!(grid.has_line_of_sight((0, 0), (6, 6)));
+        grid.set((3, 3), TileType::Lava);
+        assert!(!grid.has_line_of_sight((0, 0), (6, 6)));
+    }
+
+    #[test]
+    fn out_of_bounds_targets() {
+        let grid = Grid::new_floor_grid();
+        assert_eq!(grid.find_path((2, 2), (GRID_SIZE, 2)), None);
+        assert_eq!(grid.find_path((GRID_SIZE + 5, 2), (2, 2)), None);
+        assert!(!grid.has_line_of_sight((2, 2), (GRID_SIZE + 10, 2)));
+        assert!(!grid.has_line_of_sight((2, GRID_SIZE), (2, 2)));
+    }
+}
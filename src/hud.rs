@@ -0,0 +1,239 @@
+use bevy::prelude::*;
+
+use crate::abilities::{ClassResource, ResourceKind};
+use crate::creature::{Health, Level, MaxHealth};
+use crate::experience::{xp_to_next_level, Experience};
+use crate::game_state::{GameState, PlayingState};
+use crate::game_world::GameWorld;
+use crate::gold::Gold;
+use crate::main_menu_plugin::menu::TEXT_COLOR;
+use crate::player::Player;
+use crate::position::Position;
+use crate::util::despawn_screen;
+
+const BAR_WIDTH: f32 = 240.0;
+const BAR_HEIGHT: f32 = 18.0;
+const XP_BAR_HEIGHT: f32 = 6.0;
+const BAR_BACKGROUND: Color = Color::rgb(0.2, 0.2, 0.2);
+const HEALTH_COLOR: Color = Color::rgb(0.8, 0.15, 0.15);
+const MANA_COLOR: Color = Color::rgb(0.2, 0.4, 0.9);
+const STAMINA_COLOR: Color = Color::rgb(0.85, 0.75, 0.2);
+const XP_COLOR: Color = Color::rgb(0.6, 0.3, 0.9);
+
+// Tag component used to tag the HUD
+#[derive(Component)]
+pub struct Hud;
+
+// The filled part of a bar of the HUD, its width follows the value
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudBar {
+    Health,
+    Resource,
+    Xp,
+}
+
+// A text of the HUD
+#[derive(Component, Clone, Copy, PartialEq, Eq)]
+pub enum HudLabel {
+    Health,
+    Resource,
+    //depth and floor type
+    Floor,
+    //level and xp
+    Level,
+    Gold,
+    Timer,
+}
+
+//counts the time played, pauses and menus don't count
+pub fn tick_run_timer(time: Res<Time>, mut playing_state: ResMut<PlayingState>) {
+    playing_state.timer += time.delta();
+}
+
+//e.g. "1:05:09" or "5:09"
+pub fn format_play_time(seconds : u64) -> String {
+    let (hours, minutes, seconds) = (seconds / 3600, seconds / 60 % 60, seconds % 60);
+    if hours > 0 {
+        format!("{}:{:02}:{:02}", hours, minutes, seconds)
+    } else {
+        format!("{}:{:02}", minutes, seconds)
+    }
+}
+
+//the HUD stays up while paused, so it is only spawned once per run
+pub fn spawn_hud(mut commands: Commands, huds: Query<(), With<Hud>>) {
+    if !huds.is_empty() {
+        return;
+    }
+    let text_style = TextStyle {
+        font_size: 18.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(20.0),
+                    left: Val::Px(20.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    row_gap: Val::Px(4.0),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            Hud,
+        ))
+        .with_children(|parent| {
+            spawn_bar(parent, HudBar::Health, HEALTH_COLOR, BAR_HEIGHT, Some((HudLabel::Health, &text_style)));
+            spawn_bar(parent, HudBar::Resource, MANA_COLOR, BAR_HEIGHT, Some((HudLabel::Resource, &text_style)));
+            for label in [HudLabel::Floor, HudLabel::Level] {
+                parent.spawn((TextBundle::from_section("", text_style.clone()), label));
+            }
+            spawn_bar(parent, HudBar::Xp, XP_COLOR, XP_BAR_HEIGHT, None);
+            parent
+                .spawn(NodeBundle {
+                    style: Style {
+                        justify_content: JustifyContent::SpaceBetween,
+                        ..default()
+                    },
+                    ..default()
+                })
+                .with_children(|parent| {
+                    for label in [HudLabel::Gold, HudLabel::Timer] {
+                        parent.spawn((TextBundle::from_section("", text_style.clone()), label));
+                    }
+                });
+        });
+}
+
+//a background with the filled part on top, and the label over both
+fn spawn_bar(parent : &mut ChildBuilder, bar : HudBar, color : Color, height : f32, label : Option<(HudLabel, &TextStyle)>) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                width: Val::Px(BAR_WIDTH),
+                height: Val::Px(height),
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::Center,
+                ..default()
+            },
+            background_color: BAR_BACKGROUND.into(),
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        left: Val::Px(0.0),
+                        top: Val::Px(0.0),
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        ..default()
+                    },
+                    background_color: color.into(),
+                    ..default()
+                },
+                bar,
+            ));
+            if let Some((label, text_style)) = label {
+                parent.spawn((
+                    TextBundle::from_section(
+                        "",
+                        TextStyle {
+                            font_size: height - 2.0,
+                            ..text_style.clone()
+                        },
+                    ),
+                    label,
+                ));
+            }
+        });
+}
+
+//what the HUD shows about the player
+type HudPlayer = (
+    &'static Health,
+    &'static MaxHealth,
+    Option<&'static ClassResource>,
+    &'static Position,
+    &'static Level,
+    Option<&'static Experience>,
+    Option<&'static Gold>,
+);
+
+pub fn update_hud(
+    players: Query<HudPlayer, With<Player>>,
+    game_world: Res<GameWorld>,
+    playing_state: Res<PlayingState>,
+    mut bars: Query<(&HudBar, &mut Style, &mut BackgroundColor)>,
+    mut labels: Query<(&HudLabel, &mut Text)>,
+) {
+    let Ok((health, max_health, resource, pos, level, experience, gold)) = players.get_single() else {
+        return;
+    };
+    let xp = experience.map_or(0, |experience| experience.xp);
+    let xp_needed = xp_to_next_level(level.0).max(1);
+    let fraction = |current : f32, max : f32| if max > 0.0 { (current / max).clamp(0.0, 1.0) } else { 0.0 };
+    for (bar, mut style, mut color) in bars.iter_mut() {
+        let filled = match bar {
+            HudBar::Health => fraction(health.0 as f32, max_health.0 as f32),
+            HudBar::Resource => resource.map_or(0.0, |resource| fraction(resource.current, resource.max)),
+            HudBar::Xp => fraction(xp as f32, xp_needed as f32),
+        };
+        let width = Val::Percent(filled * 100.0);
+        if style.width != width {
+            style.width = width;
+        }
+        if let (HudBar::Resource, Some(resource)) = (bar, resource) {
+            let wanted = match resource.kind {
+                ResourceKind::Mana => MANA_COLOR,
+                ResourceKind::Stamina => STAMINA_COLOR,
+            };
+            if color.0 != wanted {
+                color.0 = wanted;
+            }
+        }
+    }
+    let floor_type = game_world.floors.get(pos.2).map_or("", |floor| floor.floor_type.name());
+    for (label, mut text) in labels.iter_mut() {
+        let value = match label {
+            HudLabel::Health => format!("{} / {}", health.0, max_health.0),
+            HudLabel::Resource => resource.map_or(String::new(), |resource| {
+                let name = match resource.kind {
+                    ResourceKind::Mana => "Mana",
+                    ResourceKind::Stamina => "Stamina",
+                };
+                format!("{} {:.0} / {:.0}", name, resource.current, resource.max)
+            }),
+            HudLabel::Floor => format!("Depth {} - {}", pos.2 + 1, floor_type),
+            HudLabel::Level => format!("Level {}  XP {} / {}", level.0, xp, xp_needed),
+            HudLabel::Gold => format!("{} gold", gold.map_or(0, |gold| gold.0)),
+            HudLabel::Timer => format_play_time(playing_state.timer.as_secs()),
+        };
+        //only touch the text when it changes, so it isn't laid out again every frame
+        if text.sections[0].value != value {
+            text.sections[0].value = value;
+        }
+    }
+}
+
+pub struct HudPlugin;
+
+impl Plugin for HudPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<PlayingState>()
+            .add_systems(OnEnter(GameState::Playing), spawn_hud)
+            .add_systems(
+                Update,
+                (tick_run_timer, update_hud)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<Hud>);
+    }
+}
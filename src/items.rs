@@ -0,0 +1,649 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::actors::Actor;
+use crate::combat_stats::StatModifiers;
+use crate::consumables::{Appearance, ConsumableCategory, ConsumableEffect, Identification, UseItemEvent};
+use crate::data_asset::{refresh_ron_resource, RonAsset, RonAssetLoader, RonResource};
+use crate::equipment::{EquipEvent, Equipment, EquipmentSlot, SlotKind, UnequipEvent};
+use crate::faction::Faction;
+use crate::game_over::RunStats;
+use crate::game_state::GameState;
+use crate::grid::TILE_SIZE;
+use crate::loot::{GeneratedItem, Rarity};
+use crate::main_menu_plugin::menu::{HOVERED_BUTTON, NORMAL_BUTTON, TEXT_COLOR};
+use crate::melee::WeaponStats;
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::{Player, PlayerClass};
+use crate::position::Position;
+use crate::save_data::{SaveData, SavedFloorItem};
+use crate::util::despawn_screen;
+
+pub const ITEMS_PATH: &str = "data/items.items.ron";
+//number of stacks the player can carry
+pub const INVENTORY_CAPACITY: usize = 20;
+//how close (in tiles) an item has to be to be picked up
+pub const PICKUP_RADIUS: f32 = 0.75;
+pub const PICKUP_KEY: KeyCode = KeyCode::G;
+pub const INVENTORY_KEY: KeyCode = KeyCode::I;
+//shown for items missing from the items file
+pub const MISSING_ITEM_SPRITE: &str = "item/misc/misc_rune.png";
+
+fn default_max_stack() -> u32 {
+    1
+}
+
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum ItemKind {
+    //worn in an equipment slot, see equipment.rs and combat_stats.rs
+    Equipment {
+        slot: SlotKind,
+        //how melee swings look with the item as a weapon
+        #[serde(default)]
+        weapon: Option<WeaponStats>,
+        #[serde(default)]
+        modifiers: StatModifiers,
+        //reputation gained with factions while the item is worn
+        #[serde(default)]
+        reputation: Vec<(Faction, i32)>,
+    },
+    //used up from the inventory, see consumables.rs
+    Consumable {
+        effect: ConsumableEffect,
+        //consumables with a category look like one of its appearances until identified
+        #[serde(default)]
+        category: Option<ConsumableCategory>,
+    },
+    Misc,
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct ItemDefinition {
+    pub name: String,
+    pub sprite: String,
+    //how many fit in a single inventory slot
+    #[serde(default = "default_max_stack")]
+    pub max_stack: u32,
+    pub kind: ItemKind,
+    //base price in gold, see shops.rs
+    #[serde(default)]
+    pub value: u32,
+    //set on items rolled from loot tables, see loot.rs
+    #[serde(default)]
+    pub rarity: Rarity,
+}
+
+//The contents of the items file, every item by id
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "7d9c2e51-6a4b-4f3e-8b1d-0c5e9f2a6b37"]
+pub struct ItemsAsset {
+    pub items: HashMap<String, ItemDefinition>,
+    //what unidentified consumables can look like, per category
+    #[serde(default)]
+    pub appearances: HashMap<ConsumableCategory, Vec<Appearance>>,
+}
+
+impl RonAsset for ItemsAsset {
+    const EXTENSIONS: &'static [&'static str] = &["items.ron"];
+}
+
+//The item definitions of the items file, plus the items rolled during the run
+#[derive(Resource)]
+pub struct Items {
+    pub handle: Handle<ItemsAsset>,
+    pub items: HashMap<String, ItemDefinition>,
+    pub appearances: HashMap<ConsumableCategory, Vec<Appearance>>,
+    //items rolled from loot tables by id, their definitions are added to items
+    pub generated: HashMap<String, GeneratedItem>,
+    pub next_generated: u64,
+}
+
+impl Items {
+    pub fn get(&self, id : &str) -> Option<&ItemDefinition> {
+        self.items.get(id)
+    }
+    pub fn name(&self, id : &str) -> String {
+        self.get(id).map_or(id.to_string(), |item| item.name.clone())
+    }
+    pub fn sprite(&self, id : &str) -> &str {
+        self.get(id).map_or(MISSING_ITEM_SPRITE, |item| item.sprite.as_str())
+    }
+    pub fn max_stack(&self, id : &str) -> u32 {
+        self.get(id).map_or(1, |item| item.max_stack.max(1))
+    }
+    pub fn rarity(&self, id : &str) -> Rarity {
+        self.get(id).map_or(Rarity::Common, |item| item.rarity)
+    }
+    //gives a rolled item its own id, e.g. "long_sword#3"
+    pub fn register_generated(&mut self, generated : GeneratedItem) -> String {
+        let id = format!("{}#{}", generated.base, self.next_generated);
+        self.next_generated += 1;
+        if let Some(base) = self.items.get(&generated.base) {
+            let definition = generated.definition(base);
+            self.items.insert(id.clone(), definition);
+        }
+        self.generated.insert(id.clone(), generated);
+        id
+    }
+    pub fn clear_generated(&mut self) {
+        for id in self.generated.keys() {
+            self.items.remove(id);
+        }
+        self.generated.clear();
+        self.next_generated = 0;
+    }
+    pub fn save_generated(&self, save_data : &mut SaveData) {
+        save_data.generated_items = self.generated.clone();
+        save_data.next_generated_item = self.next_generated;
+    }
+    //gives the rolled items of a save their definitions back, before the items that refer to them are loaded
+    pub fn load_generated(&mut self, save_data : &SaveData) {
+        for (id, generated) in save_data.generated_items.iter() {
+            if let Some(base) = self.items.get(&generated.base) {
+                let definition = generated.definition(base);
+                self.items.insert(id.clone(), definition);
+            }
+            self.generated.insert(id.clone(), generated.clone());
+        }
+        //ids are never reused
+        self.next_generated = self.next_generated.max(save_data.next_generated_item);
+    }
+}
+
+//Some amount of a single item, by id
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ItemStack {
+    pub id: String,
+    pub count: u32,
+}
+
+impl ItemStack {
+    pub fn new(id : &str, count : u32) -> Self {
+        ItemStack {
+            id: id.to_string(),
+            count,
+        }
+    }
+}
+
+//An item lying on a floor, it stays there when the player changes floors
+#[derive(Component, Debug, Clone)]
+pub struct FloorItem(pub ItemStack);
+
+#[derive(Component, Debug, Clone, Serialize, Deserialize)]
+pub struct Inventory {
+    pub stacks: Vec<ItemStack>,
+    //max number of stacks
+    pub capacity: usize,
+}
+
+impl Default for Inventory {
+    fn default() -> Self {
+        Inventory {
+            stacks: Vec::new(),
+            capacity: INVENTORY_CAPACITY,
+        }
+    }
+}
+
+impl Inventory {
+    pub fn for_class(class : PlayerClass) -> Self {
+        Inventory {
+            stacks: class
+                .definition()
+                .starting_kit
+                .iter()
+                .map(|id| ItemStack::new(id, 1))
+                .collect(),
+            ..Default::default()
+        }
+    }
+
+    //adds as much of the stack as there is room for, returns what didn't fit
+    pub fn add(&mut self, mut stack : ItemStack, max_stack : u32) -> Option<ItemStack> {
+        for existing in self.stacks.iter_mut().filter(|existing| existing.id == stack.id) {
+            let moved = stack.count.min(max_stack.saturating_sub(existing.count));
+            existing.count += moved;
+            stack.count -= moved;
+        }
+        while stack.count > 0 && self.stacks.len() < self.capacity {
+            let moved = stack.count.min(max_stack);
+            self.stacks.push(ItemStack::new(&stack.id, moved));
+            stack.count -= moved;
+        }
+        if stack.count > 0 {
+            Some(stack)
+        } else {
+            None
+        }
+    }
+
+    //removes up to count items from a slot
+    pub fn take(&mut self, slot : usize, count : u32) -> Option<ItemStack> {
+        let existing = self.stacks.get_mut(slot)?;
+        let taken = ItemStack::new(&existing.id, count.min(existing.count));
+        existing.count -= taken.count;
+        if existing.count == 0 {
+            self.stacks.remove(slot);
+        }
+        Some(taken)
+    }
+}
+
+//sent to drop a whole inventory slot on the ground
+#[derive(Event)]
+pub struct DropItemEvent {
+    pub entity: Entity,
+    pub slot: usize,
+}
+
+pub fn spawn_floor_item(
+    commands : &mut Commands,
+    asset_server : &AssetServer,
+    items : &Items,
+    stack : ItemStack,
+    position : Position,
+) -> Entity {
+    commands
+        .spawn((
+            Actor::default(),
+            SpriteBundle {
+                texture: asset_server.load(items.sprite(&stack.id)),
+                //under the creatures, over the tiles
+                transform: Transform::from_xyz(position.0, position.1, 0.5),
+                ..default()
+            },
+            position,
+            FloorItem(stack),
+        ))
+        .id()
+}
+
+//the player's inventory and equipment and every item lying on a floor, as they are stored in a save
+pub fn save_items<'a>(
+    save_data : &mut SaveData,
+    inventory : &Inventory,
+    equipment : &Equipment,
+    floor_items : impl Iterator<Item = (&'a FloorItem, &'a Position)>,
+) {
+    save_data.inventory = inventory.stacks.clone();
+    save_data.equipment = equipment.slots.clone();
+    save_data.floor_items = floor_items
+        .map(|(item, pos)| SavedFloorItem {
+            stack: item.0.clone(),
+            x: pos.0,
+            y: pos.1,
+            level: pos.2,
+        })
+        .collect();
+}
+
+//puts the items of a save back on their floors, returns the inventory and equipment to give to the player
+pub fn load_items(
+    save_data : &SaveData,
+    commands : &mut Commands,
+    asset_server : &AssetServer,
+    items : &Items,
+) -> (Inventory, Equipment) {
+    for saved in save_data.floor_items.iter() {
+        spawn_floor_item(
+            commands,
+            asset_server,
+            items,
+            saved.stack.clone(),
+            Position(saved.x, saved.y, saved.level),
+        );
+    }
+    (
+        Inventory {
+            stacks: save_data.inventory.clone(),
+            ..Default::default()
+        },
+        Equipment {
+            slots: save_data.equipment.clone(),
+        },
+    )
+}
+
+pub fn load_items_file(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Items {
+        handle: asset_server.load(ITEMS_PATH),
+        items: HashMap::new(),
+        appearances: HashMap::new(),
+        generated: HashMap::new(),
+        next_generated: 0,
+    });
+}
+
+impl RonResource for Items {
+    type Asset = ItemsAsset;
+    fn handle(&self) -> &Handle<ItemsAsset> {
+        &self.handle
+    }
+    fn refresh(&mut self, asset : &ItemsAsset) {
+        self.items = asset.items.clone();
+        self.appearances = asset.appearances.clone();
+        //rolled items follow the changes of their base item
+        let generated : Vec<(String, ItemDefinition)> = self
+            .generated
+            .iter()
+            .filter_map(|(id, generated)| Some((id.clone(), generated.definition(self.items.get(&generated.base)?))))
+            .collect();
+        self.items.extend(generated);
+        info!("items loaded with {} items", self.items.len());
+    }
+}
+
+//unidentified consumables on the floors show their appearance of the run
+pub fn update_floor_item_sprites(
+    items: Res<Items>,
+    identification: Res<Identification>,
+    mut floor_items: Query<(Ref<FloorItem>, &mut Handle<Image>)>,
+    asset_server: Res<AssetServer>,
+) {
+    let refresh_all = items.is_changed() || identification.is_changed();
+    for (item, mut texture) in floor_items.iter_mut() {
+        if refresh_all || item.is_changed() {
+            *texture = asset_server.load(identification.sprite(&items, &item.0.id));
+        }
+    }
+}
+
+//what it takes to name an item the way the player knows it
+#[derive(SystemParam)]
+pub struct ItemNames<'w> {
+    items: Res<'w, Items>,
+    identification: Res<'w, Identification>,
+}
+
+//picks up everything close enough to the player, what doesn't fit stays on the floor
+pub fn pickup_items(
+    keys: Res<Input<KeyCode>>,
+    mut players: Query<(&Position, &mut Inventory), With<Player>>,
+    mut floor_items: Query<(Entity, &Position, &mut FloorItem)>,
+    names: ItemNames,
+    mut log: ResMut<MessageLog>,
+    mut run_stats: ResMut<RunStats>,
+    mut commands: Commands,
+) {
+    if !keys.just_pressed(PICKUP_KEY) {
+        return;
+    }
+    let ItemNames { items, identification } = names;
+    let Ok((pos, mut inventory)) = players.get_single_mut() else {
+        return;
+    };
+    for (entity, item_pos, mut item) in floor_items.iter_mut() {
+        if item_pos.2 != pos.2 || pos.distance(item_pos) > PICKUP_RADIUS * TILE_SIZE {
+            continue;
+        }
+        let max_stack = items.max_stack(&item.0.id);
+        //a full inventory still tops up the stacks it has, add gives back what didn't fit
+        match inventory.add(item.0.clone(), max_stack) {
+            Some(left) => {
+                if left.count != item.0.count {
+                    run_stats.items_found += item.0.count - left.count;
+                    item.0 = left;
+                }
+                log.add(MessageKind::Warning, "Your inventory is full");
+            }
+            None => {
+                let mut name = identification.name(&items, &item.0.id);
+                if item.0.count > 1 {
+                    name = format!("{} ({})", name, item.0.count);
+                }
+                log.add(MessageKind::Loot, format!("You pick up {}", name));
+                run_stats.items_found += item.0.count;
+                commands.entity(entity).despawn_recursive();
+            }
+        }
+    }
+}
+
+pub fn drop_items(
+    mut drop_events: EventReader<DropItemEvent>,
+    mut holders: Query<(&Position, &mut Inventory)>,
+    items: Res<Items>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for event in drop_events.iter() {
+        let Ok((pos, mut inventory)) = holders.get_mut(event.entity) else {
+            continue;
+        };
+        let Some(count) = inventory.stacks.get(event.slot).map(|stack| stack.count) else {
+            continue;
+        };
+        if let Some(dropped) = inventory.take(event.slot, count) {
+            spawn_floor_item(&mut commands, &asset_server, &items, dropped, pos.clone());
+        }
+    }
+}
+
+// Tag component used to tag the inventory panel
+#[derive(Component)]
+pub struct InventoryPanel;
+
+// What a button of the inventory panel does with its slot
+#[derive(Component, Clone, Copy)]
+pub enum InventoryAction {
+    Drop(usize),
+    Equip(usize),
+    Use(usize),
+    Unequip(EquipmentSlot),
+}
+
+//whether the inventory panel is shown, toggled with INVENTORY_KEY
+#[derive(Resource, Default)]
+pub struct InventoryOpen(pub bool);
+
+pub fn toggle_inventory(keys: Res<Input<KeyCode>>, mut open: ResMut<InventoryOpen>) {
+    if keys.just_pressed(INVENTORY_KEY) {
+        open.0 = !open.0;
+    }
+}
+
+//rebuilds the panel when it is toggled or the inventory changes
+pub fn update_inventory_panel(
+    open: Res<InventoryOpen>,
+    players: Query<(Ref<Inventory>, Ref<Equipment>), With<Player>>,
+    panels: Query<Entity, With<InventoryPanel>>,
+    items: Res<Items>,
+    identification: Res<Identification>,
+    mut commands: Commands,
+) {
+    let Ok((inventory, equipment)) = players.get_single() else {
+        return;
+    };
+    if !open.is_changed()
+        && !inventory.is_changed()
+        && !equipment.is_changed()
+        && !items.is_changed()
+        && !identification.is_changed()
+    {
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    if !open.0 {
+        return;
+    }
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            InventoryPanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section("Equipment", text_style.clone()));
+            for slot in EquipmentSlot::ALL {
+                let worn = equipment.slots.get(&slot);
+                let label = format!("{}: {}", slot.name(), worn.map_or("-".to_string(), |id| items.name(id)));
+                let color = worn.map_or(TEXT_COLOR, |id| items.rarity(id).color());
+                let actions : Vec<(InventoryAction, &str)> = if worn.is_some() {
+                    vec![(InventoryAction::Unequip(slot), "Remove")]
+                } else {
+                    Vec::new()
+                };
+                spawn_inventory_row(parent, label, color, &actions, &text_style);
+            }
+            parent.spawn(TextBundle::from_section(
+                format!("Inventory ({}/{})", inventory.stacks.len(), inventory.capacity),
+                text_style.clone(),
+            ));
+            for (slot, stack) in inventory.stacks.iter().enumerate() {
+                let mut label = identification.name(&items, &stack.id);
+                if !identification.is_known(&stack.id) {
+                    label.push_str(" (unidentified)");
+                }
+                if stack.count > 1 {
+                    label = format!("{} x{}", label, stack.count);
+                }
+                let mut actions = Vec::new();
+                match items.get(&stack.id).map(|item| &item.kind) {
+                    Some(ItemKind::Equipment { .. }) => actions.push((InventoryAction::Equip(slot), "Equip")),
+                    Some(ItemKind::Consumable { .. }) => actions.push((InventoryAction::Use(slot), "Use")),
+                    _ => {}
+                }
+                actions.push((InventoryAction::Drop(slot), "Drop"));
+                let color = items.rarity(&stack.id).color();
+                spawn_inventory_row(parent, label, color, &actions, &text_style);
+            }
+        });
+}
+
+//a line of the inventory (or trade) panel, a label in the item's rarity color followed by its buttons
+pub fn spawn_inventory_row<A : Component + Copy>(
+    parent : &mut ChildBuilder,
+    label : String,
+    label_color : Color,
+    actions : &[(A, &str)],
+    text_style : &TextStyle,
+) {
+    parent
+        .spawn(NodeBundle {
+            style: Style {
+                align_items: AlignItems::Center,
+                justify_content: JustifyContent::SpaceBetween,
+                ..default()
+            },
+            ..default()
+        })
+        .with_children(|parent| {
+            parent.spawn(TextBundle::from_section(
+                label,
+                TextStyle {
+                    color: label_color,
+                    ..text_style.clone()
+                },
+            ));
+            for (action, text) in actions {
+                parent
+                    .spawn((
+                        ButtonBundle {
+                            style: Style {
+                                width: Val::Px(70.0),
+                                height: Val::Px(25.0),
+                                margin: UiRect::left(Val::Px(10.0)),
+                                justify_content: JustifyContent::Center,
+                                align_items: AlignItems::Center,
+                                ..default()
+                            },
+                            background_color: NORMAL_BUTTON.into(),
+                            ..default()
+                        },
+                        *action,
+                    ))
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(*text, text_style.clone()));
+                    });
+            }
+        });
+}
+
+pub fn inventory_buttons(
+    mut interaction_query: Query<(&Interaction, &InventoryAction, &mut BackgroundColor), Changed<Interaction>>,
+    players: Query<Entity, With<Player>>,
+    mut drop_events: EventWriter<DropItemEvent>,
+    mut equip_events: EventWriter<EquipEvent>,
+    mut unequip_events: EventWriter<UnequipEvent>,
+    mut use_events: EventWriter<UseItemEvent>,
+) {
+    for (interaction, action, mut color) in interaction_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            _ => NORMAL_BUTTON.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let Ok(player) = players.get_single() else {
+            continue;
+        };
+        match action {
+            InventoryAction::Drop(slot) => drop_events.send(DropItemEvent {
+                entity: player,
+                slot: *slot,
+            }),
+            InventoryAction::Equip(slot) => equip_events.send(EquipEvent {
+                entity: player,
+                inventory_slot: *slot,
+            }),
+            InventoryAction::Unequip(slot) => unequip_events.send(UnequipEvent {
+                entity: player,
+                slot: *slot,
+            }),
+            InventoryAction::Use(slot) => use_events.send(UseItemEvent {
+                entity: player,
+                inventory_slot: *slot,
+            }),
+        }
+    }
+}
+
+pub struct ItemsPlugin;
+
+impl Plugin for ItemsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<ItemsAsset>()
+            .init_asset_loader::<RonAssetLoader<ItemsAsset>>()
+            .init_resource::<InventoryOpen>()
+            .add_event::<DropItemEvent>()
+            .add_systems(Startup, load_items_file)
+            .add_systems(Update, refresh_ron_resource::<Items>)
+            .add_systems(
+                Update,
+                (
+                    pickup_items,
+                    inventory_buttons,
+                    drop_items,
+                    update_floor_item_sprites,
+                    toggle_inventory,
+                    update_inventory_panel,
+                )
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<InventoryPanel>);
+    }
+}
@@ -0,0 +1,495 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use rand::seq::SliceRandom;
+use rand::Rng;
+use serde::{Deserialize, Serialize};
+
+use crate::actors::Actor;
+use crate::combat::{handle_deaths, resolve_attacks, DeathEvent};
+use crate::combat_stats::{DamageType, StatModifiers};
+use crate::creature::{CreatureType, Monster};
+use crate::data_asset::{refresh_ron_resource, RonAsset, RonAssetLoader, RonResource};
+use crate::equipment::SlotKind;
+use crate::floor::FloorType;
+use crate::game_plugin::game::FloorGenerated;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::gold::{roll_gold, spawn_gold_pile};
+use crate::grid::TILE_SIZE;
+use crate::items::{spawn_floor_item, ItemDefinition, ItemKind, ItemStack, Items, PICKUP_KEY, PICKUP_RADIUS};
+use crate::player::Player;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::spawning::{populate_new_floors, spawn_candidates, MIN_DISTANCE_FROM_STAIRS};
+use crate::tiles::TileType;
+use crate::traps::place_traps;
+
+pub const LOOT_PATH: &str = "data/loot.loot.ron";
+//chests on the first floor, one more every four floors
+pub const BASE_CHESTS_PER_FLOOR: usize = 1;
+pub const MAX_CHESTS_PER_FLOOR: usize = 4;
+pub const CHEST_SPRITE: &str = "dungeon/chest_2_closed.png";
+pub const OPEN_CHEST_SPRITE: &str = "dungeon/chest_2_open.png";
+//chests hold more gold than the piles lying around
+pub const CHEST_GOLD_MULTIPLIER: u32 = 3;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord, Default, Serialize, Deserialize)]
+pub enum Rarity {
+    #[default]
+    Common,
+    Uncommon,
+    Rare,
+    Epic,
+    Legendary,
+}
+
+impl Rarity {
+    pub fn affix_count(&self) -> usize {
+        match self {
+            Rarity::Common => 0,
+            Rarity::Uncommon => 1,
+            Rarity::Rare => 2,
+            Rarity::Epic | Rarity::Legendary => 3,
+        }
+    }
+
+    //what the rarity does to the price of an item in shops
+    pub fn price_multiplier(&self) -> f32 {
+        match self {
+            Rarity::Common => 1.0,
+            Rarity::Uncommon => 1.5,
+            Rarity::Rare => 2.5,
+            Rarity::Epic => 4.0,
+            Rarity::Legendary => 8.0,
+        }
+    }
+
+    //the color of the item's name in the inventory
+    pub fn color(&self) -> Color {
+        match self {
+            Rarity::Common => Color::rgb(0.9, 0.9, 0.9),
+            Rarity::Uncommon => Color::rgb(0.3, 0.9, 0.3),
+            Rarity::Rare => Color::rgb(0.3, 0.5, 1.0),
+            Rarity::Epic => Color::rgb(0.7, 0.3, 0.9),
+            Rarity::Legendary => Color::rgb(1.0, 0.6, 0.1),
+        }
+    }
+}
+
+//Where a drop comes from, loot tables list the sources they apply to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Deserialize)]
+pub enum LootSource {
+    Creature(CreatureType),
+    Chest,
+    //the stock of the shops in towns, see shops.rs
+    Shop,
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootEntry {
+    pub item: String,
+    //relative chance of being picked against the other entries of the table
+    pub weight: u32,
+    //range (inclusive) of how many drop, equipment always drops one at a time
+    #[serde(default = "default_count")]
+    pub count: (u32, u32),
+}
+
+fn default_count() -> (u32, u32) {
+    (1, 1)
+}
+
+fn default_rolls() -> u32 {
+    1
+}
+
+fn deepest() -> usize {
+    usize::MAX
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct LootTable {
+    //empty for every creature, chests and shops only use the tables that list them
+    #[serde(default)]
+    pub sources: Vec<LootSource>,
+    //depths (inclusive) where the table is used
+    #[serde(default)]
+    pub min_depth: usize,
+    #[serde(default = "deepest")]
+    pub max_depth: usize,
+    //chance of each roll to drop something
+    pub drop_chance: f32,
+    #[serde(default = "default_rolls")]
+    pub rolls: u32,
+    pub entries: Vec<LootEntry>,
+}
+
+impl LootTable {
+    pub fn applies_to(&self, source : LootSource, depth : usize) -> bool {
+        let source_matches = if self.sources.is_empty() {
+            matches!(source, LootSource::Creature(_))
+        } else {
+            self.sources.contains(&source)
+        };
+        source_matches && self.min_depth <= depth && depth <= self.max_depth
+    }
+}
+
+//How likely a rarity is, deeper floors shift the odds by per_depth every floor
+#[derive(Debug, Clone, Deserialize)]
+pub struct RarityWeight {
+    pub rarity: Rarity,
+    pub weight: f32,
+    #[serde(default)]
+    pub per_depth: f32,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum AffixPosition {
+    //before the item's name, e.g. "Sharp Dagger"
+    Prefix,
+    //after the item's name, e.g. "Dagger of the Bear"
+    Suffix,
+}
+
+//The stat an affix adds to, see combat_stats::StatModifiers
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub enum AffixStat {
+    AttackBonus,
+    ExtraAttackRolls,
+    Defense,
+    Speed,
+    Resistance(DamageType),
+}
+
+impl AffixStat {
+    pub fn apply(&self, modifiers : &mut StatModifiers, value : f32) {
+        match self {
+            AffixStat::AttackBonus => modifiers.attack_bonus += value.round() as i32,
+            AffixStat::ExtraAttackRolls => modifiers.extra_attack_rolls += value.round().max(0.0) as u32,
+            AffixStat::Defense => modifiers.defense += value.round().max(0.0) as u32,
+            AffixStat::Speed => modifiers.speed += value,
+            AffixStat::Resistance(damage_type) => *modifiers.resistances.entry(*damage_type).or_insert(0.0) += value,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Deserialize)]
+pub struct Affix {
+    pub name: String,
+    pub position: AffixPosition,
+    pub stat: AffixStat,
+    //range (inclusive) of the rolled value, legendary items always get the max
+    pub min: f32,
+    pub max: f32,
+    //the kinds of equipment the affix can roll on, empty for every kind
+    #[serde(default)]
+    pub slots: Vec<SlotKind>,
+}
+
+//An affix as it was rolled on an item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RolledAffix {
+    pub name: String,
+    pub position: AffixPosition,
+    pub stat: AffixStat,
+    pub value: f32,
+}
+
+//An item rolled from a loot table, stored by the items resource under its own id so inventories,
+//equipment and saves can refer to it like to any other item
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct GeneratedItem {
+    //id of the item it is based on
+    pub base: String,
+    pub rarity: Rarity,
+    pub affixes: Vec<RolledAffix>,
+    //name of a legendary item, e.g. "Doombringer"
+    #[serde(default)]
+    pub title: Option<String>,
+}
+
+impl GeneratedItem {
+    //the base item with the affixes added and a generated name
+    pub fn definition(&self, base : &ItemDefinition) -> ItemDefinition {
+        let mut definition = base.clone();
+        definition.rarity = self.rarity;
+        if let ItemKind::Equipment { modifiers, .. } = &mut definition.kind {
+            for affix in self.affixes.iter() {
+                affix.stat.apply(modifiers, affix.value);
+            }
+        }
+        definition.name = match &self.title {
+            Some(title) => format!("{}, {}", title, base.name),
+            None => {
+                let mut name = base.name.clone();
+                if let Some(prefix) = self.affixes.iter().find(|affix| affix.position == AffixPosition::Prefix) {
+                    name = format!("{} {}", prefix.name, name);
+                }
+                if let Some(suffix) = self.affixes.iter().find(|affix| affix.position == AffixPosition::Suffix) {
+                    name = format!("{} {}", name, suffix.name);
+                }
+                name
+            }
+        };
+        definition
+    }
+}
+
+//The contents of the loot file
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "e1a6c3d8-2f4b-4c7e-9a5d-8b3f0e6c1d29"]
+pub struct LootAsset {
+    pub rarities: Vec<RarityWeight>,
+    pub affixes: Vec<Affix>,
+    //legendary titles are made of a random first and second part, e.g. "Doom" + "bringer"
+    pub legendary_names: (Vec<String>, Vec<String>),
+    pub tables: Vec<LootTable>,
+}
+
+impl RonAsset for LootAsset {
+    const EXTENSIONS: &'static [&'static str] = &["loot.ron"];
+}
+
+//The rarities, affixes and loot tables of the loot file
+#[derive(Resource)]
+pub struct Loot {
+    pub handle: Handle<LootAsset>,
+    pub rarities: Vec<RarityWeight>,
+    pub affixes: Vec<Affix>,
+    pub legendary_names: (Vec<String>, Vec<String>),
+    pub tables: Vec<LootTable>,
+}
+
+impl Loot {
+    pub fn roll_rarity(&self, depth : usize, rng : &mut impl Rng) -> Rarity {
+        self.rarities
+            .choose_weighted(rng, |rarity| (rarity.weight + rarity.per_depth * depth as f32).max(0.0))
+            .map_or(Rarity::Common, |rarity| rarity.rarity)
+    }
+
+    //rolls the rarity and affixes of a piece of equipment, None when it stays a plain common item
+    pub fn roll_equipment(&self, items : &Items, base : &str, depth : usize, rng : &mut impl Rng) -> Option<GeneratedItem> {
+        let Some(ItemKind::Equipment { slot, .. }) = items.get(base).map(|item| &item.kind) else {
+            return None;
+        };
+        let rarity = self.roll_rarity(depth, rng);
+        if rarity == Rarity::Common {
+            return None;
+        }
+        let candidates : Vec<&Affix> = self
+            .affixes
+            .iter()
+            .filter(|affix| affix.slots.is_empty() || affix.slots.contains(slot))
+            .collect();
+        let affixes = candidates
+            .choose_multiple(rng, rarity.affix_count())
+            .map(|affix| RolledAffix {
+                name: affix.name.clone(),
+                position: affix.position,
+                stat: affix.stat,
+                value: if rarity == Rarity::Legendary || affix.min >= affix.max {
+                    affix.max
+                } else {
+                    rng.gen_range(affix.min..=affix.max)
+                },
+            })
+            .collect();
+        let title = if rarity == Rarity::Legendary {
+            let (firsts, seconds) = &self.legendary_names;
+            match (firsts.choose(rng), seconds.choose(rng)) {
+                (Some(first), Some(second)) => Some(format!("{}{}", first, second)),
+                _ => None,
+            }
+        } else {
+            None
+        };
+        Some(GeneratedItem {
+            base: base.to_string(),
+            rarity,
+            affixes,
+            title,
+        })
+    }
+
+    //rolls every table that applies to the source, generated items are registered in the items resource
+    pub fn roll_drops(&self, source : LootSource, depth : usize, items : &mut Items, rng : &mut impl Rng) -> Vec<ItemStack> {
+        let mut drops = Vec::new();
+        for table in self.tables.iter().filter(|table| table.applies_to(source, depth)) {
+            for _ in 0..table.rolls {
+                if rng.gen::<f32>() >= table.drop_chance {
+                    continue;
+                }
+                let Ok(entry) = table.entries.choose_weighted(rng, |entry| entry.weight) else {
+                    continue;
+                };
+                let count = rng.gen_range(entry.count.0..=entry.count.1.max(entry.count.0));
+                if !matches!(items.get(&entry.item).map(|item| &item.kind), Some(ItemKind::Equipment { .. })) {
+                    drops.push(ItemStack::new(&entry.item, count));
+                    continue;
+                }
+                for _ in 0..count {
+                    let id = match self.roll_equipment(items, &entry.item, depth, rng) {
+                        Some(generated) => items.register_generated(generated),
+                        None => entry.item.clone(),
+                    };
+                    drops.push(ItemStack::new(&id, 1));
+                }
+            }
+        }
+        drops
+    }
+}
+
+//A chest on a floor, opened with the pickup key
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Chest {
+    pub opened: bool,
+}
+
+pub fn spawn_chest(commands : &mut Commands, asset_server : &AssetServer, chest : Chest, position : Position) -> Entity {
+    let sprite = if chest.opened { OPEN_CHEST_SPRITE } else { CHEST_SPRITE };
+    commands
+        .spawn((
+            Actor::default(),
+            SpriteBundle {
+                texture: asset_server.load(sprite),
+                transform: Transform::from_xyz(position.0, position.1, 0.5),
+                ..default()
+            },
+            position,
+            chest,
+        ))
+        .id()
+}
+
+pub fn load_loot(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Loot {
+        handle: asset_server.load(LOOT_PATH),
+        rarities: Vec::new(),
+        affixes: Vec::new(),
+        legendary_names: (Vec::new(), Vec::new()),
+        tables: Vec::new(),
+    });
+}
+
+impl RonResource for Loot {
+    type Asset = LootAsset;
+    fn handle(&self) -> &Handle<LootAsset> {
+        &self.handle
+    }
+    fn refresh(&mut self, asset : &LootAsset) {
+        self.rarities = asset.rarities.clone();
+        self.affixes = asset.affixes.clone();
+        self.legendary_names = asset.legendary_names.clone();
+        self.tables = asset.tables.clone();
+        info!("loot loaded with {} tables", self.tables.len());
+    }
+}
+
+//the loot tables, and what rolling them needs, the generated items are registered in Items
+#[derive(SystemParam)]
+pub struct LootRolls<'w> {
+    loot: Res<'w, Loot>,
+    items: ResMut<'w, Items>,
+    run_rng: ResMut<'w, RunRng>,
+}
+
+//monsters drop their loot where they die
+pub fn drop_monster_loot(
+    mut death_events: EventReader<DeathEvent>,
+    monsters: Query<(&CreatureType, &Position), With<Monster>>,
+    rolls: LootRolls,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let LootRolls { loot, mut items, mut run_rng } = rolls;
+    for event in death_events.iter() {
+        let Ok((creature_type, pos)) = monsters.get(event.entity) else {
+            continue;
+        };
+        for stack in loot.roll_drops(LootSource::Creature(*creature_type), pos.2, &mut items, &mut run_rng.loot) {
+            spawn_floor_item(&mut commands, &asset_server, &items, stack, pos.clone());
+        }
+    }
+}
+
+//put a few chests on every newly generated floor except towns
+pub fn place_chests(
+    mut ev_floor_generated: EventReader<FloorGenerated>,
+    game_world: Res<GameWorld>,
+    asset_server: Res<AssetServer>,
+    mut run_rng: ResMut<RunRng>,
+    mut commands: Commands,
+) {
+    for event in ev_floor_generated.iter() {
+        let floor = &game_world.floors[event.level];
+        if floor.floor_type == FloorType::Town {
+            continue;
+        }
+        let mut avoid = Vec::new();
+        if let Some(upstairs) = floor.grid.find_tile(TileType::UpStairs) {
+            avoid.push((upstairs, MIN_DISTANCE_FROM_STAIRS));
+        }
+        let candidates = spawn_candidates(&floor.grid, &avoid);
+        let count = (BASE_CHESTS_PER_FLOOR + event.level / 4).min(MAX_CHESTS_PER_FLOOR);
+        for tile in candidates.choose_multiple(&mut run_rng.loot, count) {
+            spawn_chest(&mut commands, &asset_server, Chest::default(), Position::from_tile(*tile, event.level));
+        }
+    }
+}
+
+//the pickup key also opens the chests next to the player, their loot spills on the floor
+pub fn open_chests(
+    keys: Res<Input<KeyCode>>,
+    players: Query<&Position, With<Player>>,
+    mut chests: Query<(&mut Chest, &Position, &mut Handle<Image>)>,
+    rolls: LootRolls,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let LootRolls { loot, mut items, mut run_rng } = rolls;
+    if !keys.just_pressed(PICKUP_KEY) {
+        return;
+    }
+    let Ok(player_pos) = players.get_single() else {
+        return;
+    };
+    for (mut chest, pos, mut texture) in chests.iter_mut() {
+        if chest.opened || pos.2 != player_pos.2 || player_pos.distance(pos) > PICKUP_RADIUS * TILE_SIZE {
+            continue;
+        }
+        chest.opened = true;
+        *texture = asset_server.load(OPEN_CHEST_SPRITE);
+        for stack in loot.roll_drops(LootSource::Chest, pos.2, &mut items, &mut run_rng.loot) {
+            spawn_floor_item(&mut commands, &asset_server, &items, stack, pos.clone());
+        }
+        let gold = roll_gold(pos.2, &mut run_rng.loot) * CHEST_GOLD_MULTIPLIER;
+        spawn_gold_pile(&mut commands, &asset_server, gold, pos.clone());
+    }
+}
+
+pub struct LootPlugin;
+
+impl Plugin for LootPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<LootAsset>()
+            .init_asset_loader::<RonAssetLoader<LootAsset>>()
+            .add_systems(Startup, load_loot)
+            .add_systems(Update, refresh_ron_resource::<Loot>)
+            .add_systems(
+                Update,
+                //after the traps so both always use the run's rolls in the same order
+                place_chests
+                    .after(populate_new_floors)
+                    .after(place_traps)
+                    .run_if(on_event::<FloorGenerated>()),
+            )
+            .add_systems(
+                Update,
+                (open_chests, drop_monster_loot.after(resolve_attacks).before(handle_deaths))
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
@@ -13,6 +13,36 @@ mod display_quality;
 mod game_plugin;
 mod util;
 mod creature;
+mod data_asset;
+mod bestiary;
+mod run_rng;
+mod spawning;
+mod combat;
+mod ai;
+mod faction;
+mod experience;
+mod abilities;
+mod character_creation;
+mod spells;
+mod projectiles;
+mod melee;
+mod items;
+mod equipment;
+mod combat_stats;
+mod consumables;
+mod exploration;
+mod status_effects;
+mod traps;
+mod loot;
+mod gold;
+mod shops;
+mod hud;
+mod message_log;
+mod minimap;
+mod game_over;
+use std::time::Duration;
+
+use bevy::asset::ChangeWatcher;
 use bevy::prelude::*;
 
 fn setup(mut commands: Commands) {
@@ -21,17 +51,48 @@ fn setup(mut commands: Commands) {
 
 fn main() {
     //create a grid
-    let mut grid = grid::Grid::new_room_based_grid(8, (5,5),(20, 10));
-    //let mut grid = grid::Grid::new_cell_automata_grid(0.5, 5);
-    grid.place_stairs();
+    let mut rng = run_rng::RunRng::random().floor_rng(0);
+    let mut grid = grid::Grid::new_room_based_grid(&mut rng, 8, (5,5),(20, 10));
+    //let mut grid = grid::Grid::new_cell_automata_grid(&mut rng, 0.5, 5);
+    grid.place_stairs(&mut rng);
     grid.pretty_print_grid();
     App::new()
-        .add_plugins(DefaultPlugins)
+        //watch the assets folder so data files (e.g. the bestiary) hot reload
+        .add_plugins(DefaultPlugins.set(AssetPlugin {
+            watch_for_changes: ChangeWatcher::with_delay(Duration::from_millis(200)),
+            ..default()
+        }))
         .insert_resource(display_quality::DisplayQuality::High)
         .insert_resource(volume::Volume(5))
         .add_systems(Startup, setup)
+        .add_plugins(bestiary::BestiaryPlugin)
         .add_plugins(main_menu_plugin::menu::MenuPlugin)
         .add_plugin(game_plugin::game::GamePlugin)
+        .add_plugins(spawning::SpawningPlugin)
+        .add_plugins(combat::CombatPlugin)
+        .add_plugins(ai::AiPlugin)
+        .add_plugins(faction::FactionPlugin)
+        .add_plugins(experience::ExperiencePlugin)
+        .add_plugins(abilities::AbilitiesPlugin)
+        .add_plugins(character_creation::CharacterCreationPlugin)
+        .add_plugins(spells::SpellsPlugin)
+        .add_plugins(projectiles::ProjectilesPlugin)
+        .add_plugins(melee::MeleePlugin)
+        .add_plugins(items::ItemsPlugin)
+        .add_plugins(equipment::EquipmentPlugin)
+        .add_plugins(combat_stats::CombatStatsPlugin)
+        .add_plugins(consumables::ConsumablesPlugin)
+        .add_plugins(exploration::ExplorationPlugin)
+        .add_plugins(status_effects::StatusEffectsPlugin)
+        .add_plugins(traps::TrapsPlugin)
+        .add_plugins(loot::LootPlugin)
+        .add_plugins(gold::GoldPlugin)
+        .add_plugins(shops::ShopsPlugin)
+        .add_plugins(hud::HudPlugin)
+        .add_plugins(message_log::MessageLogPlugin)
+        .add_plugins(minimap::MinimapPlugin)
+        .add_plugins(game_over::GameOverPlugin)
+        .add_plugins(save_data::SaveDataPlugin)
         .add_state::<game_state::GameState>()
         .run()
     ;
@@ -5,10 +5,23 @@ use crate::game_state::GameState;
 
 
 pub mod menu {
+    use bevy::ecs::system::SystemParam;
     use bevy::{app::AppExit, prelude::*};
 
+    use crate::bestiary::Bestiary;
+    use crate::character_creation::{
+        playable_ancestries, AncestryChoice, CharacterChoice, ClassChoice, NewCharacter, MAX_NAME_LENGTH,
+        MAX_SEED_LENGTH,
+    };
     use crate::game_state::GameState;
     use crate::display_quality::DisplayQuality;
+    use crate::player::PlayerClass;
+    use crate::hud::format_play_time;
+    use crate::minimap::floor_image;
+    use crate::save_data::{
+        delete_slot, format_timestamp, read_save, slot_path, CurrentSave, LoadedSave, SaveNotices, SaveSlot,
+        SaveSlots,
+    };
     use crate::volume::Volume;
     use crate::util::despawn_screen;
 
@@ -203,10 +216,216 @@ pub mod menu {
             });
     }
 
-    // This plugin manages the menu, with 5 different screens:
-    // - a main menu with "New Game", "Settings", "Quit"
+    fn character_creation_menu_setup(mut commands: Commands, bestiary: Res<Bestiary>) {
+        let new_character = NewCharacter::default();
+        let button_style = Style {
+            width: Val::Px(140.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(5.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let row_style = Style {
+            align_items: AlignItems::Center,
+            margin: UiRect::all(Val::Px(5.0)),
+            ..default()
+        };
+        let button_text_style = TextStyle {
+            font_size: 25.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+        let label_text_style = TextStyle {
+            font_size: 30.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    ..default()
+                },
+                OnCharacterCreationScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::CRIMSON.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                "Create your character",
+                                TextStyle {
+                                    font_size: 50.0,
+                                    color: TEXT_COLOR,
+                                    ..default()
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(20.0)),
+                                ..default()
+                            }),
+                        );
+                        // The name and the seed are typed into the field that was clicked last
+                        for (field, label) in [(TextField::Name, "Name"), (TextField::Seed, "Seed")] {
+                            parent
+                                .spawn(NodeBundle {
+                                    style: row_style.clone(),
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(label, label_text_style.clone()));
+                                    let mut entity = parent.spawn((
+                                        ButtonBundle {
+                                            style: Style {
+                                                width: Val::Px(320.0),
+                                                ..button_style.clone()
+                                            },
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        field,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn((
+                                            TextBundle::from_section(
+                                                field.display(&new_character),
+                                                button_text_style.clone(),
+                                            ),
+                                            FieldText(field),
+                                        ));
+                                    });
+                                    if field == TextField::Name {
+                                        entity.insert(SelectedOption);
+                                    }
+                                });
+                        }
+                        // One button per class
+                        parent
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("Class", label_text_style.clone()));
+                                for class in PlayerClass::ALL {
+                                    let choice = ClassChoice(class);
+                                    let mut entity = parent.spawn((
+                                        ButtonBundle {
+                                            style: button_style.clone(),
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        choice,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section(
+                                            class.definition().name,
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if choice.is_selected(&new_character) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+                        // One button per playable ancestry
+                        parent
+                            .spawn(NodeBundle {
+                                style: row_style.clone(),
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("Ancestry", label_text_style.clone()));
+                                for ancestry in playable_ancestries(&bestiary) {
+                                    let choice = AncestryChoice(ancestry);
+                                    let mut entity = parent.spawn((
+                                        ButtonBundle {
+                                            style: button_style.clone(),
+                                            background_color: NORMAL_BUTTON.into(),
+                                            ..default()
+                                        },
+                                        choice,
+                                    ));
+                                    entity.with_children(|parent| {
+                                        parent.spawn(TextBundle::from_section(
+                                            ancestry.get_stats(&bestiary).type_name.clone(),
+                                            button_text_style.clone(),
+                                        ));
+                                    });
+                                    if choice.is_selected(&new_character) {
+                                        entity.insert(SelectedOption);
+                                    }
+                                }
+                            });
+                        // Preview of the stats the character will start with
+                        parent.spawn((
+                            TextBundle::from_section(new_character.preview(&bestiary), label_text_style.clone())
+                                .with_style(Style {
+                                    margin: UiRect::all(Val::Px(20.0)),
+                                    ..default()
+                                }),
+                            StatsPreviewText,
+                        ));
+                        parent
+                            .spawn(NodeBundle {
+                                style: row_style,
+                                ..default()
+                            })
+                            .with_children(|parent| {
+                                for (action, text) in [
+                                    (MenuButtonAction::BackToMainMenu, "Back"),
+                                    (MenuButtonAction::Play, "Start"),
+                                ] {
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                style: button_style.clone(),
+                                                background_color: NORMAL_BUTTON.into(),
+                                                ..default()
+                                            },
+                                            action,
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn(TextBundle::from_section(
+                                                text,
+                                                button_text_style.clone(),
+                                            ));
+                                        });
+                                }
+                            });
+                    });
+            });
+        commands.insert_resource(new_character);
+        commands.insert_resource(ActiveTextField(Some(TextField::Name)));
+    }
+
+    // This plugin manages the menu, with 8 different screens:
+    // - a main menu with "Continue" and "Load Game" (when there are saved runs), "New Game", "Settings", "Quit"
+    // - a character creation screen shown before starting a new game
     // - a settings menu with two submenus and a back button
     // - two settings screen with a setting that can be set and a back button
+    // - a pause screen shown over the game with "Resume", "Settings", "Save & Quit to Menu", "Quit"
+    // - a save slots screen, to load or delete a saved run from the main menu or pick where to save
+    //   from the pause screen
+    // - a screen confirming that a save slot gets overwritten or deleted
+    // What went wrong with the saves is shown in a banner above whichever screen is up
     pub struct MenuPlugin;
 
     impl Plugin for MenuPlugin {
@@ -221,6 +440,27 @@ pub mod menu {
                 .add_systems(OnEnter(MenuState::Main), main_menu_setup)
                 .add_systems(OnExit(MenuState::Main), despawn_screen::<OnMainMenuScreen>)
                 //.add_systems(OnExit(GameState::Menu), despawn_screen::<Camera2d>)
+                // Systems to handle the character creation screen
+                .add_systems(
+                    OnEnter(MenuState::CharacterCreation),
+                    character_creation_menu_setup,
+                )
+                .add_systems(
+                    Update,
+                    (
+                        character_choice_button::<ClassChoice>,
+                        character_choice_button::<AncestryChoice>,
+                        text_field_button,
+                        text_input,
+                        update_character_texts,
+                    )
+                        .chain()
+                        .run_if(in_state(MenuState::CharacterCreation)),
+                )
+                .add_systems(
+                    OnExit(MenuState::CharacterCreation),
+                    despawn_screen::<OnCharacterCreationScreen>,
+                )
                 // Systems to handle the settings menu screen
                 .add_systems(OnEnter(MenuState::Settings), settings_menu_setup)
                 .add_systems(
@@ -253,10 +493,25 @@ pub mod menu {
                     OnExit(MenuState::SettingsSound),
                     despawn_screen::<OnSoundSettingsMenuScreen>,
                 )
+                // Systems to handle the pause screen
+                .add_systems(Update, pause_game.run_if(in_state(GameState::Playing)))
+                .add_systems(OnEnter(MenuState::Paused), pause_menu_setup)
+                .add_systems(Update, resume_game.run_if(in_state(MenuState::Paused)))
+                .add_systems(OnExit(MenuState::Paused), despawn_screen::<OnPauseMenuScreen>)
+                // Systems to handle the save slots screen and its confirmation
+                .add_systems(OnEnter(MenuState::SaveSlots), save_slots_menu_setup)
+                .add_systems(OnExit(MenuState::SaveSlots), despawn_screen::<OnSaveSlotsScreen>)
+                .add_systems(OnEnter(MenuState::ConfirmSlot), confirm_slot_menu_setup)
+                .add_systems(OnExit(MenuState::ConfirmSlot), despawn_screen::<OnConfirmSlotScreen>)
                 // Common systems to all screens that handles buttons behavior
                 .add_systems(
                     Update,
-                    (menu_action, button_system).run_if(in_state(GameState::Menu)),
+                    (menu_action, button_system, show_save_notices)
+                        .run_if(in_state(GameState::Menu).or_else(in_state(GameState::Paused))),
+                )
+                .add_systems(
+                    OnExit(GameState::Menu),
+                    (despawn_screen::<OnSaveNoticeBanner>, clear_save_notices),
                 );
         }
     }
@@ -265,9 +520,13 @@ pub mod menu {
     #[derive(Clone, Copy, Default, Eq, PartialEq, Debug, Hash, States)]
     enum MenuState {
         Main,
+        CharacterCreation,
         Settings,
         SettingsDisplay,
         SettingsSound,
+        Paused,
+        SaveSlots,
+        ConfirmSlot,
         #[default]
         Disabled,
     }
@@ -276,10 +535,66 @@ pub mod menu {
     #[derive(Component)]
     struct OnMainMenuScreen;
 
+    // Tag component used to tag entities added on the character creation screen
+    #[derive(Component)]
+    struct OnCharacterCreationScreen;
+
     // Tag component used to tag entities added on the settings menu screen
     #[derive(Component)]
     struct OnSettingsMenuScreen;
 
+    // Tag component used to tag entities added on the pause screen
+    #[derive(Component)]
+    struct OnPauseMenuScreen;
+
+    // Tag component used to tag entities added on the save slots screen
+    #[derive(Component)]
+    struct OnSaveSlotsScreen;
+
+    // Tag component used to tag entities added on the screen confirming a slot action
+    #[derive(Component)]
+    struct OnConfirmSlotScreen;
+
+    // Tag component used to tag the banner showing the save notices
+    #[derive(Component)]
+    struct OnSaveNoticeBanner;
+
+    // What the confirmation screen is asking about
+    #[derive(Resource, Clone, Copy)]
+    enum PendingSlotAction {
+        Overwrite(usize),
+        Delete(usize),
+    }
+
+    // A field of the character creation screen that can be typed into, on the button that focuses it
+    #[derive(Component, Clone, Copy, PartialEq, Eq)]
+    enum TextField {
+        Name,
+        Seed,
+    }
+
+    impl TextField {
+        fn display(&self, new_character: &NewCharacter) -> String {
+            match self {
+                TextField::Name => new_character.name.clone(),
+                TextField::Seed if new_character.seed.is_empty() => "random".to_string(),
+                TextField::Seed => new_character.seed.clone(),
+            }
+        }
+    }
+
+    // The text field that receives typed characters
+    #[derive(Resource, Default)]
+    struct ActiveTextField(Option<TextField>);
+
+    // Tag component used to tag the text showing the content of a text field
+    #[derive(Component)]
+    struct FieldText(TextField);
+
+    // Tag component used to tag the stats preview on the character creation screen
+    #[derive(Component)]
+    struct StatsPreviewText;
+
     // Tag component used to tag entities added on the display settings menu screen
     #[derive(Component)]
     struct OnDisplaySettingsMenuScreen;
@@ -288,8 +603,8 @@ pub mod menu {
     #[derive(Component)]
     struct OnSoundSettingsMenuScreen;
 
-    const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
-    const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
+    pub const NORMAL_BUTTON: Color = Color::rgb(0.15, 0.15, 0.15);
+    pub const HOVERED_BUTTON: Color = Color::rgb(0.25, 0.25, 0.25);
     const HOVERED_PRESSED_BUTTON: Color = Color::rgb(0.25, 0.65, 0.25);
     const PRESSED_BUTTON: Color = Color::rgb(0.35, 0.75, 0.35);
 
@@ -300,12 +615,24 @@ pub mod menu {
     // All actions that can be triggered from a button click
     #[derive(Component)]
     enum MenuButtonAction {
+        Continue,
+        LoadGame,
+        LoadSlot(usize),
+        // saves the paused run to the slot, asking first if another run is there
+        SaveToSlot(usize),
+        DeleteSlot(usize),
+        ConfirmSlot,
+        BackToSaveSlots,
+        DismissNotices,
+        NewGame,
         Play,
         Settings,
         SettingsDisplay,
         SettingsSound,
         BackToMainMenu,
         BackToSettings,
+        Resume,
+        SaveAndQuit,
         Quit,
     }
 
@@ -345,11 +672,216 @@ pub mod menu {
         }
     }
 
+    // The buttons just pressed, hovered or left
+    type ChangedButtons = (Changed<Interaction>, With<Button>);
+    // The selected button among the ones tagged with T
+    type SelectedAmong<T> = (With<SelectedOption>, With<T>);
+
+    // Same as `setting_button`, but the choice is stored in `NewCharacter` and there is one
+    // selected button per kind of choice
+    fn character_choice_button<T: CharacterChoice>(
+        interaction_query: Query<(&Interaction, &T, Entity), ChangedButtons>,
+        mut selected_query: Query<(Entity, &mut BackgroundColor), SelectedAmong<T>>,
+        mut commands: Commands,
+        mut new_character: ResMut<NewCharacter>,
+    ) {
+        for (interaction, choice, entity) in &interaction_query {
+            if *interaction == Interaction::Pressed && !choice.is_selected(&new_character) {
+                for (previous_button, mut previous_color) in &mut selected_query {
+                    *previous_color = NORMAL_BUTTON.into();
+                    commands.entity(previous_button).remove::<SelectedOption>();
+                }
+                commands.entity(entity).insert(SelectedOption);
+                choice.apply(&mut new_character);
+            }
+        }
+    }
+
+    // Clicking a text field makes it the one typed characters go to
+    fn text_field_button(
+        interaction_query: Query<(&Interaction, &TextField, Entity), ChangedButtons>,
+        mut selected_query: Query<(Entity, &mut BackgroundColor), SelectedAmong<TextField>>,
+        mut commands: Commands,
+        mut active_field: ResMut<ActiveTextField>,
+    ) {
+        for (interaction, field, entity) in &interaction_query {
+            if *interaction == Interaction::Pressed && active_field.0 != Some(*field) {
+                for (previous_button, mut previous_color) in &mut selected_query {
+                    *previous_color = NORMAL_BUTTON.into();
+                    commands.entity(previous_button).remove::<SelectedOption>();
+                }
+                commands.entity(entity).insert(SelectedOption);
+                active_field.0 = Some(*field);
+            }
+        }
+    }
+
+    fn text_input(
+        mut received_characters: EventReader<ReceivedCharacter>,
+        keys: Res<Input<KeyCode>>,
+        active_field: Res<ActiveTextField>,
+        mut new_character: ResMut<NewCharacter>,
+    ) {
+        let typed: Vec<char> = received_characters.iter().map(|event| event.char).collect();
+        let erase = keys.just_pressed(KeyCode::Back);
+        let Some(field) = active_field.0 else {
+            return;
+        };
+        // Don't touch the resource when nothing was typed so the texts aren't updated every frame
+        if typed.is_empty() && !erase {
+            return;
+        }
+        let new_character = new_character.as_mut();
+        let (text, max_length) = match field {
+            TextField::Name => (&mut new_character.name, MAX_NAME_LENGTH),
+            TextField::Seed => (&mut new_character.seed, MAX_SEED_LENGTH),
+        };
+        if erase {
+            text.pop();
+        }
+        for character in typed {
+            // Backspace is also received as a character
+            if character.is_control() || (field == TextField::Seed && !character.is_ascii_digit()) {
+                continue;
+            }
+            if text.chars().count() < max_length {
+                text.push(character);
+            }
+        }
+    }
+
+    fn update_character_texts(
+        new_character: Res<NewCharacter>,
+        bestiary: Res<Bestiary>,
+        mut field_texts: Query<(&FieldText, &mut Text)>,
+        mut preview_texts: Query<&mut Text, (With<StatsPreviewText>, Without<FieldText>)>,
+    ) {
+        if !new_character.is_changed() && !bestiary.is_changed() {
+            return;
+        }
+        for (field_text, mut text) in &mut field_texts {
+            text.sections[0].value = field_text.0.display(&new_character);
+        }
+        for mut text in &mut preview_texts {
+            text.sections[0].value = new_character.preview(&bestiary);
+        }
+    }
+
     fn menu_setup(mut menu_state: ResMut<NextState<MenuState>>) {
         menu_state.set(MenuState::Main);
     }
 
-    fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>) {
+    const PAUSE_KEY: KeyCode = KeyCode::Escape;
+
+    // Escape freezes the game, every gameplay system only runs in GameState::Playing
+    fn pause_game(
+        keys: Res<Input<KeyCode>>,
+        mut menu_state: ResMut<NextState<MenuState>>,
+        mut game_state: ResMut<NextState<GameState>>,
+    ) {
+        if keys.just_pressed(PAUSE_KEY) {
+            game_state.set(GameState::Paused);
+            menu_state.set(MenuState::Paused);
+        }
+    }
+
+    // Escape on the pause screen goes back to the game, like the resume button
+    fn resume_game(
+        keys: Res<Input<KeyCode>>,
+        mut menu_state: ResMut<NextState<MenuState>>,
+        mut game_state: ResMut<NextState<GameState>>,
+    ) {
+        if keys.just_pressed(PAUSE_KEY) {
+            game_state.set(GameState::Playing);
+            menu_state.set(MenuState::Disabled);
+        }
+    }
+
+    fn pause_menu_setup(mut commands: Commands) {
+        let button_style = Style {
+            width: Val::Px(360.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = TextStyle {
+            font_size: 40.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    // darkens the game behind the menu
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                },
+                OnPauseMenuScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            ..default()
+                        },
+                        background_color: Color::CRIMSON.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                "Paused",
+                                TextStyle {
+                                    font_size: 60.0,
+                                    color: TEXT_COLOR,
+                                    ..default()
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::all(Val::Px(30.0)),
+                                ..default()
+                            }),
+                        );
+                        for (action, text) in [
+                            (MenuButtonAction::Resume, "Resume"),
+                            (MenuButtonAction::Settings, "Settings"),
+                            (MenuButtonAction::SaveAndQuit, "Save & Quit to Menu"),
+                            (MenuButtonAction::Quit, "Quit"),
+                        ] {
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: button_style.clone(),
+                                        background_color: NORMAL_BUTTON.into(),
+                                        ..default()
+                                    },
+                                    action,
+                                ))
+                                .with_children(|parent| {
+                                    parent.spawn(TextBundle::from_section(
+                                        text,
+                                        button_text_style.clone(),
+                                    ));
+                                });
+                        }
+                    });
+            });
+    }
+
+    fn main_menu_setup(mut commands: Commands, asset_server: Res<AssetServer>, mut save_slots: ResMut<SaveSlots>) {
+        *save_slots = SaveSlots::scan();
         // Common style for all buttons on the screen
         let button_style = Style {
             width: Val::Px(250.0),
@@ -415,10 +947,58 @@ pub mod menu {
                             }),
                         );
 
-                        // Display three buttons for each action available from the main menu:
+                        // Display a button for each action available from the main menu:
+                        // - continue, when a run was saved
+                        // - load game, when there is something in the save slots
                         // - new game
                         // - settings
                         // - quit
+                        if save_slots.latest().is_some() {
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: button_style.clone(),
+                                        background_color: NORMAL_BUTTON.into(),
+                                        ..default()
+                                    },
+                                    MenuButtonAction::Continue,
+                                ))
+                                .with_children(|parent| {
+                                    let icon = asset_server.load("textures/Game Icons/right.png");
+                                    parent.spawn(ImageBundle {
+                                        style: button_icon_style.clone(),
+                                        image: UiImage::new(icon),
+                                        ..default()
+                                    });
+                                    parent.spawn(TextBundle::from_section(
+                                        "Continue",
+                                        button_text_style.clone(),
+                                    ));
+                                });
+                        }
+                        if !save_slots.all_empty() {
+                            parent
+                                .spawn((
+                                    ButtonBundle {
+                                        style: button_style.clone(),
+                                        background_color: NORMAL_BUTTON.into(),
+                                        ..default()
+                                    },
+                                    MenuButtonAction::LoadGame,
+                                ))
+                                .with_children(|parent| {
+                                    let icon = asset_server.load("textures/Game Icons/right.png");
+                                    parent.spawn(ImageBundle {
+                                        style: button_icon_style.clone(),
+                                        image: UiImage::new(icon),
+                                        ..default()
+                                    });
+                                    parent.spawn(TextBundle::from_section(
+                                        "Load Game",
+                                        button_text_style.clone(),
+                                    ));
+                                });
+                        }
                         parent
                             .spawn((
                                 ButtonBundle {
@@ -426,7 +1006,7 @@ pub mod menu {
                                     background_color: NORMAL_BUTTON.into(),
                                     ..default()
                                 },
-                                MenuButtonAction::Play,
+                                MenuButtonAction::NewGame,
                             ))
                             .with_children(|parent| {
                                 let icon = asset_server.load("textures/Game Icons/right.png");
@@ -549,6 +1129,389 @@ pub mod menu {
             });
     }
 
+    // Lists the save slots, each with a thumbnail of the floor its run is on. Opened from the main
+    // menu the slots are loaded or deleted, opened from the pause screen the run is saved to one
+    fn save_slots_menu_setup(
+        mut commands: Commands,
+        mut images: ResMut<Assets<Image>>,
+        game_state: Res<State<GameState>>,
+        mut save_slots: ResMut<SaveSlots>,
+    ) {
+        *save_slots = SaveSlots::scan();
+        let saving = *game_state.get() == GameState::Paused;
+        let button_style = Style {
+            width: Val::Px(120.0),
+            height: Val::Px(50.0),
+            margin: UiRect::all(Val::Px(10.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = TextStyle {
+            font_size: 30.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+        let info_text_style = TextStyle {
+            font_size: 22.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+        let thumbnail_style = Style {
+            width: Val::Px(96.0),
+            height: Val::Px(96.0),
+            margin: UiRect::all(Val::Px(10.0)),
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                },
+                OnSaveSlotsScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        background_color: Color::CRIMSON.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(
+                            TextBundle::from_section(
+                                if saving { "Save to which slot?" } else { "Load Game" },
+                                TextStyle {
+                                    font_size: 50.0,
+                                    color: TEXT_COLOR,
+                                    ..default()
+                                },
+                            )
+                            .with_style(Style {
+                                margin: UiRect::bottom(Val::Px(20.0)),
+                                ..default()
+                            }),
+                        );
+                        for (slot, save_slot) in save_slots.0.iter().enumerate() {
+                            let (lines, thumbnail) = match save_slot {
+                                SaveSlot::Empty => (vec![format!("Slot {}: empty", slot + 1)], None),
+                                SaveSlot::Unreadable(error) => (
+                                    vec![format!("Slot {}: can't be loaded", slot + 1), error.clone()],
+                                    None,
+                                ),
+                                SaveSlot::Saved(summary) => (
+                                    vec![
+                                        format!(
+                                            "Slot {}: {}, level {} {}",
+                                            slot + 1,
+                                            summary.name,
+                                            summary.level,
+                                            summary.class.definition().name
+                                        ),
+                                        format!(
+                                            "Depth {}, played {}",
+                                            summary.depth + 1,
+                                            format_play_time(summary.play_time.as_secs())
+                                        ),
+                                        // saves from before the header didn't keep the time
+                                        if summary.saved_at == 0 {
+                                            "Saved at an unknown time".to_string()
+                                        } else {
+                                            format!("Saved {}", format_timestamp(summary.saved_at))
+                                        },
+                                    ],
+                                    summary
+                                        .floor
+                                        .as_ref()
+                                        .map(|floor| images.add(floor_image(floor, summary.player_tile))),
+                                ),
+                            };
+                            let mut actions = Vec::new();
+                            if saving {
+                                actions.push((MenuButtonAction::SaveToSlot(slot), "Save"));
+                            } else {
+                                if let SaveSlot::Saved(_) = save_slot {
+                                    actions.push((MenuButtonAction::LoadSlot(slot), "Load"));
+                                }
+                                if !matches!(save_slot, SaveSlot::Empty) {
+                                    actions.push((MenuButtonAction::DeleteSlot(slot), "Delete"));
+                                }
+                            }
+                            parent
+                                .spawn(NodeBundle {
+                                    style: Style {
+                                        width: Val::Px(760.0),
+                                        align_items: AlignItems::Center,
+                                        margin: UiRect::all(Val::Px(5.0)),
+                                        ..default()
+                                    },
+                                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.3).into(),
+                                    ..default()
+                                })
+                                .with_children(|parent| {
+                                    match thumbnail {
+                                        Some(image) => {
+                                            parent.spawn(ImageBundle {
+                                                style: thumbnail_style.clone(),
+                                                image: UiImage::new(image),
+                                                ..default()
+                                            });
+                                        }
+                                        None => {
+                                            parent.spawn(NodeBundle {
+                                                style: thumbnail_style.clone(),
+                                                background_color: Color::BLACK.into(),
+                                                ..default()
+                                            });
+                                        }
+                                    }
+                                    parent
+                                        .spawn(NodeBundle {
+                                            style: Style {
+                                                flex_direction: FlexDirection::Column,
+                                                flex_grow: 1.0,
+                                                ..default()
+                                            },
+                                            ..default()
+                                        })
+                                        .with_children(|parent| {
+                                            for line in lines {
+                                                parent.spawn(TextBundle::from_section(line, info_text_style.clone()));
+                                            }
+                                        });
+                                    for (action, text) in actions {
+                                        parent
+                                            .spawn((
+                                                ButtonBundle {
+                                                    style: button_style.clone(),
+                                                    background_color: NORMAL_BUTTON.into(),
+                                                    ..default()
+                                                },
+                                                action,
+                                            ))
+                                            .with_children(|parent| {
+                                                parent.spawn(TextBundle::from_section(
+                                                    text,
+                                                    button_text_style.clone(),
+                                                ));
+                                            });
+                                    }
+                                });
+                        }
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: button_style.clone(),
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                MenuButtonAction::BackToMainMenu,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("Back", button_text_style.clone()));
+                            });
+                    });
+            });
+    }
+
+    fn confirm_slot_menu_setup(mut commands: Commands, pending: Res<PendingSlotAction>) {
+        let question = match *pending {
+            PendingSlotAction::Overwrite(slot) => format!("Overwrite the run saved in slot {}?", slot + 1),
+            PendingSlotAction::Delete(slot) => format!("Delete the run saved in slot {}?", slot + 1),
+        };
+        let button_style = Style {
+            width: Val::Px(200.0),
+            height: Val::Px(65.0),
+            margin: UiRect::all(Val::Px(20.0)),
+            justify_content: JustifyContent::Center,
+            align_items: AlignItems::Center,
+            ..default()
+        };
+        let button_text_style = TextStyle {
+            font_size: 40.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        width: Val::Percent(100.0),
+                        height: Val::Percent(100.0),
+                        align_items: AlignItems::Center,
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    background_color: Color::rgba(0.0, 0.0, 0.0, 0.5).into(),
+                    ..default()
+                },
+                OnConfirmSlotScreen,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            padding: UiRect::all(Val::Px(20.0)),
+                            ..default()
+                        },
+                        background_color: Color::CRIMSON.into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        parent.spawn(TextBundle::from_section(question, button_text_style.clone()));
+                        parent
+                            .spawn(NodeBundle::default())
+                            .with_children(|parent| {
+                                for (action, text) in [
+                                    (MenuButtonAction::ConfirmSlot, "Yes"),
+                                    (MenuButtonAction::BackToSaveSlots, "No"),
+                                ] {
+                                    parent
+                                        .spawn((
+                                            ButtonBundle {
+                                                style: button_style.clone(),
+                                                background_color: NORMAL_BUTTON.into(),
+                                                ..default()
+                                            },
+                                            action,
+                                        ))
+                                        .with_children(|parent| {
+                                            parent.spawn(TextBundle::from_section(
+                                                text,
+                                                button_text_style.clone(),
+                                            ));
+                                        });
+                                }
+                            });
+                    });
+            });
+    }
+
+    // Rebuilds the banner when a notice comes in or they are dismissed
+    fn show_save_notices(
+        mut commands: Commands,
+        notices: Res<SaveNotices>,
+        banners: Query<Entity, With<OnSaveNoticeBanner>>,
+    ) {
+        if !notices.is_changed() {
+            return;
+        }
+        for banner in &banners {
+            commands.entity(banner).despawn_recursive();
+        }
+        if notices.0.is_empty() {
+            return;
+        }
+        let text_style = TextStyle {
+            font_size: 24.0,
+            color: TEXT_COLOR,
+            ..default()
+        };
+
+        commands
+            .spawn((
+                NodeBundle {
+                    style: Style {
+                        position_type: PositionType::Absolute,
+                        top: Val::Px(20.0),
+                        width: Val::Percent(100.0),
+                        justify_content: JustifyContent::Center,
+                        ..default()
+                    },
+                    // above the menu screens
+                    z_index: ZIndex::Global(10),
+                    ..default()
+                },
+                OnSaveNoticeBanner,
+            ))
+            .with_children(|parent| {
+                parent
+                    .spawn(NodeBundle {
+                        style: Style {
+                            flex_direction: FlexDirection::Column,
+                            align_items: AlignItems::Center,
+                            max_width: Val::Px(800.0),
+                            padding: UiRect::all(Val::Px(10.0)),
+                            ..default()
+                        },
+                        background_color: Color::rgb(0.4, 0.05, 0.05).into(),
+                        ..default()
+                    })
+                    .with_children(|parent| {
+                        for notice in notices.0.iter() {
+                            parent.spawn(TextBundle::from_section(notice.clone(), text_style.clone()));
+                        }
+                        parent
+                            .spawn((
+                                ButtonBundle {
+                                    style: Style {
+                                        width: Val::Px(100.0),
+                                        height: Val::Px(40.0),
+                                        margin: UiRect::top(Val::Px(10.0)),
+                                        justify_content: JustifyContent::Center,
+                                        align_items: AlignItems::Center,
+                                        ..default()
+                                    },
+                                    background_color: NORMAL_BUTTON.into(),
+                                    ..default()
+                                },
+                                MenuButtonAction::DismissNotices,
+                            ))
+                            .with_children(|parent| {
+                                parent.spawn(TextBundle::from_section("OK", text_style.clone()));
+                            });
+                    });
+            });
+    }
+
+    // Starting or continuing a run dismisses the notices, they were seen
+    fn clear_save_notices(mut notices: ResMut<SaveNotices>) {
+        notices.0.clear();
+    }
+
+    // The run is rebuilt from the save when entering GameState::Playing
+    fn load_slot(
+        slot: usize,
+        commands: &mut Commands,
+        game_state: &mut NextState<GameState>,
+        menu_state: &mut NextState<MenuState>,
+        notices: &mut SaveNotices,
+    ) {
+        match read_save(&slot_path(slot)) {
+            Ok(save_data) => {
+                commands.insert_resource(LoadedSave { slot, save_data });
+                game_state.set(GameState::Playing);
+                menu_state.set(MenuState::Disabled);
+            }
+            Err(error) => notices.0.push(format!("Slot {} can't be loaded: {}", slot + 1, error)),
+        }
+    }
+
+    // What the buttons of the save slots screens need to know about the saves
+    #[derive(SystemParam)]
+    struct SaveMenu<'w> {
+        current_save: Option<Res<'w, CurrentSave>>,
+        pending_slot_action: Option<Res<'w, PendingSlotAction>>,
+        notices: ResMut<'w, SaveNotices>,
+        save_slots: Res<'w, SaveSlots>,
+    }
 
     fn menu_action(
         interaction_query: Query<
@@ -558,11 +1521,64 @@ pub mod menu {
         mut app_exit_events: EventWriter<AppExit>,
         mut menu_state: ResMut<NextState<MenuState>>,
         mut game_state: ResMut<NextState<GameState>>,
+        current_game_state: Res<State<GameState>>,
+        save_menu: SaveMenu,
+        mut commands: Commands,
     ) {
+        let SaveMenu {
+            current_save,
+            pending_slot_action,
+            mut notices,
+            save_slots,
+        } = save_menu;
         for (interaction, menu_button_action) in &interaction_query {
             if *interaction == Interaction::Pressed {
                 match menu_button_action {
                     MenuButtonAction::Quit => app_exit_events.send(AppExit),
+                    MenuButtonAction::Continue => {
+                        if let Some(slot) = save_slots.latest() {
+                            load_slot(slot, &mut commands, &mut game_state, &mut menu_state, &mut notices);
+                        }
+                    }
+                    MenuButtonAction::DismissNotices => notices.0.clear(),
+                    MenuButtonAction::LoadGame | MenuButtonAction::BackToSaveSlots => {
+                        menu_state.set(MenuState::SaveSlots);
+                    }
+                    MenuButtonAction::LoadSlot(slot) => {
+                        load_slot(*slot, &mut commands, &mut game_state, &mut menu_state, &mut notices);
+                    }
+                    // save_run writes the run to CurrentSave when going to the menu
+                    MenuButtonAction::SaveToSlot(slot) => match save_slots.0.get(*slot) {
+                        Some(SaveSlot::Empty) | None => {
+                            commands.insert_resource(CurrentSave(*slot));
+                            game_state.set(GameState::Menu);
+                        }
+                        _ => {
+                            commands.insert_resource(PendingSlotAction::Overwrite(*slot));
+                            menu_state.set(MenuState::ConfirmSlot);
+                        }
+                    },
+                    MenuButtonAction::DeleteSlot(slot) => {
+                        commands.insert_resource(PendingSlotAction::Delete(*slot));
+                        menu_state.set(MenuState::ConfirmSlot);
+                    }
+                    MenuButtonAction::ConfirmSlot => {
+                        commands.remove_resource::<PendingSlotAction>();
+                        match pending_slot_action.as_deref() {
+                            Some(PendingSlotAction::Overwrite(slot)) => {
+                                commands.insert_resource(CurrentSave(*slot));
+                                game_state.set(GameState::Menu);
+                            }
+                            Some(PendingSlotAction::Delete(slot)) => {
+                                if let Err(error) = delete_slot(*slot) {
+                                    notices.0.push(format!("Slot {} could not be deleted: {}", slot + 1, error));
+                                }
+                                menu_state.set(MenuState::SaveSlots);
+                            }
+                            None => menu_state.set(MenuState::SaveSlots),
+                        }
+                    }
+                    MenuButtonAction::NewGame => menu_state.set(MenuState::CharacterCreation),
                     MenuButtonAction::Play => {
                         game_state.set(GameState::Playing);
                         menu_state.set(MenuState::Disabled);
@@ -574,10 +1590,21 @@ pub mod menu {
                     MenuButtonAction::SettingsSound => {
                         menu_state.set(MenuState::SettingsSound);
                     }
+                    // the settings opened from the pause screen go back to it
+                    MenuButtonAction::BackToMainMenu if *current_game_state.get() == GameState::Paused => {
+                        menu_state.set(MenuState::Paused);
+                    }
                     MenuButtonAction::BackToMainMenu => menu_state.set(MenuState::Main),
                     MenuButtonAction::BackToSettings => {
                         menu_state.set(MenuState::Settings);
                     }
+                    MenuButtonAction::Resume => {
+                        game_state.set(GameState::Playing);
+                        menu_state.set(MenuState::Disabled);
+                    }
+                    // menu_setup shows the main menu, a run that wasn't saved yet picks a slot first
+                    MenuButtonAction::SaveAndQuit if current_save.is_some() => game_state.set(GameState::Menu),
+                    MenuButtonAction::SaveAndQuit => menu_state.set(MenuState::SaveSlots),
                 }
             }
         }
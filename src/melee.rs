@@ -0,0 +1,181 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::dash;
+use crate::combat::AttackEvent;
+use crate::combat_stats::DamageType;
+use crate::creature::Health;
+use crate::faction::{Faction, FactionTable, Stance};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::TILE_SIZE;
+use crate::player::{Aim, Player};
+use crate::position::Position;
+use crate::status_effects::{is_stunned, StatusEffects};
+
+//how much time slows down during a hit-stop, and for how many real seconds
+pub const HIT_STOP_SPEED: f32 = 0.05;
+pub const HIT_STOP_TIME: f32 = 0.08;
+
+//How a melee weapon swings, distances are in tiles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct WeaponStats {
+    pub reach: f32,
+    //full width of the arc in front of the wielder, in degrees
+    pub arc: f32,
+    //seconds between two swings
+    pub swing_time: f32,
+    //how far the creatures hit are pushed back
+    #[serde(default)]
+    pub knockback: f32,
+}
+
+//fists
+impl Default for WeaponStats {
+    fn default() -> Self {
+        WeaponStats {
+            reach: 1.2,
+            arc: 60.0,
+            swing_time: 0.5,
+            knockback: 1.0,
+        }
+    }
+}
+
+//The melee weapon the player swings with the left mouse button
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Weapon {
+    pub stats: WeaponStats,
+    //seconds left before the next swing
+    pub cooldown: f32,
+}
+
+//Time runs slowly while this is above 0, counted in real seconds
+#[derive(Resource, Default)]
+pub struct HitStop {
+    pub remaining: f32,
+}
+
+//every creature in the arc in front of the attacker, the direction is normalized
+pub fn creatures_in_arc(
+    from : &Position,
+    direction : Vec2,
+    weapon : &WeaponStats,
+    creatures : impl Iterator<Item = (Entity, Position)>,
+) -> Vec<Entity> {
+    let half_arc = (weapon.arc / 2.0).to_radians();
+    creatures
+        .filter(|(_, pos)| {
+            let to_creature = pos.to_vec2() - from.to_vec2();
+            pos.2 == from.2
+                && to_creature.length() <= weapon.reach * TILE_SIZE
+                //a creature standing on the attacker is always in front of it
+                && (to_creature.length() == 0.0 || direction.angle_between(to_creature).abs() <= half_arc)
+        })
+        .map(|(entity, _)| entity)
+        .collect()
+}
+
+type Swinger = (Entity, &'static Position, &'static Aim, &'static mut Weapon, Option<&'static StatusEffects>);
+type OtherCreatures = (With<Health>, Without<Player>);
+
+//the left mouse button, and the interface buttons that take the click instead
+#[derive(SystemParam)]
+pub struct SwingInput<'w, 's> {
+    buttons: Res<'w, Input<MouseButton>>,
+    ui_buttons: Query<'w, 's, &'static Interaction, With<Button>>,
+}
+
+//the creatures a swing can hit, and whether the player is allowed to
+#[derive(SystemParam)]
+pub struct SwingTargets<'w, 's> {
+    creatures: Query<'w, 's, (Entity, &'static mut Position, &'static Faction), OtherCreatures>,
+    faction_table: Res<'w, FactionTable>,
+}
+
+//swing at everything in the arc, friendly creatures are left alone
+pub fn swing_weapon(
+    time: Res<Time>,
+    input: SwingInput,
+    mut players: Query<Swinger, With<Player>>,
+    targets: SwingTargets,
+    game_world: Res<GameWorld>,
+    mut hit_stop: ResMut<HitStop>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    let SwingInput { buttons, ui_buttons } = input;
+    let SwingTargets { mut creatures, faction_table } = targets;
+    let Ok((player, pos, aim, mut weapon, effects)) = players.get_single_mut() else {
+        return;
+    };
+    weapon.cooldown = (weapon.cooldown - time.delta_seconds()).max(0.0);
+    if !buttons.pressed(MouseButton::Left) || weapon.cooldown > 0.0 || is_stunned(effects) {
+        return;
+    }
+    //clicking a button of the interface isn't a swing
+    if ui_buttons.iter().any(|interaction| *interaction != Interaction::None) {
+        return;
+    }
+    weapon.cooldown = weapon.stats.swing_time;
+    let hits = creatures_in_arc(
+        pos,
+        aim.direction,
+        &weapon.stats,
+        creatures
+            .iter()
+            .filter(|(_, _, faction)| faction_table.stance(Faction::Player, **faction) != Stance::Friendly)
+            .map(|(entity, pos, _)| (entity, pos.clone())),
+    );
+    if hits.is_empty() {
+        return;
+    }
+    hit_stop.remaining = HIT_STOP_TIME;
+    let grid = game_world.grid(pos.2);
+    for target in hits {
+        attack_events.send(AttackEvent {
+            attacker: player,
+            target,
+            damage: None,
+            damage_type: DamageType::Physical,
+        });
+        if weapon.stats.knockback <= 0.0 {
+            continue;
+        }
+        if let Ok((_, mut target_pos, _)) = creatures.get_mut(target) {
+            let away = (target_pos.to_vec2() - pos.to_vec2()).try_normalize().unwrap_or(aim.direction);
+            *target_pos = dash(grid, &target_pos, away, weapon.stats.knockback * TILE_SIZE);
+        }
+    }
+}
+
+pub fn update_hit_stop(mut time: ResMut<Time>, mut hit_stop: ResMut<HitStop>) {
+    if hit_stop.remaining > 0.0 {
+        hit_stop.remaining -= time.raw_delta_seconds();
+        let speed = if hit_stop.remaining > 0.0 { HIT_STOP_SPEED } else { 1.0 };
+        if time.relative_speed() != speed {
+            time.set_relative_speed(speed);
+        }
+    }
+}
+
+//don't leave time slowed down when the game stops
+pub fn reset_hit_stop(mut time: ResMut<Time>, mut hit_stop: ResMut<HitStop>) {
+    hit_stop.remaining = 0.0;
+    time.set_relative_speed(1.0);
+}
+
+pub struct MeleePlugin;
+
+impl Plugin for MeleePlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<HitStop>()
+            .add_systems(
+                Update,
+                (swing_weapon, update_hit_stop)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnExit(GameState::Playing), reset_hit_stop);
+    }
+}
@@ -0,0 +1,393 @@
+use std::collections::VecDeque;
+
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::bestiary::Bestiary;
+use crate::combat::{handle_deaths, resolve_attacks, DamageEvent, DeathEvent};
+use crate::creature::CreatureType;
+use crate::experience::LevelUpEvent;
+use crate::game_plugin::game::RunStarted;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::main_menu_plugin::menu::TEXT_COLOR;
+use crate::player::Player;
+use crate::position::Position;
+use crate::save_data::SaveData;
+use crate::status_effects::{ApplyStatusEvent, StatusKind};
+use crate::traps::TrapTriggeredEvent;
+use crate::util::despawn_screen;
+
+//older messages are forgotten
+pub const MAX_MESSAGES: usize = 100;
+//number of lines the panel shows
+pub const VISIBLE_MESSAGES: usize = 8;
+pub const SCROLL_UP_KEY: KeyCode = KeyCode::PageUp;
+pub const SCROLL_DOWN_KEY: KeyCode = KeyCode::PageDown;
+
+//What a message is about, decides its color
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum MessageKind {
+    Info,
+    //the player hurting something
+    Combat,
+    //something hurting the player
+    Hurt,
+    Loot,
+    Level,
+    Warning,
+    Travel,
+}
+
+impl MessageKind {
+    pub fn color(&self) -> Color {
+        match self {
+            MessageKind::Info => TEXT_COLOR,
+            MessageKind::Combat => Color::rgb(1.0, 0.75, 0.45),
+            MessageKind::Hurt => Color::rgb(1.0, 0.35, 0.35),
+            MessageKind::Loot => Color::GOLD,
+            MessageKind::Level => Color::rgb(0.75, 0.5, 1.0),
+            MessageKind::Warning => Color::YELLOW,
+            MessageKind::Travel => Color::rgb(0.5, 0.8, 1.0),
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct Message {
+    pub text: String,
+    pub kind: MessageKind,
+    //how many times in a row it was logged
+    pub count: u32,
+}
+
+impl Message {
+    //e.g. "You hit the Goblin for 4 x3"
+    pub fn display(&self) -> String {
+        if self.count > 1 {
+            format!("{} x{}", self.text, self.count)
+        } else {
+            self.text.clone()
+        }
+    }
+}
+
+//What happened during the run, newest last
+#[derive(Resource, Default)]
+pub struct MessageLog {
+    pub messages: VecDeque<Message>,
+    //how many messages up the panel is scrolled
+    pub scroll: usize,
+}
+
+impl MessageLog {
+    //the same message logged again just counts up
+    pub fn add(&mut self, kind : MessageKind, text : impl Into<String>) {
+        let text = text.into();
+        self.scroll = 0;
+        if let Some(last) = self.messages.back_mut() {
+            if last.kind == kind && last.text == text {
+                last.count += 1;
+                return;
+            }
+        }
+        self.messages.push_back(Message { text, kind, count: 1 });
+        while self.messages.len() > MAX_MESSAGES {
+            self.messages.pop_front();
+        }
+    }
+    pub fn save(&self, save_data : &mut SaveData) {
+        save_data.messages = self.messages.iter().cloned().collect();
+    }
+    pub fn load(save_data : &SaveData) -> Self {
+        MessageLog {
+            messages: save_data.messages.iter().cloned().collect(),
+            ..Default::default()
+        }
+    }
+}
+
+//e.g. "the Goblin", or "you" for the player
+fn creature_name(bestiary : &Bestiary, creature_type : &CreatureType, player : bool) -> String {
+    if player {
+        "you".to_string()
+    } else {
+        format!("the {}", creature_type.get_stats(bestiary).type_name)
+    }
+}
+
+fn capitalize(text : &str) -> String {
+    let mut chars = text.chars();
+    match chars.next() {
+        Some(first) => first.to_uppercase().chain(chars).collect(),
+        None => String::new(),
+    }
+}
+
+//hits the player deals or takes, fights between other creatures aren't logged
+pub fn log_damage(
+    mut damage_events: EventReader<DamageEvent>,
+    creatures: Query<(&CreatureType, Option<&Player>)>,
+    bestiary: Res<Bestiary>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in damage_events.iter() {
+        let Ok((target_type, target_player)) = creatures.get(event.target) else {
+            continue;
+        };
+        if event.attacker == event.target {
+            if target_player.is_some() {
+                log.add(MessageKind::Hurt, format!("You take {} {} damage", event.damage, event.damage_type.name()));
+            }
+            continue;
+        }
+        let Ok((attacker_type, attacker_player)) = creatures.get(event.attacker) else {
+            continue;
+        };
+        if attacker_player.is_some() {
+            let target = creature_name(&bestiary, target_type, false);
+            log.add(MessageKind::Combat, format!("You hit {} for {}", target, event.damage));
+        } else if target_player.is_some() {
+            let attacker = creature_name(&bestiary, attacker_type, false);
+            log.add(MessageKind::Hurt, format!("{} hits you for {}", capitalize(&attacker), event.damage));
+        }
+    }
+}
+
+//read before the dead are despawned
+pub fn log_deaths(
+    mut death_events: EventReader<DeathEvent>,
+    creatures: Query<(&CreatureType, Option<&Player>)>,
+    bestiary: Res<Bestiary>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in death_events.iter() {
+        let Ok((creature_type, player)) = creatures.get(event.entity) else {
+            continue;
+        };
+        if player.is_some() {
+            log.add(MessageKind::Warning, "You die...");
+            continue;
+        }
+        let killed_by_player = event.killer.is_some_and(|killer| creatures.get(killer).is_ok_and(|(_, player)| player.is_some()));
+        if killed_by_player {
+            log.add(MessageKind::Combat, format!("You kill {}", creature_name(&bestiary, creature_type, false)));
+        }
+    }
+}
+
+pub fn log_level_ups(
+    mut level_up_events: EventReader<LevelUpEvent>,
+    players: Query<(), With<Player>>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in level_up_events.iter() {
+        if players.contains(event.entity) {
+            log.add(MessageKind::Level, format!("You are now level {}", event.new_level));
+        }
+    }
+}
+
+pub fn log_traps(
+    mut trap_events: EventReader<TrapTriggeredEvent>,
+    players: Query<(), With<Player>>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in trap_events.iter() {
+        if players.contains(event.victim) {
+            log.add(MessageKind::Warning, format!("You trigger a {}", event.trap.name()));
+        }
+    }
+}
+
+pub fn log_status_effects(
+    mut status_events: EventReader<ApplyStatusEvent>,
+    players: Query<(), With<Player>>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in status_events.iter() {
+        if !players.contains(event.target) {
+            continue;
+        }
+        let kind = match event.status.kind {
+            StatusKind::Hasted | StatusKind::Regenerating | StatusKind::Invisible => MessageKind::Info,
+            _ => MessageKind::Warning,
+        };
+        log.add(kind, format!("You are {}", event.status.kind.name()));
+    }
+}
+
+pub fn log_floor_changes(
+    players: Query<&Position, With<Player>>,
+    game_world: Res<GameWorld>,
+    mut log: ResMut<MessageLog>,
+    mut run_started: EventReader<RunStarted>,
+    mut last_floor: Local<Option<usize>>,
+) {
+    //a new run doesn't come from the floor the last one ended on
+    if !run_started.is_empty() {
+        run_started.clear();
+        *last_floor = None;
+    }
+    let Ok(pos) = players.get_single() else {
+        return;
+    };
+    let previous = last_floor.replace(pos.2);
+    let Some(previous) = previous.filter(|previous| *previous != pos.2) else {
+        return;
+    };
+    let direction = if pos.2 > previous { "down" } else { "up" };
+    let floor_type = game_world.floors.get(pos.2).map_or("", |floor| floor.floor_type.name());
+    log.add(
+        MessageKind::Travel,
+        format!("You go {} to depth {} ({})", direction, pos.2 + 1, floor_type),
+    );
+}
+
+pub fn scroll_message_log(keys: Res<Input<KeyCode>>, mut log: ResMut<MessageLog>) {
+    let max_scroll = log.messages.len().saturating_sub(VISIBLE_MESSAGES);
+    if keys.just_pressed(SCROLL_UP_KEY) {
+        log.scroll = (log.scroll + 1).min(max_scroll);
+    }
+    if keys.just_pressed(SCROLL_DOWN_KEY) {
+        log.scroll = log.scroll.saturating_sub(1);
+    }
+}
+
+// Tag component used to tag the message log panel
+#[derive(Component)]
+pub struct MessageLogPanel;
+
+//shows the latest messages (or older ones when scrolled) in the bottom right corner
+pub fn update_message_log_panel(
+    log: Res<MessageLog>,
+    panels: Query<Entity, With<MessageLogPanel>>,
+    mut commands: Commands,
+) {
+    if !log.is_changed() && !panels.is_empty() {
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    let end = log.messages.len().saturating_sub(log.scroll);
+    let start = end.saturating_sub(VISIBLE_MESSAGES);
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    bottom: Val::Px(20.0),
+                    right: Val::Px(20.0),
+                    width: Val::Px(420.0),
+                    flex_direction: FlexDirection::Column,
+                    padding: UiRect::all(Val::Px(8.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.6).into(),
+                ..default()
+            },
+            MessageLogPanel,
+        ))
+        .with_children(|parent| {
+            for message in log.messages.range(start..end) {
+                parent.spawn(TextBundle::from_section(
+                    message.display(),
+                    TextStyle {
+                        font_size: 16.0,
+                        color: message.kind.color(),
+                        ..default()
+                    },
+                ));
+            }
+            if log.scroll > 0 {
+                parent.spawn(TextBundle::from_section(
+                    format!("({} newer)", log.scroll),
+                    TextStyle {
+                        font_size: 14.0,
+                        color: TEXT_COLOR,
+                        ..default()
+                    },
+                ));
+            }
+        });
+}
+
+pub struct MessageLogPlugin;
+
+impl Plugin for MessageLogPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<MessageLog>()
+            .add_systems(
+                Update,
+                (
+                    log_damage.after(resolve_attacks),
+                    log_deaths.after(resolve_attacks).before(handle_deaths),
+                    log_level_ups,
+                    log_traps,
+                    log_status_effects,
+                    log_floor_changes,
+                )
+                    .before(update_message_log_panel)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                Update,
+                (scroll_message_log, update_message_log_panel)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<MessageLogPanel>);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn a_repeated_message_counts_up() {
+        let mut log = MessageLog::default();
+        log.add(MessageKind::Combat, "You hit the Goblin for 4");
+        log.add(MessageKind::Combat, "You hit the Goblin for 4");
+        log.add(MessageKind::Combat, "You hit the Goblin for 4");
+        assert_eq!(log.messages.len(), 1);
+        assert_eq!(log.messages[0].count, 3);
+        assert_eq!(log.messages[0].display(), "You hit the Goblin for 4 x3");
+    }
+
+    #[test]
+    fn only_the_same_text_and_kind_count_up() {
+        let mut log = MessageLog::default();
+        log.add(MessageKind::Combat, "You hit the Goblin for 4");
+        log.add(MessageKind::Combat, "You hit the Goblin for 5");
+        log.add(MessageKind::Warning, "You hit the Goblin for 5");
+        log.add(MessageKind::Combat, "You hit the Goblin for 4");
+        assert_eq!(log.messages.len(), 4);
+        assert!(log.messages.iter().all(|message| message.count == 1));
+        assert_eq!(log.messages[0].display(), "You hit the Goblin for 4");
+    }
+
+    #[test]
+    fn old_messages_are_forgotten() {
+        let mut log = MessageLog::default();
+        for n in 0..MAX_MESSAGES + 10 {
+            log.add(MessageKind::Info, format!("message {}", n));
+        }
+        assert_eq!(log.messages.len(), MAX_MESSAGES);
+        assert_eq!(log.messages.front().unwrap().text, "message 10");
+        assert_eq!(log.messages.back().unwrap().text, format!("message {}", MAX_MESSAGES + 9));
+    }
+
+    #[test]
+    fn a_new_message_scrolls_back_down() {
+        let mut log = MessageLog::default();
+        log.add(MessageKind::Info, "first");
+        log.scroll = 3;
+        log.add(MessageKind::Info, "first");
+        assert_eq!(log.scroll, 0);
+        log.scroll = 3;
+        log.add(MessageKind::Info, "second");
+        assert_eq!(log.scroll, 0);
+    }
+}
@@ -0,0 +1,256 @@
+use bevy::prelude::*;
+use bevy::render::render_resource::{Extent3d, TextureDimension, TextureFormat};
+use bevy::render::texture::ImageSampler;
+
+use crate::creature::Monster;
+use crate::exploration::{explore_around_player, SIGHT_RADIUS};
+use crate::floor::Floor;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::GRID_SIZE;
+use crate::player::Player;
+use crate::position::Position;
+use crate::tiles::TileType;
+use crate::util::despawn_screen;
+
+pub const MINIMAP_KEY: KeyCode = KeyCode::M;
+//screen pixels per tile, and how opaque the map is, in the corner and enlarged
+const SMALL_SCALE: f32 = 2.0;
+const SMALL_ALPHA: f32 = 0.9;
+const LARGE_SCALE: f32 = 6.0;
+const LARGE_ALPHA: f32 = 0.6;
+const UNEXPLORED: [u8; 4] = [0, 0, 0, 0];
+const PLAYER_COLOR: [u8; 4] = [255, 255, 255, 255];
+const MONSTER_COLOR: [u8; 4] = [230, 40, 40, 255];
+
+fn tile_color(tile_type : TileType) -> [u8; 4] {
+    match tile_type {
+        TileType::Wall => [90, 80, 70, 255],
+        TileType::Floor => [150, 140, 120, 255],
+        TileType::DownStairs => [255, 215, 0, 255],
+        TileType::UpStairs => [80, 220, 120, 255],
+        TileType::Fountain => [100, 180, 255, 255],
+        TileType::Water => [40, 80, 200, 255],
+        TileType::Lava => [230, 90, 20, 255],
+    }
+}
+
+//the texture's rows go down, the grid's go up
+fn pixel_index(tile : (usize, usize)) -> usize {
+    ((GRID_SIZE - 1 - tile.1) * GRID_SIZE + tile.0) * 4
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum MinimapMode {
+    //in the corner
+    #[default]
+    Small,
+    //over the middle of the screen, see-through
+    Large,
+}
+
+//The minimap's texture, one pixel per tile, and what is drawn on it
+#[derive(Resource)]
+pub struct Minimap {
+    pub image: Handle<Image>,
+    pub mode: MinimapMode,
+    //floor the tiles were drawn for
+    pub level: Option<usize>,
+    //the color of every tile as last drawn, without the markers
+    pub tiles: Vec<[u8; 4]>,
+    //tiles the player and monster markers were drawn on
+    pub markers: Vec<(usize, usize)>,
+}
+
+//one pixel per tile, nothing explored yet
+fn blank_image() -> Image {
+    let mut image = Image::new_fill(
+        Extent3d {
+            width: GRID_SIZE as u32,
+            height: GRID_SIZE as u32,
+            depth_or_array_layers: 1,
+        },
+        TextureDimension::D2,
+        &UNEXPLORED,
+        TextureFormat::Rgba8UnormSrgb,
+    );
+    //sharp pixels when scaled up
+    image.sampler_descriptor = ImageSampler::nearest();
+    image
+}
+
+//the explored part of a floor drawn like the minimap, without the monsters, e.g. for the save slots
+pub fn floor_image(floor : &Floor, player_tile : (usize, usize)) -> Image {
+    let mut image = blank_image();
+    for y in 0..GRID_SIZE {
+        for x in 0..GRID_SIZE {
+            if floor.is_explored((x, y)) {
+                let index = pixel_index((x, y));
+                image.data[index..index + 4].copy_from_slice(&tile_color(floor.grid.get((x, y))));
+            }
+        }
+    }
+    let index = pixel_index(player_tile);
+    image.data[index..index + 4].copy_from_slice(&PLAYER_COLOR);
+    image
+}
+
+pub fn create_minimap(mut commands: Commands, mut images: ResMut<Assets<Image>>) {
+    commands.insert_resource(Minimap {
+        image: images.add(blank_image()),
+        mode: MinimapMode::default(),
+        level: None,
+        tiles: vec![UNEXPLORED; GRID_SIZE * GRID_SIZE],
+        markers: Vec::new(),
+    });
+}
+
+// Tag component used to tag the minimap
+#[derive(Component)]
+pub struct MinimapNode;
+
+//the minimap stays up while paused, so it is only spawned once per run
+pub fn spawn_minimap(mut commands: Commands, minimap: Res<Minimap>, nodes: Query<(), With<MinimapNode>>) {
+    if !nodes.is_empty() {
+        return;
+    }
+    commands.spawn((
+        ImageBundle {
+            style: minimap_style(minimap.mode),
+            image: UiImage::new(minimap.image.clone()),
+            background_color: minimap_tint(minimap.mode).into(),
+            ..default()
+        },
+        MinimapNode,
+    ));
+}
+
+fn minimap_style(mode : MinimapMode) -> Style {
+    match mode {
+        MinimapMode::Small => Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(20.0),
+            right: Val::Px(20.0),
+            width: Val::Px(GRID_SIZE as f32 * SMALL_SCALE),
+            height: Val::Px(GRID_SIZE as f32 * SMALL_SCALE),
+            ..default()
+        },
+        MinimapMode::Large => Style {
+            position_type: PositionType::Absolute,
+            top: Val::Px(40.0),
+            left: Val::Percent(50.0),
+            margin: UiRect::left(Val::Px(-(GRID_SIZE as f32) * LARGE_SCALE / 2.0)),
+            width: Val::Px(GRID_SIZE as f32 * LARGE_SCALE),
+            height: Val::Px(GRID_SIZE as f32 * LARGE_SCALE),
+            ..default()
+        },
+    }
+}
+
+fn minimap_tint(mode : MinimapMode) -> Color {
+    match mode {
+        MinimapMode::Small => Color::rgba(1.0, 1.0, 1.0, SMALL_ALPHA),
+        MinimapMode::Large => Color::rgba(1.0, 1.0, 1.0, LARGE_ALPHA),
+    }
+}
+
+pub fn toggle_minimap(
+    keys: Res<Input<KeyCode>>,
+    mut minimap: ResMut<Minimap>,
+    mut nodes: Query<(&mut Style, &mut BackgroundColor), With<MinimapNode>>,
+) {
+    if !keys.just_pressed(MINIMAP_KEY) {
+        return;
+    }
+    minimap.mode = match minimap.mode {
+        MinimapMode::Small => MinimapMode::Large,
+        MinimapMode::Large => MinimapMode::Small,
+    };
+    for (mut style, mut color) in nodes.iter_mut() {
+        *style = minimap_style(minimap.mode);
+        *color = minimap_tint(minimap.mode).into();
+    }
+}
+
+//redraws the tiles that changed since the last frame (all of them when the player changes floors),
+//then moves the markers of the player and the monsters they can see
+pub fn update_minimap(
+    game_world: Res<GameWorld>,
+    players: Query<&Position, With<Player>>,
+    monsters: Query<&Position, With<Monster>>,
+    mut minimap: ResMut<Minimap>,
+    mut images: ResMut<Assets<Image>>,
+) {
+    let Ok(player_pos) = players.get_single() else {
+        return;
+    };
+    let level = player_pos.2;
+    let Some(floor) = game_world.floors.get(level) else {
+        return;
+    };
+    let minimap = &mut *minimap;
+    let mut changed = Vec::new();
+    if minimap.level != Some(level) || game_world.is_changed() {
+        for y in 0..GRID_SIZE {
+            for x in 0..GRID_SIZE {
+                let color = if floor.is_explored((x, y)) {
+                    tile_color(floor.grid.get((x, y)))
+                } else {
+                    UNEXPLORED
+                };
+                let tile = &mut minimap.tiles[y * GRID_SIZE + x];
+                if *tile != color || minimap.level != Some(level) {
+                    *tile = color;
+                    changed.push(((x, y), color));
+                }
+            }
+        }
+        minimap.level = Some(level);
+    }
+    let player_tile = player_pos.to_tile();
+    let mut markers = vec![(player_tile, PLAYER_COLOR)];
+    for pos in monsters.iter().filter(|pos| pos.2 == level) {
+        let tile = pos.to_tile();
+        let dx = tile.0 as i32 - player_tile.0 as i32;
+        let dy = tile.1 as i32 - player_tile.1 as i32;
+        if dx * dx + dy * dy <= SIGHT_RADIUS * SIGHT_RADIUS && floor.grid.has_line_of_sight(player_tile, tile) {
+            markers.push((tile, MONSTER_COLOR));
+        }
+    }
+    //the player is drawn last, on top of the monsters
+    markers.reverse();
+    let marker_tiles : Vec<(usize, usize)> = markers.iter().map(|(tile, _)| *tile).collect();
+    if changed.is_empty() && marker_tiles == minimap.markers {
+        return;
+    }
+    let Some(image) = images.get_mut(&minimap.image) else {
+        return;
+    };
+    //the tiles under the old markers show again
+    for tile in minimap.markers.iter() {
+        changed.push((*tile, minimap.tiles[tile.1 * GRID_SIZE + tile.0]));
+    }
+    changed.extend(markers);
+    for (tile, color) in changed {
+        let index = pixel_index(tile);
+        image.data[index..index + 4].copy_from_slice(&color);
+    }
+    minimap.markers = marker_tiles;
+}
+
+pub struct MinimapPlugin;
+
+impl Plugin for MinimapPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_systems(Startup, create_minimap)
+            .add_systems(OnEnter(GameState::Playing), spawn_minimap)
+            .add_systems(
+                Update,
+                (toggle_minimap, update_minimap)
+                    .chain()
+                    .after(explore_around_player)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<MinimapNode>);
+    }
+}
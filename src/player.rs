@@ -1,15 +1,118 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Default)]
+use crate::abilities::{ClassAbility, ResourceKind};
+use crate::creature::CreatureArchetype;
+use crate::equipment::EquipmentSlot;
+
+#[derive(Default, Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
 pub enum PlayerClass {
     #[default]
     Warrior,
     Wizard,
 }
 
+//What a class changes on top of the player's ancestry
+pub struct ClassDefinition {
+    pub name: &'static str,
+    //added to the ancestry's base max health and attack (may be negative)
+    pub health_bonus: i32,
+    pub attack_bonus: i32,
+    pub extra_attack_rolls: u32,
+    //added to the ancestry's scaling
+    pub hp_scaling_bonus: f32,
+    pub attack_scaling_bonus: f32,
+    pub resource: ResourceKind,
+    pub max_resource: f32,
+    //resource regained per second
+    pub resource_regen: f32,
+    //ids of the items the class starts with in its inventory
+    pub starting_kit: &'static [&'static str],
+    //ids of the items the class starts wearing
+    pub starting_equipment: &'static [(EquipmentSlot, &'static str)],
+    //abilities in hotkey order
+    pub abilities: &'static [ClassAbility],
+    //ids of the spells the class starts with, in hotkey order
+    pub starting_spells: &'static [&'static str],
+}
+
+const WARRIOR: ClassDefinition = ClassDefinition {
+    name: "Warrior",
+    health_bonus: 8,
+    attack_bonus: 1,
+    extra_attack_rolls: 0,
+    hp_scaling_bonus: 0.02,
+    attack_scaling_bonus: 0.0,
+    resource: ResourceKind::Stamina,
+    max_resource: 100.0,
+    resource_regen: 10.0,
+    starting_kit: &["healing_potion"],
+    starting_equipment: &[(EquipmentSlot::Weapon, "short_sword"), (EquipmentSlot::Armor, "leather_armor")],
+    abilities: &[ClassAbility::Charge, ClassAbility::SecondWind],
+    starting_spells: &[],
+};
+
+const WIZARD: ClassDefinition = ClassDefinition {
+    name: "Wizard",
+    health_bonus: -2,
+    attack_bonus: 0,
+    extra_attack_rolls: 0,
+    hp_scaling_bonus: 0.0,
+    attack_scaling_bonus: 0.02,
+    resource: ResourceKind::Mana,
+    max_resource: 50.0,
+    resource_regen: 2.0,
+    starting_kit: &["mana_potion"],
+    starting_equipment: &[(EquipmentSlot::Weapon, "quarterstaff"), (EquipmentSlot::Armor, "robe")],
+    abilities: &[ClassAbility::Blink, ClassAbility::ManaShield],
+    starting_spells: &["firebolt", "frost_cone", "fireball", "mend"],
+};
+
+impl PlayerClass {
+    pub const ALL: [PlayerClass; 2] = [PlayerClass::Warrior, PlayerClass::Wizard];
+
+    pub fn definition(&self) -> &'static ClassDefinition {
+        match self {
+            PlayerClass::Warrior => &WARRIOR,
+            PlayerClass::Wizard => &WIZARD,
+        }
+    }
+
+    //the archetype of a player of this class, built from the archetype of their ancestry
+    pub fn apply(&self, ancestry : &CreatureArchetype) -> CreatureArchetype {
+        let class = self.definition();
+        CreatureArchetype {
+            max_health: (ancestry.max_health as i32 + class.health_bonus).max(1) as u32,
+            attack: (ancestry.attack as i32 + class.attack_bonus).max(1) as u32,
+            attack_rolls: ancestry.attack_rolls + class.extra_attack_rolls,
+            hp_scaling: ancestry.hp_scaling + class.hp_scaling_bonus,
+            attack_scaling: ancestry.attack_scaling + class.attack_scaling_bonus,
+            type_name: format!("{} {}", ancestry.type_name, class.name),
+            ..ancestry.clone()
+        }
+    }
+}
+
 //The player is a component
 #[derive(Component,Default)]
 pub struct Player {
     pub class : PlayerClass,
+    pub name : String,
 }
 
+//Where the player is aiming with the mouse, as a normalized direction in world space
+#[derive(Component)]
+pub struct Aim {
+    pub direction: Vec2,
+    //from the player to the cursor in world pixels, the camera is centered on the player
+    pub offset: Vec2,
+}
+
+impl Default for Aim {
+    fn default() -> Self {
+        Aim {
+            direction: Vec2::X,
+            offset: Vec2::ZERO,
+        }
+    }
+}
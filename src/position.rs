@@ -1,12 +1,15 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::grid::{GRID_SIZE, TILE_SIZE};
 
 //positions are floats even though we are using a grid. This is because we want to be able to move from one tile to another smoothly.
 
 
-#[derive(Debug, Clone, PartialEq, Component,Default)]
+#[derive(Debug, Clone, PartialEq, Component,Default, Serialize, Deserialize)]
 pub struct Position(pub f32, pub f32, pub usize);
 
-#[derive(Debug, Clone, PartialEq, Component,Default)]
+#[derive(Debug, Clone, PartialEq, Component,Default, Serialize, Deserialize)]
 pub struct Velocity(pub f32, pub f32);
 
 
@@ -30,4 +33,14 @@ impl Position {
     pub fn from_vec2(vec : Vec2, level : usize) -> Position {
         Position(vec.x, vec.y, level)
     }
+    //the tile the position is on, tiles are centered on multiples of TILE_SIZE
+    pub fn to_tile(&self) -> (usize, usize) {
+        (
+            (self.0 / TILE_SIZE).round().clamp(0.0, (GRID_SIZE - 1) as f32) as usize,
+            (self.1 / TILE_SIZE).round().clamp(0.0, (GRID_SIZE - 1) as f32) as usize,
+        )
+    }
+    pub fn from_tile(tile : (usize, usize), level : usize) -> Position {
+        Position(tile.0 as f32 * TILE_SIZE, tile.1 as f32 * TILE_SIZE, level)
+    }
 }
\ No newline at end of file
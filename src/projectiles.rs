@@ -0,0 +1,221 @@
+use std::collections::HashSet;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::actors::Actor;
+use crate::combat::{resolve_attacks, AttackEvent};
+use crate::combat_stats::DamageType;
+use crate::creature::Health;
+use crate::faction::{Faction, FactionTable};
+use crate::game_plugin::game::{handle_movement_event_with_collisions, WallCollisionEvent};
+use crate::game_state::GameState;
+use crate::grid::TILE_SIZE;
+use crate::position::{Position, Velocity};
+use crate::status_effects::{ApplyStatusEvent, StatusApplication};
+
+//how close (in tiles) a projectile has to be to a creature to hit it
+pub const PROJECTILE_HIT_RADIUS: f32 = 0.5;
+
+//What a projectile does, shared by spells, ranged weapons and monster ranged attacks. Distances are in tiles
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct ProjectileStats {
+    //tiles per second
+    pub speed: f32,
+    //how many creatures it flies through before stopping
+    #[serde(default)]
+    pub pierce: u32,
+    //how many times it bounces off walls before stopping
+    #[serde(default)]
+    pub bounces: u32,
+    //damages every hostile creature around where it stops, 0 to not explode
+    #[serde(default)]
+    pub explosion_radius: f32,
+    #[serde(default)]
+    pub damage_type: DamageType,
+    //put on every creature it hits
+    #[serde(default)]
+    pub status: Option<StatusApplication>,
+    pub sprite: String,
+}
+
+//A creature's attack from afar, set per creature type in the bestiary
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+pub struct RangedAttack {
+    //in tiles
+    pub range: f32,
+    pub projectile: ProjectileStats,
+}
+
+//sent to fire a projectile from a position, the projectile belongs to the owner's faction
+#[derive(Event)]
+pub struct FireProjectileEvent {
+    pub owner: Entity,
+    pub from: Position,
+    //normalized
+    pub direction: Vec2,
+    pub stats: ProjectileStats,
+    //in tiles
+    pub max_range: f32,
+    //damage already rolled (e.g. by a spell), the owner's attack is rolled on hit when None
+    pub damage: Option<u32>,
+}
+
+#[derive(Component, Debug)]
+pub struct Projectile {
+    pub owner: Entity,
+    pub faction: Faction,
+    pub damage: Option<u32>,
+    pub damage_type: DamageType,
+    pub status: Option<StatusApplication>,
+    pub pierce: u32,
+    pub bounces: u32,
+    //in pixels
+    pub explosion_radius: f32,
+    pub max_range: f32,
+    pub travelled: f32,
+    //creatures already hit, a piercing projectile doesn't hit them twice
+    pub hit: Vec<Entity>,
+}
+
+pub fn spawn_projectiles(
+    mut fire_events: EventReader<FireProjectileEvent>,
+    factions: Query<&Faction>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    for event in fire_events.iter() {
+        let velocity = event.direction * event.stats.speed * TILE_SIZE;
+        commands.spawn((
+            Actor::default(),
+            event.from.clone(),
+            Velocity(velocity.x, velocity.y),
+            SpriteBundle {
+                texture: asset_server.load(event.stats.sprite.as_str()),
+                transform: Transform::from_xyz(event.from.0, event.from.1, 2.0)
+                    .with_rotation(Quat::from_rotation_z(velocity.y.atan2(velocity.x))),
+                ..default()
+            },
+            Projectile {
+                owner: event.owner,
+                faction: factions.get(event.owner).copied().unwrap_or_default(),
+                damage: event.damage,
+                damage_type: event.stats.damage_type,
+                status: event.stats.status,
+                pierce: event.stats.pierce,
+                bounces: event.stats.bounces,
+                explosion_radius: event.stats.explosion_radius * TILE_SIZE,
+                max_range: event.max_range * TILE_SIZE,
+                travelled: 0.0,
+                hit: Vec::new(),
+            },
+        ));
+    }
+}
+
+//the creatures a projectile can hit, and which of them its faction is hostile to
+#[derive(SystemParam)]
+pub struct ProjectileTargets<'w, 's> {
+    targets: Query<'w, 's, (Entity, &'static Position, &'static Faction), With<Health>>,
+    faction_table: Res<'w, FactionTable>,
+}
+
+//hits, bounces and stops the projectiles once the movement pipeline has moved them
+pub fn update_projectiles(
+    time: Res<Time>,
+    mut wall_collisions: EventReader<WallCollisionEvent>,
+    mut projectiles: Query<(Entity, &mut Projectile, &Position, &Velocity, &mut Transform)>,
+    targets: ProjectileTargets,
+    mut attack_events: EventWriter<AttackEvent>,
+    mut status_events: EventWriter<ApplyStatusEvent>,
+    mut commands: Commands,
+) {
+    let ProjectileTargets { targets, faction_table } = targets;
+    let hit_walls : HashSet<Entity> = wall_collisions.iter().map(|event| event.actor).collect();
+    for (entity, mut projectile, pos, velocity, mut transform) in projectiles.iter_mut() {
+        let velocity = Vec2::new(velocity.0, velocity.1);
+        projectile.travelled += velocity.length() * time.delta_seconds();
+        //the velocity changes when bouncing
+        transform.rotation = Quat::from_rotation_z(velocity.y.atan2(velocity.x));
+        let mut stopped = projectile.travelled >= projectile.max_range;
+        if hit_walls.contains(&entity) {
+            if projectile.bounces > 0 {
+                projectile.bounces -= 1;
+            } else {
+                stopped = true;
+            }
+        }
+        let hit = targets.iter().find(|(target, target_pos, faction)| {
+            *target != projectile.owner
+                && !projectile.hit.contains(target)
+                && target_pos.2 == pos.2
+                && pos.distance(target_pos) <= PROJECTILE_HIT_RADIUS * TILE_SIZE
+                && faction_table.is_hostile(projectile.faction, **faction)
+        });
+        if let Some((target, _, _)) = hit {
+            attack_events.send(AttackEvent {
+                attacker: projectile.owner,
+                target,
+                damage: projectile.damage,
+                damage_type: projectile.damage_type,
+            });
+            if let Some(status) = projectile.status {
+                status_events.send(ApplyStatusEvent {
+                    target,
+                    source: Some(projectile.owner),
+                    status,
+                });
+            }
+            projectile.hit.push(target);
+            if projectile.pierce > 0 {
+                projectile.pierce -= 1;
+            } else {
+                stopped = true;
+            }
+        }
+        if !stopped {
+            continue;
+        }
+        if projectile.explosion_radius > 0.0 {
+            for (target, target_pos, faction) in targets.iter() {
+                if target != projectile.owner
+                    && !projectile.hit.contains(&target)
+                    && target_pos.2 == pos.2
+                    && pos.distance(target_pos) <= projectile.explosion_radius
+                    && faction_table.is_hostile(projectile.faction, *faction)
+                {
+                    attack_events.send(AttackEvent {
+                        attacker: projectile.owner,
+                        target,
+                        damage: projectile.damage,
+                        damage_type: projectile.damage_type,
+                    });
+                    if let Some(status) = projectile.status {
+                        status_events.send(ApplyStatusEvent {
+                            target,
+                            source: Some(projectile.owner),
+                            status,
+                        });
+                    }
+                }
+            }
+        }
+        commands.entity(entity).despawn_recursive();
+    }
+}
+
+pub struct ProjectilesPlugin;
+
+impl Plugin for ProjectilesPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<FireProjectileEvent>().add_systems(
+            Update,
+            (spawn_projectiles, update_projectiles)
+                .chain()
+                .after(handle_movement_event_with_collisions)
+                .before(resolve_attacks)
+                .run_if(in_state(GameState::Playing)),
+        );
+    }
+}
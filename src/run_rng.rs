@@ -0,0 +1,58 @@
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+
+//mixed into the seed for the floor generators, so they don't repeat the run's own numbers
+const FLOOR_SEED_SALT: u64 = 0x9e37_79b9_7f4a_7c15;
+//same for the loot and the monster AI streams
+const LOOT_SEED_SALT: u64 = 0xbf58_476d_1ce4_e5b9;
+const AI_SEED_SALT: u64 = 0x94d0_49bb_1331_11eb;
+
+//All the gameplay randomness of a run goes through this resource, so a run can be reproduced from its seed
+#[derive(Resource)]
+pub struct RunRng {
+    pub seed: u64,
+    pub rng: ChaCha8Rng,
+    //monster drops, chests, gold and shop stock, on their own so the same seed gives the same drops
+    pub loot: ChaCha8Rng,
+    //the monsters' idle wandering, rolled every frame, kept apart so the frame rate doesn't shift the other streams
+    pub ai: ChaCha8Rng,
+}
+
+impl RunRng {
+    pub fn from_seed(seed : u64) -> Self {
+        RunRng {
+            seed,
+            rng: ChaCha8Rng::seed_from_u64(seed),
+            loot: ChaCha8Rng::seed_from_u64(seed ^ LOOT_SEED_SALT),
+            ai: ChaCha8Rng::seed_from_u64(seed ^ AI_SEED_SALT),
+        }
+    }
+    pub fn random() -> Self {
+        Self::from_seed(rand::random())
+    }
+    //every floor has its own generator, so the same seed gives the same dungeon whatever the
+    //player did on the floors above
+    pub fn floor_rng(&self, depth : usize) -> ChaCha8Rng {
+        ChaCha8Rng::seed_from_u64(self.seed ^ FLOOR_SEED_SALT.wrapping_mul(depth as u64 + 1))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand::Rng;
+
+    #[test]
+    fn loot_stream_ignores_the_other_streams() {
+        let mut quiet = RunRng::from_seed(42);
+        let mut busy = RunRng::from_seed(42);
+        for _ in 0..1000 {
+            busy.ai.gen::<f32>();
+            busy.rng.gen::<u32>();
+        }
+        let quiet_rolls : Vec<u32> = (0..10).map(|_| quiet.loot.gen()).collect();
+        let busy_rolls : Vec<u32> = (0..10).map(|_| busy.loot.gen()).collect();
+        assert_eq!(quiet_rolls, busy_rolls);
+    }
+}
@@ -1,7 +1,961 @@
+use std::collections::HashMap;
+use std::fs;
+use std::path::Path;
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
 
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::SeedableRng;
+use rand_chacha::ChaCha8Rng;
+use serde::{Deserialize, Serialize};
 
+use crate::abilities::{ClassAbilities, ClassResource, ManaShield};
+use crate::ai::{Ai, AiState, Behavior};
+use crate::bestiary::Bestiary;
+use crate::consumables::{shuffle_appearances, Identification};
+use crate::creature::{effective_archetype, CreatureType, Health, Level, MaxHealth, Monster};
+use crate::equipment::{Equipment, EquipmentSlot};
+use crate::experience::{Experience, StatAllocation};
+use crate::faction::{Faction, FactionTable};
+use crate::floor::{Floor, FloorType};
+use crate::game_over::RunStats;
+use crate::game_plugin::game::{spawn_player, RunEntities, RunStarted};
+use crate::game_state::{GameState, PlayingState};
+use crate::game_world::GameWorld;
+use crate::gold::{spawn_gold_pile, Gold, GoldPile};
+use crate::grid::{Grid, GRID_SIZE};
+use crate::items::{load_items, save_items, FloorItem, Inventory, ItemStack, Items};
+use crate::loot::{spawn_chest, Chest, GeneratedItem};
+use crate::melee::Weapon;
+use crate::message_log::{Message, MessageLog};
+use crate::player::{Player, PlayerClass};
+use crate::position::{Position, Velocity};
+use crate::projectiles::RangedAttack;
+use crate::run_rng::RunRng;
+use crate::shops::{spawn_shopkeeper, Shop, Trading};
+use crate::spawning::spawn_monster;
+use crate::spells::Spellbook;
+use crate::status_effects::{StatusEffect, StatusEffects, StatusKind};
+use crate::traps::{spawn_trap, Trap, TrapKind};
+
+//how many runs can be saved side by side, each in its own file
+pub const SAVE_SLOTS: usize = 5;
+
+pub fn slot_path(slot : usize) -> String {
+    format!("saves/slot_{}.ron", slot + 1)
+}
+
+//where the run was saved before there were slots, see adopt_legacy_save
+const LEGACY_SAVE_PATH: &str = "saves/save.ron";
+
+//The first line of a save, a ron comment so the rest stays plain ron:
+//"//save version 1 checksum 0123456789abcdef", the checksum being the fnv-1a hash of the rest
+const HEADER_PREFIX: &str = "//save version ";
+
+//the version of the saves written by this build, bump it and add a migration when SaveData changes
+pub const SAVE_VERSION: u32 = 2;
+
+//MIGRATIONS[n] brings a save of version n up to version n + 1, they run one after the other.
+//Fields added to SaveData take #[serde(default)] (and renamed ones #[serde(alias)]) so older saves
+//still parse, their migration then fills in what the default can't
+const MIGRATIONS: [fn(&mut SaveData); SAVE_VERSION as usize] = [migrate_from_headerless, migrate_from_shared_rng];
+
+//version 0, the saves written before the header. Every field added since has a default, the time
+//they were saved at wasn't kept and stays 0, shown as unknown on the load screen
+fn migrate_from_headerless(_save_data : &mut SaveData) {}
+
+//version 1, loot and the monster AI rolled from the run's main stream. They get the streams a run
+//with the same seed starts with
+fn migrate_from_shared_rng(save_data : &mut SaveData) {
+    if let Some(board_state) = save_data.board_state.as_mut() {
+        let run_rng = RunRng::from_seed(board_state.rng_seed);
+        board_state.loot_rng = run_rng.loot;
+        board_state.ai_rng = run_rng.ai;
+    }
+}
+
+//placeholder for the streams missing from older saves, replaced by their migration
+fn unseeded_rng() -> ChaCha8Rng {
+    ChaCha8Rng::seed_from_u64(0)
+}
+
+fn checksum(bytes : &[u8]) -> u64 {
+    bytes.iter().fold(0xcbf29ce484222325, |hash, byte| (hash ^ *byte as u64).wrapping_mul(0x100000001b3))
+}
+
+struct SaveHeader {
+    version: u32,
+    checksum: u64,
+}
+
+impl SaveHeader {
+    //the header line without HEADER_PREFIX
+    fn parse(line : &str) -> Option<Self> {
+        let (version, checksum) = line.trim_end().split_once(" checksum ")?;
+        Some(SaveHeader {
+            version: version.parse().ok()?,
+            checksum: u64::from_str_radix(checksum, 16).ok()?,
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
 pub struct SaveData {
     pub name: String,
     pub level: u32,
-    //pub board_state: BoardState, //not yet added, planning to add this later
-}
\ No newline at end of file
+    //the floors, the creatures and everything else on them, see BoardState
+    #[serde(default)]
+    pub board_state: Option<BoardState>,
+    //the player's inventory, see items::save_items
+    #[serde(default)]
+    pub inventory: Vec<ItemStack>,
+    #[serde(default)]
+    pub equipment: HashMap<EquipmentSlot, String>,
+    //items lying on the floors, they stay where they were dropped
+    #[serde(default)]
+    pub floor_items: Vec<SavedFloorItem>,
+    //the gold the player carries, see gold.rs
+    #[serde(default)]
+    pub gold: u32,
+    //ids of the consumables identified during the run, see consumables::Identification
+    #[serde(default)]
+    pub identified: Vec<String>,
+    //items rolled from loot tables by id, see items::Items::register_generated
+    #[serde(default)]
+    pub generated_items: HashMap<String, GeneratedItem>,
+    #[serde(default)]
+    pub next_generated_item: u64,
+    //the message log, see message_log::MessageLog
+    #[serde(default)]
+    pub messages: Vec<Message>,
+    //seconds since the unix epoch, shown on the load screen
+    #[serde(default)]
+    pub saved_at: u64,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFloorItem {
+    pub stack: ItemStack,
+    pub x: f32,
+    pub y: f32,
+    pub level: usize,
+}
+
+//Everything needed to continue a run where it was left. Projectiles in flight are not kept
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct BoardState {
+    pub floors: Vec<SavedFloor>,
+    pub player: SavedPlayer,
+    pub monsters: Vec<SavedCreature>,
+    pub chests: Vec<(Chest, Position)>,
+    pub gold_piles: Vec<(u32, Position)>,
+    pub shops: Vec<(Inventory, Position)>,
+    pub traps: Vec<SavedTrap>,
+    pub rng_seed: u64,
+    pub rng: ChaCha8Rng,
+    //see RunRng::loot and RunRng::ai
+    #[serde(default = "unseeded_rng")]
+    pub loot_rng: ChaCha8Rng,
+    #[serde(default = "unseeded_rng")]
+    pub ai_rng: ChaCha8Rng,
+    pub play_time: Duration,
+    pub run_stats: RunStats,
+    pub reputation: HashMap<Faction, i32>,
+}
+
+//A floor with one string per row of tiles (see Grid::to_rows) and of explored tiles, '1' for explored
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedFloor {
+    pub floor_type: FloorType,
+    pub level: u32,
+    pub tiles: Vec<String>,
+    pub explored: Vec<String>,
+}
+
+impl SavedFloor {
+    pub fn save(floor : &Floor) -> Self {
+        SavedFloor {
+            floor_type: floor.floor_type,
+            level: floor.level,
+            tiles: floor.grid.to_rows(),
+            explored: floor
+                .explored
+                .iter()
+                .map(|row| row.iter().map(|explored| if *explored { '1' } else { '0' }).collect())
+                .collect(),
+        }
+    }
+    pub fn load(&self) -> Result<Floor, String> {
+        let grid = Grid::from_rows(&self.tiles).ok_or(format!("the tiles of floor {} are damaged", self.level + 1))?;
+        let mut explored = [[false; GRID_SIZE]; GRID_SIZE];
+        for (y, row) in self.explored.iter().enumerate().take(GRID_SIZE) {
+            for (x, c) in row.chars().enumerate().take(GRID_SIZE) {
+                explored[y][x] = c == '1';
+            }
+        }
+        Ok(Floor {
+            grid,
+            floor_type: self.floor_type,
+            level: self.level,
+            explored,
+        })
+    }
+}
+
+//A status effect, its source by index in the saved creatures (the player first, then the monsters)
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedStatusEffect {
+    pub kind: StatusKind,
+    pub remaining: f32,
+    pub magnitude: f32,
+    pub source: Option<usize>,
+    pub tick_timer: f32,
+}
+
+//What a monster is doing, the creatures it deals with by index like SavedStatusEffect::source
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedAi {
+    pub state: AiState,
+    pub behavior: Behavior,
+    pub target: Option<usize>,
+    pub aggressor: Option<usize>,
+    pub last_known_target_tile: Option<(usize, usize)>,
+    pub path: Vec<(usize, usize)>,
+    pub time_in_state: f32,
+    pub repath_timer: f32,
+    pub attack_cooldown: f32,
+    pub ranged: Option<RangedAttack>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedCreature {
+    pub creature_type: CreatureType,
+    pub position: Position,
+    pub velocity: Velocity,
+    pub level: u32,
+    pub health: u32,
+    pub max_health: u32,
+    pub faction: Faction,
+    pub status_effects: Vec<SavedStatusEffect>,
+    //the gear of humanoid monsters, the player's is in SaveData::equipment
+    pub equipment: Option<HashMap<EquipmentSlot, String>>,
+    pub ai: Option<SavedAi>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedPlayer {
+    pub creature: SavedCreature,
+    pub class: PlayerClass,
+    pub experience: Experience,
+    pub stat_allocation: StatAllocation,
+    pub resource: Option<ClassResource>,
+    pub abilities: ClassAbilities,
+    pub spellbook: Spellbook,
+    pub weapon: Weapon,
+    //seconds left on the mana shield
+    pub mana_shield: Option<f32>,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct SavedTrap {
+    pub kind: TrapKind,
+    pub rearm: f32,
+    pub revealed: bool,
+    pub position: Position,
+}
+
+//The components saved for every creature
+type CreatureData = (
+    Entity,
+    &'static CreatureType,
+    &'static Position,
+    &'static Velocity,
+    &'static Level,
+    &'static Health,
+    &'static MaxHealth,
+    &'static Faction,
+    Option<&'static StatusEffects>,
+);
+
+fn save_creature(
+    data : (Entity, &CreatureType, &Position, &Velocity, &Level, &Health, &MaxHealth, &Faction, Option<&StatusEffects>),
+    indices : &HashMap<Entity, usize>,
+) -> SavedCreature {
+    let (_, creature_type, position, velocity, level, health, max_health, faction, status_effects) = data;
+    SavedCreature {
+        creature_type: *creature_type,
+        position: position.clone(),
+        velocity: velocity.clone(),
+        level: level.0,
+        health: health.0,
+        max_health: max_health.0,
+        faction: *faction,
+        status_effects: status_effects.map_or(Vec::new(), |status_effects| {
+            status_effects
+                .effects
+                .iter()
+                .map(|effect| SavedStatusEffect {
+                    kind: effect.kind,
+                    remaining: effect.remaining,
+                    magnitude: effect.magnitude,
+                    source: effect.source.and_then(|source| indices.get(&source).copied()),
+                    tick_timer: effect.tick_timer,
+                })
+                .collect()
+        }),
+        equipment: None,
+        ai: None,
+    }
+}
+
+fn save_ai(ai : &Ai, indices : &HashMap<Entity, usize>) -> SavedAi {
+    SavedAi {
+        state: ai.state,
+        behavior: ai.behavior,
+        target: ai.target.and_then(|target| indices.get(&target).copied()),
+        aggressor: ai.aggressor.and_then(|aggressor| indices.get(&aggressor).copied()),
+        last_known_target_tile: ai.last_known_target_tile,
+        path: ai.path.clone(),
+        time_in_state: ai.time_in_state,
+        repath_timer: ai.repath_timer,
+        attack_cooldown: ai.attack_cooldown,
+        ranged: ai.ranged.clone(),
+    }
+}
+
+//writes a save as ron after its header, creating its folder if needed. The save is written next to
+//the old one first, so quitting halfway through doesn't leave half a save
+pub fn write_save(save_data : &SaveData, path : &str) -> Result<(), String> {
+    let body = ron::ser::to_string_pretty(save_data, ron::ser::PrettyConfig::default())
+        .map_err(|error| format!("could not serialize the save: {}", error))?;
+    let text = format!("{}{} checksum {:016x}\n{}", HEADER_PREFIX, SAVE_VERSION, checksum(body.as_bytes()), body);
+    if let Some(folder) = Path::new(path).parent() {
+        fs::create_dir_all(folder).map_err(|error| format!("could not create {:?}: {}", folder, error))?;
+    }
+    let temporary_path = format!("{}.tmp", path);
+    fs::write(&temporary_path, text).map_err(|error| format!("could not write {}: {}", temporary_path, error))?;
+    fs::rename(&temporary_path, path).map_err(|error| format!("could not write {}: {}", path, error))
+}
+
+//checks the header and brings older saves up to date, the errors are meant for the player
+pub fn read_save(path : &str) -> Result<SaveData, String> {
+    let bytes = fs::read(path).map_err(|error| format!("could not read {}: {}", path, error))?;
+    let text = String::from_utf8(bytes).map_err(|_| format!("{} is corrupted, it is not text", path))?;
+    let (version, body) = match text.strip_prefix(HEADER_PREFIX) {
+        Some(rest) => {
+            let (header, body) = rest
+                .split_once('\n')
+                .ok_or(format!("{} is truncated, nothing follows its header", path))?;
+            let header = SaveHeader::parse(header).ok_or(format!("{} is corrupted, its header can't be read", path))?;
+            if header.version > SAVE_VERSION {
+                return Err(format!(
+                    "{} was saved by a newer version of the game (save version {}, this one reads up to {})",
+                    path, header.version, SAVE_VERSION
+                ));
+            }
+            if checksum(body.as_bytes()) != header.checksum {
+                return Err(format!("{} is truncated or corrupted, its checksum doesn't match", path));
+            }
+            (header.version, body)
+        }
+        None => (0, text.as_str()),
+    };
+    let mut save_data : SaveData =
+        ron::from_str(body).map_err(|error| format!("{} is corrupted, it can't be read: {}", path, error))?;
+    for migration in MIGRATIONS.iter().skip(version as usize) {
+        migration(&mut save_data);
+    }
+    Ok(save_data)
+}
+
+//A save that parses can still put things on floors or tiles that aren't there, which would panic
+//once the run is going. Gives back the run and its floors when it can be continued
+pub fn check_save(save_data : &SaveData) -> Result<(&BoardState, Vec<Floor>), String> {
+    let board_state = save_data.board_state.as_ref().ok_or("the save has no run to continue".to_string())?;
+    let floors : Vec<Floor> = board_state.floors.iter().map(SavedFloor::load).collect::<Result<_, _>>()?;
+    let mut levels = vec![board_state.player.creature.position.2];
+    levels.extend(board_state.monsters.iter().map(|monster| monster.position.2));
+    levels.extend(board_state.chests.iter().map(|(_, pos)| pos.2));
+    levels.extend(board_state.gold_piles.iter().map(|(_, pos)| pos.2));
+    levels.extend(board_state.shops.iter().map(|(_, pos)| pos.2));
+    levels.extend(board_state.traps.iter().map(|trap| trap.position.2));
+    levels.extend(save_data.floor_items.iter().map(|item| item.level));
+    if let Some(level) = levels.into_iter().find(|level| *level >= floors.len()) {
+        return Err(format!("the save refers to floor {} but only has {}", level + 1, floors.len()));
+    }
+    let in_grid = |tile : &(usize, usize)| tile.0 < GRID_SIZE && tile.1 < GRID_SIZE;
+    for ai in board_state.monsters.iter().filter_map(|monster| monster.ai.as_ref()) {
+        if !ai.path.iter().chain(ai.last_known_target_tile.iter()).all(in_grid) {
+            return Err("the save has a monster heading off the map".to_string());
+        }
+    }
+    Ok((board_state, floors))
+}
+
+//The components saved for the player besides CreatureData: their progress and their gear
+type PlayerData = (
+    CreatureData,
+    &'static Player,
+    (
+        &'static Experience,
+        &'static StatAllocation,
+        Option<&'static ClassResource>,
+        &'static ClassAbilities,
+        &'static Spellbook,
+        &'static Weapon,
+        Option<&'static ManaShield>,
+    ),
+    (&'static Inventory, &'static Equipment, Option<&'static Gold>),
+);
+
+//What lies on the floors besides the creatures
+#[derive(SystemParam)]
+pub struct FloorThings<'w, 's> {
+    chests: Query<'w, 's, (&'static Chest, &'static Position)>,
+    gold_piles: Query<'w, 's, (&'static GoldPile, &'static Position)>,
+    shops: Query<'w, 's, (&'static Shop, &'static Position)>,
+    traps: Query<'w, 's, (&'static Trap, &'static Position, &'static Visibility)>,
+    floor_items: Query<'w, 's, (&'static FloorItem, &'static Position)>,
+}
+
+//The state of the run kept outside of the entities
+#[derive(SystemParam)]
+pub struct RunResources<'w> {
+    game_world: Res<'w, GameWorld>,
+    run_rng: Res<'w, RunRng>,
+    playing_state: Res<'w, PlayingState>,
+    run_stats: Res<'w, RunStats>,
+    faction_table: Res<'w, FactionTable>,
+    items: Res<'w, Items>,
+    identification: Res<'w, Identification>,
+    log: Res<'w, MessageLog>,
+}
+
+//saves the whole run to the slot of CurrentSave
+pub fn save_run(
+    players: Query<PlayerData>,
+    monsters: Query<(CreatureData, &Ai, Option<&Equipment>), With<Monster>>,
+    things: FloorThings,
+    run: RunResources,
+    current_save: Option<Res<CurrentSave>>,
+    mut notices: ResMut<SaveNotices>,
+) {
+    let Ok((player_data, player, progress, gear)) = players.get_single() else {
+        return;
+    };
+    let Some(current_save) = current_save else {
+        notices.0.push("The run could not be saved, no save slot was picked".to_string());
+        return;
+    };
+    let (experience, stat_allocation, resource, abilities, spellbook, weapon, mana_shield) = progress;
+    let (inventory, equipment, gold) = gear;
+    let FloorThings {
+        chests,
+        gold_piles,
+        shops,
+        traps,
+        floor_items,
+    } = things;
+    let RunResources {
+        game_world,
+        run_rng,
+        playing_state,
+        run_stats,
+        faction_table,
+        items,
+        identification,
+        log,
+    } = run;
+    //the player is 0, the monsters follow in the order they are saved
+    let mut indices = HashMap::new();
+    indices.insert(player_data.0, 0);
+    for (index, (monster_data, _, _)) in monsters.iter().enumerate() {
+        indices.insert(monster_data.0, index + 1);
+    }
+    let board_state = BoardState {
+        floors: game_world.floors.iter().map(SavedFloor::save).collect(),
+        player: SavedPlayer {
+            creature: save_creature(player_data, &indices),
+            class: player.class,
+            experience: experience.clone(),
+            stat_allocation: stat_allocation.clone(),
+            resource: resource.cloned(),
+            abilities: abilities.clone(),
+            spellbook: spellbook.clone(),
+            weapon: weapon.clone(),
+            mana_shield: mana_shield.map(|shield| shield.0.remaining_secs()),
+        },
+        monsters: monsters
+            .iter()
+            .map(|(monster_data, ai, equipment)| SavedCreature {
+                equipment: equipment.map(|equipment| equipment.slots.clone()),
+                ai: Some(save_ai(ai, &indices)),
+                ..save_creature(monster_data, &indices)
+            })
+            .collect(),
+        chests: chests.iter().map(|(chest, pos)| (chest.clone(), pos.clone())).collect(),
+        gold_piles: gold_piles.iter().map(|(pile, pos)| (pile.0, pos.clone())).collect(),
+        shops: shops.iter().map(|(shop, pos)| (shop.stock.clone(), pos.clone())).collect(),
+        traps: traps
+            .iter()
+            .map(|(trap, pos, visibility)| SavedTrap {
+                kind: trap.kind,
+                rearm: trap.rearm,
+                revealed: *visibility != Visibility::Hidden,
+                position: pos.clone(),
+            })
+            .collect(),
+        rng_seed: run_rng.seed,
+        rng: run_rng.rng.clone(),
+        loot_rng: run_rng.loot.clone(),
+        ai_rng: run_rng.ai.clone(),
+        play_time: playing_state.timer,
+        run_stats: run_stats.clone(),
+        reputation: faction_table.reputation.clone(),
+    };
+    let mut save_data = SaveData {
+        name: player.name.clone(),
+        level: board_state.player.creature.level,
+        board_state: Some(board_state),
+        gold: gold.map_or(0, |gold| gold.0),
+        saved_at: SystemTime::now().duration_since(UNIX_EPOCH).map_or(0, |time| time.as_secs()),
+        ..Default::default()
+    };
+    save_items(&mut save_data, inventory, equipment, floor_items.iter());
+    items.save_generated(&mut save_data);
+    identification.save(&mut save_data);
+    log.save(&mut save_data);
+    if let Err(error) = write_save(&save_data, &slot_path(current_save.0)) {
+        notices.0.push(format!("The run could not be saved: {}", error));
+    }
+}
+
+//What went wrong with the saves, for the player. The menu shows them until they are dismissed
+#[derive(Resource, Default)]
+pub struct SaveNotices(pub Vec<String>);
+
+//A save to continue, the run is rebuilt from it when entering GameState::Playing
+#[derive(Resource)]
+pub struct LoadedSave {
+    pub slot: usize,
+    pub save_data: SaveData,
+}
+
+//The slot the current run was saved to or continued from, its file is deleted when the player dies
+#[derive(Resource)]
+pub struct CurrentSave(pub usize);
+
+fn load_status_effects(saved : &[SavedStatusEffect], entities : &[Entity]) -> StatusEffects {
+    StatusEffects {
+        effects: saved
+            .iter()
+            .map(|effect| StatusEffect {
+                kind: effect.kind,
+                remaining: effect.remaining,
+                magnitude: effect.magnitude,
+                source: effect.source.and_then(|source| entities.get(source).copied()),
+                tick_timer: effect.tick_timer,
+            })
+            .collect(),
+    }
+}
+
+fn load_ai(saved : &SavedAi, entities : &[Entity]) -> Ai {
+    Ai {
+        state: saved.state,
+        target: saved.target.and_then(|target| entities.get(target).copied()),
+        aggressor: saved.aggressor.and_then(|aggressor| entities.get(aggressor).copied()),
+        last_known_target_tile: saved.last_known_target_tile,
+        path: saved.path.clone(),
+        time_in_state: saved.time_in_state,
+        repath_timer: saved.repath_timer,
+        attack_cooldown: saved.attack_cooldown,
+        ranged: saved.ranged.clone(),
+        ..Ai::new(saved.behavior)
+    }
+}
+
+//puts the saved run back, replacing whatever is left of the previous one
+//What the saved run is rebuilt with
+#[derive(SystemParam)]
+pub struct RunRestorer<'w> {
+    bestiary: Res<'w, Bestiary>,
+    asset_server: Res<'w, AssetServer>,
+    items: ResMut<'w, Items>,
+    faction_table: ResMut<'w, FactionTable>,
+}
+
+pub fn load_run(
+    loaded_save: Res<LoadedSave>,
+    leftovers: Query<Entity, RunEntities>,
+    restorer: RunRestorer,
+    mut game_state: ResMut<NextState<GameState>>,
+    mut notices: ResMut<SaveNotices>,
+    mut run_started: EventWriter<RunStarted>,
+    mut commands: Commands,
+) {
+    let RunRestorer {
+        bestiary,
+        asset_server,
+        mut items,
+        mut faction_table,
+    } = restorer;
+    commands.remove_resource::<LoadedSave>();
+    let save_data = &loaded_save.save_data;
+    let (board_state, floors) = match check_save(save_data) {
+        Ok(checked) => checked,
+        Err(error) => {
+            notices.0.push(format!("The run in slot {} can't be continued: {}", loaded_save.slot + 1, error));
+            game_state.set(GameState::Menu);
+            return;
+        }
+    };
+    for entity in leftovers.iter() {
+        commands.entity(entity).despawn_recursive();
+    }
+
+    //every creature is spawned first, so the ones they refer to have an entity
+    let saved_player = &board_state.player;
+    let player_entity = spawn_player(&mut commands, &asset_server, saved_player.creature.position.clone());
+    let mut entities = vec![player_entity];
+    for monster in board_state.monsters.iter() {
+        entities.push(spawn_monster(
+            &mut commands,
+            &asset_server,
+            &bestiary,
+            monster.creature_type,
+            monster.level,
+            monster.position.to_tile(),
+            monster.position.2,
+        ));
+    }
+
+    let player = Player {
+        class: saved_player.class,
+        name: save_data.name.clone(),
+    };
+    let archetype = effective_archetype(saved_player.creature.creature_type, Some(&player), &bestiary);
+    items.clear_generated();
+    items.load_generated(save_data);
+    let (inventory, equipment) = load_items(save_data, &mut commands, &asset_server, &items);
+    let creature = &saved_player.creature;
+    let mut player_commands = commands.entity(player_entity);
+    player_commands.insert((
+        asset_server.load::<Image, _>(archetype.sprite.as_str()),
+        creature.creature_type,
+        creature.velocity.clone(),
+        Level(creature.level),
+        Health(creature.health),
+        MaxHealth(creature.max_health),
+        creature.faction,
+        load_status_effects(&creature.status_effects, &entities),
+        saved_player.experience.clone(),
+        saved_player.stat_allocation.clone(),
+        (saved_player.abilities.clone(), saved_player.spellbook.clone(), saved_player.weapon.clone()),
+        (inventory, equipment, Gold(save_data.gold)),
+        player,
+    ));
+    if let Some(resource) = &saved_player.resource {
+        player_commands.insert(resource.clone());
+    }
+    if let Some(remaining) = saved_player.mana_shield {
+        player_commands.insert(ManaShield(Timer::from_seconds(remaining, TimerMode::Once)));
+    }
+
+    for (monster, entity) in board_state.monsters.iter().zip(entities.iter().skip(1)) {
+        let mut monster_commands = commands.entity(*entity);
+        monster_commands.insert((
+            monster.position.clone(),
+            monster.velocity.clone(),
+            Health(monster.health),
+            MaxHealth(monster.max_health),
+            monster.faction,
+            load_status_effects(&monster.status_effects, &entities),
+        ));
+        if let Some(ai) = &monster.ai {
+            monster_commands.insert(load_ai(ai, &entities));
+        }
+        if let Some(slots) = &monster.equipment {
+            monster_commands.insert(Equipment { slots: slots.clone() });
+        }
+    }
+    for (chest, position) in board_state.chests.iter() {
+        spawn_chest(&mut commands, &asset_server, chest.clone(), position.clone());
+    }
+    for (amount, position) in board_state.gold_piles.iter() {
+        spawn_gold_pile(&mut commands, &asset_server, *amount, position.clone());
+    }
+    for (stock, position) in board_state.shops.iter() {
+        let shop = Shop {
+            stock: stock.clone(),
+            stocked: true,
+        };
+        spawn_shopkeeper(&mut commands, &asset_server, shop, position.clone());
+    }
+    for trap in board_state.traps.iter() {
+        let saved = Trap {
+            kind: trap.kind,
+            rearm: trap.rearm,
+        };
+        spawn_trap(&mut commands, &asset_server, saved, trap.position.clone(), trap.revealed);
+    }
+
+    let mut game_world = GameWorld::new();
+    game_world.floors = floors;
+    game_world.player = Some(player_entity);
+    commands.insert_resource(game_world);
+    commands.insert_resource(RunRng {
+        seed: board_state.rng_seed,
+        rng: board_state.rng.clone(),
+        loot: board_state.loot_rng.clone(),
+        ai: board_state.ai_rng.clone(),
+    });
+    commands.insert_resource(PlayingState {
+        timer: board_state.play_time,
+    });
+    commands.insert_resource(board_state.run_stats.clone());
+    faction_table.reputation = board_state.reputation.clone();
+    //the appearances are shuffled from the seed like when the run started, so they don't get reshuffled
+    let mut identification = Identification::load(save_data);
+    identification.appearances = shuffle_appearances(&items, board_state.rng_seed);
+    identification.seed = Some(board_state.rng_seed);
+    commands.insert_resource(identification);
+    commands.insert_resource(MessageLog::load(save_data));
+    commands.insert_resource(Trading::default());
+    commands.insert_resource(CurrentSave(loaded_save.slot));
+    run_started.send(RunStarted);
+}
+
+//What the save slots screen shows of a saved run
+pub struct SlotSummary {
+    pub name: String,
+    pub class: PlayerClass,
+    pub level: u32,
+    //0 for the first floor
+    pub depth: usize,
+    pub play_time: Duration,
+    pub saved_at: u64,
+    //the floor the player is on and where they stand, for the thumbnail
+    pub floor: Option<Floor>,
+    pub player_tile: (usize, usize),
+}
+
+pub enum SaveSlot {
+    Empty,
+    //boxed, the floor makes the summary big
+    Saved(Box<SlotSummary>),
+    //the file is there but can't be continued, with the reason
+    Unreadable(String),
+}
+
+pub fn read_slot(slot : usize) -> SaveSlot {
+    let path = slot_path(slot);
+    if !Path::new(&path).exists() {
+        return SaveSlot::Empty;
+    }
+    let save_data = match read_save(&path) {
+        Ok(save_data) => save_data,
+        Err(error) => return SaveSlot::Unreadable(error),
+    };
+    let (board_state, mut floors) = match check_save(&save_data) {
+        Ok(checked) => checked,
+        Err(error) => return SaveSlot::Unreadable(format!("{}: {}", path, error)),
+    };
+    let player = &board_state.player.creature;
+    SaveSlot::Saved(Box::new(SlotSummary {
+        name: save_data.name.clone(),
+        class: board_state.player.class,
+        level: save_data.level,
+        depth: player.position.2,
+        play_time: board_state.play_time,
+        saved_at: save_data.saved_at,
+        floor: Some(floors.swap_remove(player.position.2)),
+        player_tile: player.position.to_tile(),
+    }))
+}
+
+//moves the save from before there were slots to a free slot, so it can still be continued
+pub fn adopt_legacy_save(mut notices: ResMut<SaveNotices>) {
+    if !Path::new(LEGACY_SAVE_PATH).exists() {
+        return;
+    }
+    let Some(slot) = (0..SAVE_SLOTS).find(|slot| !Path::new(&slot_path(*slot)).exists()) else {
+        notices.0.push(format!(
+            "The run saved in {} needs a free save slot, delete one and restart the game to continue it",
+            LEGACY_SAVE_PATH
+        ));
+        return;
+    };
+    if let Err(error) = fs::rename(LEGACY_SAVE_PATH, slot_path(slot)) {
+        notices.0.push(format!(
+            "The run saved in {} could not be moved to slot {}: {}",
+            LEGACY_SAVE_PATH,
+            slot + 1,
+            error
+        ));
+    }
+}
+
+//What is in every save slot. The menu screens that show the slots read them once when they are set up
+#[derive(Resource, Default)]
+pub struct SaveSlots(pub Vec<SaveSlot>);
+
+impl SaveSlots {
+    pub fn scan() -> Self {
+        SaveSlots((0..SAVE_SLOTS).map(read_slot).collect())
+    }
+    //the slot saved last, that Continue picks up
+    pub fn latest(&self) -> Option<usize> {
+        self.0
+            .iter()
+            .enumerate()
+            .filter_map(|(slot, save_slot)| match save_slot {
+                SaveSlot::Saved(summary) => Some((slot, summary.saved_at)),
+                _ => None,
+            })
+            .max_by_key(|(_, saved_at)| *saved_at)
+            .map(|(slot, _)| slot)
+    }
+    pub fn all_empty(&self) -> bool {
+        self.0.iter().all(|save_slot| matches!(save_slot, SaveSlot::Empty))
+    }
+}
+
+pub fn delete_slot(slot : usize) -> Result<(), String> {
+    let path = slot_path(slot);
+    fs::remove_file(&path).map_err(|error| format!("could not delete {}: {}", path, error))
+}
+
+//e.g. "2026-10-19 14:03 UTC", from seconds since the unix epoch
+pub fn format_timestamp(seconds : u64) -> String {
+    //days to a civil date, see http://howardhinnant.github.io/date_algorithms.html#civil_from_days
+    let days = (seconds / 86400) as i64 + 719468;
+    let era = days.div_euclid(146097);
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let shifted_month = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * shifted_month + 2) / 5 + 1;
+    let month = if shifted_month < 10 { shifted_month + 3 } else { shifted_month - 9 };
+    let year = year_of_era + era * 400 + if month <= 2 { 1 } else { 0 };
+    format!(
+        "{}-{:02}-{:02} {:02}:{:02} UTC",
+        year,
+        month,
+        day,
+        seconds / 3600 % 24,
+        seconds / 60 % 60
+    )
+}
+
+pub struct SaveDataPlugin;
+
+impl Plugin for SaveDataPlugin {
+    fn build(&self, app: &mut App) {
+        //the run is saved before leaving it tears it down
+        app.init_resource::<SaveNotices>()
+            .init_resource::<SaveSlots>()
+            .add_systems(
+                OnTransition {
+                    from: GameState::Paused,
+                    to: GameState::Menu,
+                },
+                save_run,
+            )
+            .add_systems(OnEnter(GameState::Playing), load_run.run_if(resource_exists::<LoadedSave>()))
+            .add_systems(Startup, adopt_legacy_save);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    //a file of its own in the temporary folder for every test, as they run in parallel
+    fn test_path(name : &str) -> String {
+        std::env::temp_dir()
+            .join(format!("rg_save_test_{}_{}.ron", std::process::id(), name))
+            .to_string_lossy()
+            .into_owned()
+    }
+
+    fn test_save() -> SaveData {
+        SaveData {
+            name: "Tester".to_string(),
+            level: 7,
+            gold: 120,
+            identified: vec!["healing_potion".to_string()],
+            saved_at: 1_700_000_000,
+            ..Default::default()
+        }
+    }
+
+    #[test]
+    fn round_trip_through_write_save() {
+        let path = test_path("round_trip");
+        write_save(&test_save(), &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        assert!(text.starts_with(&format!("{}{} checksum ", HEADER_PREFIX, SAVE_VERSION)));
+        let loaded = read_save(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+        assert_eq!(loaded.name, "Tester");
+        assert_eq!(loaded.level, 7);
+        assert_eq!(loaded.gold, 120);
+        assert_eq!(loaded.identified, vec!["healing_potion".to_string()]);
+        assert_eq!(loaded.saved_at, 1_700_000_000);
+    }
+
+    #[test]
+    fn headerless_save_is_migrated() {
+        let path = test_path("headerless");
+        //written before the header and before most of the fields existed
+        fs::write(&path, "(name: \"Old\", level: 3, gold: 5)").unwrap();
+        let loaded = read_save(&path);
+        fs::remove_file(&path).unwrap();
+        let loaded = loaded.unwrap();
+        assert_eq!(loaded.name, "Old");
+        assert_eq!(loaded.level, 3);
+        assert_eq!(loaded.gold, 5);
+        assert_eq!(loaded.saved_at, 0);
+        assert!(loaded.board_state.is_none());
+    }
+
+    #[test]
+    fn wrong_checksum_is_an_error() {
+        let path = test_path("checksum");
+        write_save(&test_save(), &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap().replace("Tester", "Tampered");
+        fs::write(&path, text).unwrap();
+        let error = read_save(&path).err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.unwrap().contains("checksum"));
+    }
+
+    #[test]
+    fn truncated_save_is_an_error() {
+        let path = test_path("truncated");
+        write_save(&test_save(), &path).unwrap();
+        let text = fs::read_to_string(&path).unwrap();
+        fs::write(&path, &text[..text.len() / 2]).unwrap();
+        let error = read_save(&path).err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.unwrap().contains("truncated"));
+    }
+
+    #[test]
+    fn newer_version_is_rejected() {
+        let path = test_path("newer");
+        let body = ron::ser::to_string(&test_save()).unwrap();
+        let text = format!("{}{} checksum {:016x}\n{}", HEADER_PREFIX, SAVE_VERSION + 1, checksum(body.as_bytes()), body);
+        fs::write(&path, text).unwrap();
+        let error = read_save(&path).err();
+        fs::remove_file(&path).unwrap();
+        assert!(error.unwrap().contains("newer version"));
+    }
+
+    #[test]
+    fn timestamps_are_formatted_as_utc_dates() {
+        assert_eq!(format_timestamp(0), "1970-01-01 00:00 UTC");
+        assert_eq!(format_timestamp(1_700_000_000), "2023-11-14 22:13 UTC");
+    }
+}
@@ -0,0 +1,374 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+
+use crate::actors::Actor;
+use crate::consumables::Identification;
+use crate::floor::FloorType;
+use crate::game_plugin::game::{FloorGenerated, RunStarted};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::gold::{scatter_gold, Gold};
+use crate::grid::TILE_SIZE;
+use crate::items::{spawn_inventory_row, Inventory, ItemStack, Items, PICKUP_KEY};
+use crate::loot::{Loot, LootSource};
+use crate::main_menu_plugin::menu::{HOVERED_BUTTON, NORMAL_BUTTON, TEXT_COLOR};
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::Player;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::spawning::spawn_candidates;
+use crate::util::despawn_screen;
+
+pub const SHOPS_PER_TOWN: usize = 2;
+//number of stacks a shop holds, what the player sells counts too
+pub const SHOP_CAPACITY: usize = 16;
+//prices go up by this fraction every floor down
+pub const PRICE_PER_DEPTH: f32 = 0.1;
+//fraction of the price the shops pay for what the player sells them
+pub const SELL_RATIO: f32 = 0.4;
+//how close (in tiles) the player has to stay to a shopkeeper to trade
+pub const TRADE_RADIUS: f32 = 1.5;
+pub const SHOPKEEPER_SPRITE: &str = "player/base/human_male.png";
+
+//A shopkeeper standing in a town, its stock is rolled again every time the player comes back to the town
+#[derive(Component, Debug, Clone)]
+pub struct Shop {
+    pub stock: Inventory,
+    //whether the stock was rolled yet, shops loaded from a save keep theirs
+    pub stocked: bool,
+}
+
+pub fn spawn_shopkeeper(commands : &mut Commands, asset_server : &AssetServer, shop : Shop, position : Position) -> Entity {
+    commands
+        .spawn((
+            Actor::default(),
+            SpriteBundle {
+                texture: asset_server.load(SHOPKEEPER_SPRITE),
+                transform: Transform::from_xyz(position.0, position.1, 1.0),
+                ..default()
+            },
+            position,
+            shop,
+        ))
+        .id()
+}
+
+//what the player pays for one item at this depth
+pub fn buy_price(items : &Items, id : &str, depth : usize) -> u32 {
+    let Some(item) = items.get(id) else {
+        return 0;
+    };
+    let price = item.value as f32 * item.rarity.price_multiplier() * (1.0 + depth as f32 * PRICE_PER_DEPTH);
+    (price.round() as u32).max(1)
+}
+
+//what the shops pay the player for one item at this depth
+pub fn sell_price(items : &Items, id : &str, depth : usize) -> u32 {
+    (buy_price(items, id, depth) as f32 * SELL_RATIO) as u32
+}
+
+//put the shopkeepers in every newly generated town
+pub fn place_shopkeepers(
+    mut ev_floor_generated: EventReader<FloorGenerated>,
+    game_world: Res<GameWorld>,
+    asset_server: Res<AssetServer>,
+    mut run_rng: ResMut<RunRng>,
+    mut commands: Commands,
+) {
+    for event in ev_floor_generated.iter() {
+        let floor = &game_world.floors[event.level];
+        if floor.floor_type != FloorType::Town {
+            continue;
+        }
+        let candidates = spawn_candidates(&floor.grid, &[]);
+        for tile in candidates.choose_multiple(&mut run_rng.rng, SHOPS_PER_TOWN) {
+            let shop = Shop {
+                stock: Inventory {
+                    stacks: Vec::new(),
+                    capacity: SHOP_CAPACITY,
+                },
+                stocked: false,
+            };
+            spawn_shopkeeper(&mut commands, &asset_server, shop, Position::from_tile(*tile, event.level));
+        }
+    }
+}
+
+//new shops, and the shops of the town the player just arrived in, get a fresh stock from the loot tables
+pub fn restock_shops(
+    players: Query<&Position, With<Player>>,
+    mut shops: Query<(&mut Shop, &Position)>,
+    loot: Res<Loot>,
+    mut items: ResMut<Items>,
+    mut run_rng: ResMut<RunRng>,
+    mut run_started: EventReader<RunStarted>,
+    mut last_floor: Local<Option<usize>>,
+) {
+    //a run that was just started or loaded didn't arrive anywhere, and a loaded shop keeps its saved stock
+    if !run_started.is_empty() {
+        run_started.clear();
+        *last_floor = None;
+    }
+    let Ok(player_pos) = players.get_single() else {
+        return;
+    };
+    let arrived = last_floor.is_some_and(|last_floor| last_floor != player_pos.2);
+    *last_floor = Some(player_pos.2);
+    for (mut shop, pos) in shops.iter_mut() {
+        if shop.stocked && !(arrived && pos.2 == player_pos.2) {
+            continue;
+        }
+        let mut stock = Inventory {
+            stacks: Vec::new(),
+            capacity: SHOP_CAPACITY,
+        };
+        for stack in loot.roll_drops(LootSource::Shop, pos.2, &mut items, &mut run_rng.loot) {
+            let max_stack = items.max_stack(&stack.id);
+            stock.add(stack, max_stack);
+        }
+        shop.stock = stock;
+        shop.stocked = true;
+    }
+}
+
+//who the player is trading with, if anyone
+#[derive(Resource, Default)]
+pub struct Trading(pub Option<Entity>);
+
+//the pickup key starts trading with a shopkeeper next to the player, or stops it. Walking away stops it too
+pub fn start_trading(
+    keys: Res<Input<KeyCode>>,
+    players: Query<&Position, With<Player>>,
+    shops: Query<(Entity, &Position), With<Shop>>,
+    mut trading: ResMut<Trading>,
+) {
+    let Ok(player_pos) = players.get_single() else {
+        return;
+    };
+    let in_reach = |pos : &Position| pos.2 == player_pos.2 && player_pos.distance(pos) <= TRADE_RADIUS * TILE_SIZE;
+    if keys.just_pressed(PICKUP_KEY) {
+        let nearby = shops.iter().find(|(_, pos)| in_reach(pos)).map(|(shop, _)| shop);
+        trading.0 = if trading.0.is_some() { None } else { nearby };
+    } else if let Some(shop) = trading.0 {
+        if !shops.get(shop).is_ok_and(|(_, pos)| in_reach(pos)) {
+            trading.0 = None;
+        }
+    }
+}
+
+// What a button of the trade panel does with its slot
+#[derive(Component, Clone, Copy)]
+pub enum TradeAction {
+    //a slot of the shop's stock
+    Buy(usize),
+    //a slot of the player's inventory
+    Sell(usize),
+}
+
+//sent to buy or sell one item of a slot
+#[derive(Event)]
+pub struct TradeEvent {
+    pub customer: Entity,
+    pub shop: Entity,
+    pub action: TradeAction,
+}
+
+pub fn trade_items(
+    mut trade_events: EventReader<TradeEvent>,
+    mut customers: Query<(&mut Inventory, &mut Gold), Without<Shop>>,
+    mut shops: Query<(&mut Shop, &Position)>,
+    items: Res<Items>,
+    identification: Res<Identification>,
+    mut log: ResMut<MessageLog>,
+) {
+    for event in trade_events.iter() {
+        let (Ok((mut inventory, mut gold)), Ok((mut shop, pos))) = (customers.get_mut(event.customer), shops.get_mut(event.shop)) else {
+            continue;
+        };
+        match event.action {
+            TradeAction::Buy(slot) => {
+                let Some(id) = shop.stock.stacks.get(slot).map(|stack| stack.id.clone()) else {
+                    continue;
+                };
+                let price = buy_price(&items, &id, pos.2);
+                if gold.0 < price {
+                    log.add(MessageKind::Warning, format!("Not enough gold for the {}", identification.name(&items, &id)));
+                    continue;
+                }
+                if inventory.add(ItemStack::new(&id, 1), items.max_stack(&id)).is_some() {
+                    log.add(MessageKind::Warning, "Your inventory is full");
+                    continue;
+                }
+                shop.stock.take(slot, 1);
+                gold.0 -= price;
+                log.add(
+                    MessageKind::Loot,
+                    format!("You buy the {} for {} gold", identification.name(&items, &id), price),
+                );
+            }
+            TradeAction::Sell(slot) => {
+                let Some(id) = inventory.stacks.get(slot).map(|stack| stack.id.clone()) else {
+                    continue;
+                };
+                if shop.stock.add(ItemStack::new(&id, 1), items.max_stack(&id)).is_some() {
+                    log.add(MessageKind::Warning, format!("The shop has no room for the {}", identification.name(&items, &id)));
+                    continue;
+                }
+                inventory.take(slot, 1);
+                let price = sell_price(&items, &id, pos.2);
+                gold.0 += price;
+                log.add(
+                    MessageKind::Loot,
+                    format!("You sell the {} for {} gold", identification.name(&items, &id), price),
+                );
+            }
+        }
+    }
+}
+
+// Tag component used to tag the trade panel
+#[derive(Component)]
+pub struct TradePanel;
+
+//rebuilds the panel when trading starts or stops, or either side of the trade changes
+pub fn update_trade_panel(
+    trading: Res<Trading>,
+    players: Query<(Ref<Inventory>, Ref<Gold>), With<Player>>,
+    shops: Query<(Ref<Shop>, &Position)>,
+    panels: Query<Entity, With<TradePanel>>,
+    items: Res<Items>,
+    identification: Res<Identification>,
+    mut commands: Commands,
+) {
+    let Ok((inventory, gold)) = players.get_single() else {
+        return;
+    };
+    let shop = trading.0.and_then(|shop| shops.get(shop).ok());
+    let shop_changed = shop.as_ref().is_some_and(|(shop, _)| shop.is_changed());
+    let outdated = panels.is_empty() == shop.is_some();
+    if !outdated
+        && !trading.is_changed()
+        && !shop_changed
+        && !inventory.is_changed()
+        && !gold.is_changed()
+        && !items.is_changed()
+        && !identification.is_changed()
+    {
+        return;
+    }
+    for panel in panels.iter() {
+        commands.entity(panel).despawn_recursive();
+    }
+    let Some((shop, pos)) = shop else {
+        return;
+    };
+    let text_style = TextStyle {
+        font_size: 20.0,
+        color: TEXT_COLOR,
+        ..default()
+    };
+    let column = NodeBundle {
+        style: Style {
+            flex_direction: FlexDirection::Column,
+            margin: UiRect::right(Val::Px(20.0)),
+            ..default()
+        },
+        ..default()
+    };
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(60.0),
+                    left: Val::Px(20.0),
+                    padding: UiRect::all(Val::Px(10.0)),
+                    ..default()
+                },
+                background_color: Color::rgba(0.1, 0.1, 0.1, 0.8).into(),
+                ..default()
+            },
+            TradePanel,
+        ))
+        .with_children(|parent| {
+            parent.spawn(column.clone()).with_children(|parent| {
+                parent.spawn(TextBundle::from_section("For sale", text_style.clone()));
+                for (slot, stack) in shop.stock.stacks.iter().enumerate() {
+                    let label = stock_label(&items, &identification, stack, buy_price(&items, &stack.id, pos.2));
+                    let color = items.rarity(&stack.id).color();
+                    spawn_inventory_row(parent, label, color, &[(TradeAction::Buy(slot), "Buy")], &text_style);
+                }
+            });
+            parent.spawn(column).with_children(|parent| {
+                parent.spawn(TextBundle::from_section(format!("Your gold: {}", gold.0), text_style.clone()));
+                for (slot, stack) in inventory.stacks.iter().enumerate() {
+                    let label = stock_label(&items, &identification, stack, sell_price(&items, &stack.id, pos.2));
+                    let color = items.rarity(&stack.id).color();
+                    spawn_inventory_row(parent, label, color, &[(TradeAction::Sell(slot), "Sell")], &text_style);
+                }
+            });
+        });
+}
+
+//e.g. "Potion of Healing x3 (15 gold)"
+fn stock_label(items : &Items, identification : &Identification, stack : &ItemStack, price : u32) -> String {
+    let mut label = identification.name(items, &stack.id);
+    if stack.count > 1 {
+        label = format!("{} x{}", label, stack.count);
+    }
+    format!("{} ({} gold)", label, price)
+}
+
+pub fn trade_buttons(
+    mut interaction_query: Query<(&Interaction, &TradeAction, &mut BackgroundColor), Changed<Interaction>>,
+    players: Query<Entity, With<Player>>,
+    trading: Res<Trading>,
+    mut trade_events: EventWriter<TradeEvent>,
+) {
+    for (interaction, action, mut color) in interaction_query.iter_mut() {
+        *color = match interaction {
+            Interaction::Hovered => HOVERED_BUTTON.into(),
+            _ => NORMAL_BUTTON.into(),
+        };
+        if *interaction != Interaction::Pressed {
+            continue;
+        }
+        let (Ok(customer), Some(shop)) = (players.get_single(), trading.0) else {
+            continue;
+        };
+        trade_events.send(TradeEvent {
+            customer,
+            shop,
+            action: *action,
+        });
+    }
+}
+
+pub fn stop_trading(mut trading: ResMut<Trading>) {
+    trading.0 = None;
+}
+
+pub struct ShopsPlugin;
+
+impl Plugin for ShopsPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<Trading>()
+            .add_event::<TradeEvent>()
+            .add_systems(
+                Update,
+                place_shopkeepers
+                    .after(scatter_gold)
+                    .run_if(on_event::<FloorGenerated>()),
+            )
+            .add_systems(
+                Update,
+                (restock_shops, start_trading, trade_buttons, trade_items, update_trade_panel)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(
+                OnEnter(GameState::Menu),
+                (stop_trading, despawn_screen::<TradePanel>),
+            );
+    }
+}
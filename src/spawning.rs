@@ -0,0 +1,346 @@
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use rand::Rng;
+
+use crate::ai::Ai;
+use crate::bestiary::Bestiary;
+use crate::combat_stats::CombatStats;
+use crate::creature::*;
+use crate::equipment::Equipment;
+use crate::floor::FloorType;
+use crate::game_plugin::game::FloorGenerated;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::Grid;
+use crate::player::Player;
+use crate::position::{Position, Velocity};
+use crate::run_rng::RunRng;
+use crate::status_effects::StatusEffects;
+use crate::tiles::TileType;
+
+//monsters never spawn closer than this (in tiles) to the up stairs, so the player isn't ambushed on arrival
+pub const MIN_DISTANCE_FROM_STAIRS: f32 = 8.0;
+//monsters spawned during play also keep this distance (in tiles) from the player
+pub const MIN_DISTANCE_FROM_PLAYER: f32 = 12.0;
+pub const MAX_GROUPS_PER_FLOOR: u32 = 12;
+//spawning during play stops once the player's floor has this many monsters
+pub const MAX_MONSTERS_PER_FLOOR: usize = 30;
+//seconds between two spawns during play
+pub const SPAWN_INTERVAL: f32 = 30.0;
+
+//One kind of monster that can show up on a floor
+pub struct SpawnEntry {
+    pub creature_type: CreatureType,
+    //depths (inclusive) where the entry is used
+    pub min_depth: usize,
+    pub max_depth: usize,
+    //relative chance of being picked against the other valid entries
+    pub weight: u32,
+    //range (inclusive) of the monster level before the depth bonus
+    pub levels: (u32, u32),
+    //range (inclusive) of how many monsters spawn together
+    pub group_size: (u32, u32),
+}
+
+impl SpawnEntry {
+    fn new(creature_type : CreatureType, depths : (usize, usize), weight : u32, levels : (u32, u32), group_size : (u32, u32)) -> Self {
+        SpawnEntry {
+            creature_type,
+            min_depth: depths.0,
+            max_depth: depths.1,
+            weight,
+            levels,
+            group_size,
+        }
+    }
+}
+
+pub struct SpawnTable {
+    pub floor_type: FloorType,
+    //number of groups placed when the floor is generated, one more is added every two floors
+    pub base_groups: u32,
+    pub entries: Vec<SpawnEntry>,
+}
+
+//The result of a roll on a spawn table
+pub struct SpawnGroup {
+    pub creature_type: CreatureType,
+    pub level: u32,
+    pub size: u32,
+}
+
+#[derive(Resource)]
+pub struct SpawnTables {
+    pub tables: Vec<SpawnTable>,
+}
+
+impl Default for SpawnTables {
+    fn default() -> Self {
+        use CreatureType::*;
+        let deep = usize::MAX;
+        SpawnTables {
+            tables: vec![
+                SpawnTable {
+                    floor_type: FloorType::Dungeon,
+                    base_groups: 3,
+                    entries: vec![
+                        SpawnEntry::new(Goblin, (0, 6), 10, (1, 2), (2, 4)),
+                        SpawnEntry::new(Skeleton, (1, 10), 6, (1, 3), (1, 3)),
+                        SpawnEntry::new(Zombie, (2, 12), 5, (2, 4), (1, 2)),
+                        SpawnEntry::new(Orc, (2, 14), 6, (2, 4), (2, 3)),
+                        SpawnEntry::new(Troll, (5, 20), 3, (3, 5), (1, 1)),
+                        SpawnEntry::new(Werewolf, (6, 25), 3, (3, 5), (1, 2)),
+                        SpawnEntry::new(Vampire, (8, 30), 2, (4, 6), (1, 1)),
+                        SpawnEntry::new(DeepElf, (10, deep), 3, (5, 7), (2, 3)),
+                        SpawnEntry::new(DeepDwarf, (10, deep), 3, (5, 7), (2, 3)),
+                        SpawnEntry::new(Demon, (12, deep), 2, (6, 8), (1, 2)),
+                        SpawnEntry::new(Devil, (12, deep), 2, (6, 8), (1, 2)),
+                        SpawnEntry::new(Angel, (15, deep), 1, (7, 9), (1, 1)),
+                        SpawnEntry::new(Dragon, (18, deep), 1, (8, 10), (1, 1)),
+                    ],
+                },
+                SpawnTable {
+                    floor_type: FloorType::BossRoom,
+                    base_groups: 1,
+                    entries: vec![
+                        SpawnEntry::new(Dragon, (0, 19), 1, (6, 8), (1, 1)),
+                        SpawnEntry::new(Demon, (20, 29), 1, (9, 11), (2, 3)),
+                        SpawnEntry::new(God, (30, deep), 1, (12, 14), (1, 1)),
+                    ],
+                },
+                //towns are safe, nothing spawns there
+                SpawnTable {
+                    floor_type: FloorType::Town,
+                    base_groups: 0,
+                    entries: Vec::new(),
+                },
+            ],
+        }
+    }
+}
+
+impl SpawnTables {
+    pub fn table(&self, floor_type : FloorType) -> Option<&SpawnTable> {
+        self.tables.iter().find(|table| table.floor_type == floor_type)
+    }
+
+    pub fn roll_group(&self, floor_type : FloorType, depth : usize, rng : &mut impl Rng) -> Option<SpawnGroup> {
+        let table = self.table(floor_type)?;
+        let valid : Vec<&SpawnEntry> = table
+            .entries
+            .iter()
+            .filter(|entry| entry.min_depth <= depth && depth <= entry.max_depth)
+            .collect();
+        let entry = valid.choose_weighted(rng, |entry| entry.weight).ok()?;
+        Some(SpawnGroup {
+            creature_type: entry.creature_type,
+            //deeper floors have stronger monsters
+            level: rng.gen_range(entry.levels.0..=entry.levels.1) + depth as u32 / 3,
+            size: rng.gen_range(entry.group_size.0..=entry.group_size.1),
+        })
+    }
+}
+
+#[derive(Resource)]
+pub struct SpawnTimer(pub Timer);
+
+impl Default for SpawnTimer {
+    fn default() -> Self {
+        SpawnTimer(Timer::from_seconds(SPAWN_INTERVAL, TimerMode::Repeating))
+    }
+}
+
+fn tile_distance(a : (usize, usize), b : (usize, usize)) -> f32 {
+    let x = a.0 as f32 - b.0 as f32;
+    let y = a.1 as f32 - b.1 as f32;
+    (x * x + y * y).sqrt()
+}
+
+//walkable floor tiles that are far enough from every tile in avoid
+pub fn spawn_candidates(grid : &Grid, avoid : &[((usize, usize), f32)]) -> Vec<(usize, usize)> {
+    grid.walkable_tiles()
+        .into_iter()
+        .filter(|tile| grid.get(*tile) == TileType::Floor)
+        .filter(|tile| avoid.iter().all(|(other, distance)| tile_distance(*tile, *other) >= *distance))
+        .collect()
+}
+
+//tiles for every member of a group, packed around the anchor tile
+fn group_tiles(grid : &Grid, anchor : (usize, usize), size : u32) -> Vec<(usize, usize)> {
+    let mut tiles = vec![anchor];
+    'search: for radius in 1..=2i32 {
+        for x_offset in -radius..=radius {
+            for y_offset in -radius..=radius {
+                if tiles.len() >= size as usize {
+                    break 'search;
+                }
+                let x = anchor.0 as i32 + x_offset;
+                let y = anchor.1 as i32 + y_offset;
+                if !Grid::in_bounds(x, y) || tiles.contains(&(x as usize, y as usize)) {
+                    continue;
+                }
+                if grid.get((x as usize, y as usize)) == TileType::Floor {
+                    tiles.push((x as usize, y as usize));
+                }
+            }
+        }
+    }
+    //not enough room around the anchor, the rest of the group shares tiles
+    while tiles.len() < size as usize {
+        tiles.push(anchor);
+    }
+    tiles
+}
+
+pub fn spawn_monster(
+    commands : &mut Commands,
+    asset_server : &AssetServer,
+    bestiary : &Bestiary,
+    creature_type : CreatureType,
+    level : u32,
+    tile : (usize, usize),
+    depth : usize,
+) -> Entity {
+    let archetype = creature_type.get_stats(bestiary);
+    let max_health = archetype.max_health_at_level(level);
+    let position = Position::from_tile(tile, depth);
+    let mut monster = commands.spawn((
+        CreatureBundle {
+            creature_type,
+            sp_bundle: SpriteBundle {
+                texture: asset_server.load(archetype.sprite.as_str()),
+                transform: Transform::from_xyz(position.0, position.1, 1.0),
+                ..Default::default()
+            },
+            position,
+            ..Default::default()
+        },
+        Monster,
+        Level(level),
+        Health(max_health),
+        MaxHealth(max_health),
+        Velocity(0.0, 0.0),
+        Ai {
+            ranged: archetype.ranged.clone(),
+            ..Ai::new(archetype.behavior)
+        },
+        archetype.faction,
+        CombatStats::default(),
+        StatusEffects::default(),
+    ));
+    if archetype.has_tag("humanoid") {
+        monster.insert(Equipment::from_list(&archetype.equipment));
+    }
+    monster.id()
+}
+
+fn spawn_group(
+    commands : &mut Commands,
+    asset_server : &AssetServer,
+    bestiary : &Bestiary,
+    grid : &Grid,
+    group : &SpawnGroup,
+    anchor : (usize, usize),
+    depth : usize,
+) {
+    for tile in group_tiles(grid, anchor, group.size) {
+        spawn_monster(commands, asset_server, bestiary, group.creature_type, group.level, tile, depth);
+    }
+}
+
+//what rolling and spawning a group of monsters needs
+#[derive(SystemParam)]
+pub struct Spawner<'w> {
+    spawn_tables: Res<'w, SpawnTables>,
+    bestiary: Res<'w, Bestiary>,
+    asset_server: Res<'w, AssetServer>,
+    run_rng: ResMut<'w, RunRng>,
+}
+
+//place the initial monsters of every newly generated floor
+pub fn populate_new_floors(
+    mut ev_floor_generated: EventReader<FloorGenerated>,
+    mut commands: Commands,
+    game_world: Res<GameWorld>,
+    spawner: Spawner,
+) {
+    let Spawner {
+        spawn_tables,
+        bestiary,
+        asset_server,
+        mut run_rng,
+    } = spawner;
+    for event in ev_floor_generated.iter() {
+        let floor = &game_world.floors[event.level];
+        let Some(table) = spawn_tables.table(floor.floor_type) else {
+            continue;
+        };
+        let mut avoid = Vec::new();
+        if let Some(upstairs) = floor.grid.find_tile(TileType::UpStairs) {
+            avoid.push((upstairs, MIN_DISTANCE_FROM_STAIRS));
+        }
+        let candidates = spawn_candidates(&floor.grid, &avoid);
+        let groups = (table.base_groups + event.level as u32 / 2).min(MAX_GROUPS_PER_FLOOR);
+        for _ in 0..groups {
+            let Some(group) = spawn_tables.roll_group(floor.floor_type, event.level, &mut run_rng.rng) else {
+                break;
+            };
+            let Some(anchor) = candidates.choose(&mut run_rng.rng) else {
+                break;
+            };
+            spawn_group(&mut commands, &asset_server, &bestiary, &floor.grid, &group, *anchor, event.level);
+        }
+    }
+}
+
+//every SPAWN_INTERVAL seconds, a new group shows up somewhere on the player's floor out of their sight
+pub fn spawn_during_play(
+    time: Res<Time>,
+    mut spawn_timer: ResMut<SpawnTimer>,
+    mut commands: Commands,
+    player: Query<&Position, With<Player>>,
+    monsters: Query<&Position, With<Monster>>,
+    game_world: Res<GameWorld>,
+    spawner: Spawner,
+) {
+    let Spawner {
+        spawn_tables,
+        bestiary,
+        asset_server,
+        mut run_rng,
+    } = spawner;
+    if !spawn_timer.0.tick(time.delta()).just_finished() {
+        return;
+    }
+    let Ok(player_pos) = player.get_single() else {
+        return;
+    };
+    let depth = player_pos.2;
+    if monsters.iter().filter(|pos| pos.2 == depth).count() >= MAX_MONSTERS_PER_FLOOR {
+        return;
+    }
+    let floor = &game_world.floors[depth];
+    let mut avoid = vec![(player_pos.to_tile(), MIN_DISTANCE_FROM_PLAYER)];
+    if let Some(upstairs) = floor.grid.find_tile(TileType::UpStairs) {
+        avoid.push((upstairs, MIN_DISTANCE_FROM_STAIRS));
+    }
+    let candidates = spawn_candidates(&floor.grid, &avoid);
+    let Some(group) = spawn_tables.roll_group(floor.floor_type, depth, &mut run_rng.rng) else {
+        return;
+    };
+    if let Some(anchor) = candidates.choose(&mut run_rng.rng) {
+        spawn_group(&mut commands, &asset_server, &bestiary, &floor.grid, &group, *anchor, depth);
+    }
+}
+
+pub struct SpawningPlugin;
+
+impl Plugin for SpawningPlugin {
+    fn build(&self, app: &mut App) {
+        app.init_resource::<SpawnTables>()
+            .init_resource::<SpawnTimer>()
+            .add_systems(Update, populate_new_floors.run_if(on_event::<FloorGenerated>()))
+            .add_systems(Update, spawn_during_play.run_if(in_state(GameState::Playing)));
+    }
+}
@@ -0,0 +1,327 @@
+use std::collections::HashMap;
+
+use bevy::ecs::system::SystemParam;
+use bevy::prelude::*;
+use bevy::reflect::{TypePath, TypeUuid};
+use serde::{Deserialize, Serialize};
+
+use crate::abilities::{ClassResource, ResourceKind};
+use crate::combat::{roll_damage, AttackEvent};
+use crate::combat_stats::DamageType;
+use crate::creature::{Health, MaxHealth};
+use crate::data_asset::{refresh_ron_resource, RonAsset, RonAssetLoader, RonResource};
+use crate::faction::{Faction, FactionTable, Stance};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::grid::{Grid, TILE_SIZE};
+use crate::message_log::{MessageKind, MessageLog};
+use crate::player::{Aim, Player, PlayerClass};
+use crate::position::Position;
+use crate::projectiles::{FireProjectileEvent, ProjectileStats};
+use crate::run_rng::RunRng;
+use crate::status_effects::{is_stunned, ApplyStatusEvent, StatusApplication, StatusEffects};
+
+pub const SPELLS_PATH: &str = "data/spells.spells.ron";
+//keys used to cast the spells of the spellbook, in order
+pub const SPELL_KEYS: [KeyCode; 4] = [KeyCode::Key1, KeyCode::Key2, KeyCode::Key3, KeyCode::Key4];
+
+//How a spell picks what it affects, distances are in tiles
+#[derive(Deserialize, Debug, Clone, PartialEq)]
+pub enum Targeting {
+    //fires a projectile along the aim direction
+    Bolt { projectile: ProjectileStats },
+    //hits everything in front of the caster, angle is the full width of the cone in degrees
+    Cone { angle: f32 },
+    //hits everything around the cursor
+    Area { radius: f32 },
+    //affects the caster
+    SelfCast,
+}
+
+#[derive(Deserialize, Debug, Clone, Copy, PartialEq)]
+pub enum SpellEffect {
+    Damage {
+        rolls: u32,
+        die: u32,
+        #[serde(default)]
+        damage_type: DamageType,
+    },
+    Heal { amount: u32 },
+}
+
+#[derive(Deserialize, Debug, Clone)]
+pub struct SpellDefinition {
+    pub name: String,
+    pub mana_cost: f32,
+    //seconds before the spell can be cast again
+    pub cooldown: f32,
+    //in tiles, how far a bolt flies, how long a cone is and how far from the caster an area can be placed
+    pub range: f32,
+    pub targeting: Targeting,
+    pub effect: SpellEffect,
+    //put on every creature the spell hits, e.g. burning or slowed
+    #[serde(default)]
+    pub status: Option<StatusApplication>,
+}
+
+//The contents of the spells file, every spell by id
+#[derive(Deserialize, TypeUuid, TypePath)]
+#[uuid = "b2e4a7c1-3d5f-4e8a-9c6b-7f1d2e3a4b5c"]
+pub struct SpellsAsset {
+    pub spells: HashMap<String, SpellDefinition>,
+}
+
+impl RonAsset for SpellsAsset {
+    const EXTENSIONS: &'static [&'static str] = &["spells.ron"];
+}
+
+//The spell definitions of the spells file, see RonResource
+#[derive(Resource)]
+pub struct Spells {
+    pub handle: Handle<SpellsAsset>,
+    pub spells: HashMap<String, SpellDefinition>,
+}
+
+impl Spells {
+    pub fn get(&self, id : &str) -> Option<&SpellDefinition> {
+        self.spells.get(id)
+    }
+}
+
+//The spells a creature knows, in hotkey order
+#[derive(Component, Debug, Clone, Default, Serialize, Deserialize)]
+pub struct Spellbook {
+    pub spells: Vec<String>,
+    //seconds left before each spell is ready
+    pub cooldowns: HashMap<String, f32>,
+}
+
+impl Spellbook {
+    pub fn for_class(class : PlayerClass) -> Self {
+        Spellbook {
+            spells: class
+                .definition()
+                .starting_spells
+                .iter()
+                .map(|id| id.to_string())
+                .collect(),
+            cooldowns: HashMap::new(),
+        }
+    }
+    pub fn is_ready(&self, id : &str) -> bool {
+        self.cooldowns.get(id).is_none_or(|cooldown| *cooldown <= 0.0)
+    }
+}
+
+//every creature the spell affects, None when it can't be cast where the player is aiming
+pub fn spell_targets(
+    spell : &SpellDefinition,
+    grid : &Grid,
+    caster : Entity,
+    caster_pos : &Position,
+    aim : &Aim,
+    creatures : &[(Entity, Position)],
+) -> Option<Vec<Entity>> {
+    let range = spell.range * TILE_SIZE;
+    let visible = |pos : &Position| pos.2 == caster_pos.2 && grid.has_line_of_sight(caster_pos.to_tile(), pos.to_tile());
+    match &spell.targeting {
+        //the projectile finds its target while flying
+        Targeting::Bolt { .. } => Some(Vec::new()),
+        Targeting::Cone { angle } => {
+            let half_angle = (angle / 2.0).to_radians();
+            Some(
+                creatures
+                    .iter()
+                    .filter(|(_, pos)| {
+                        let to_creature = pos.to_vec2() - caster_pos.to_vec2();
+                        to_creature.length() <= range
+                            && to_creature.length() > 0.0
+                            && aim.direction.angle_between(to_creature).abs() <= half_angle
+                            && visible(pos)
+                    })
+                    .map(|(entity, _)| *entity)
+                    .collect(),
+            )
+        }
+        Targeting::Area { radius } => {
+            let center = Position::from_vec2(caster_pos.to_vec2() + aim.offset.clamp_length_max(range), caster_pos.2);
+            if !visible(&center) {
+                return None;
+            }
+            Some(
+                creatures
+                    .iter()
+                    .filter(|(_, pos)| pos.2 == center.2 && pos.distance(&center) <= *radius * TILE_SIZE)
+                    .map(|(entity, _)| *entity)
+                    .collect(),
+            )
+        }
+        Targeting::SelfCast => Some(vec![caster]),
+    }
+}
+
+pub fn load_spells(mut commands: Commands, asset_server: Res<AssetServer>) {
+    commands.insert_resource(Spells {
+        handle: asset_server.load(SPELLS_PATH),
+        spells: HashMap::new(),
+    });
+}
+
+impl RonResource for Spells {
+    type Asset = SpellsAsset;
+    fn handle(&self) -> &Handle<SpellsAsset> {
+        &self.handle
+    }
+    fn refresh(&mut self, asset : &SpellsAsset) {
+        self.spells = asset.spells.clone();
+        info!("spells loaded with {} spells", self.spells.len());
+    }
+}
+
+//The components of the player a spell needs and spends
+type Caster = (
+    Entity,
+    &'static Position,
+    &'static Aim,
+    &'static mut Spellbook,
+    &'static mut ClassResource,
+    Option<&'static StatusEffects>,
+);
+
+//The creatures a spell can hit or heal
+#[derive(SystemParam)]
+pub struct SpellTargets<'w, 's> {
+    creatures: Query<'w, 's, (Entity, &'static Position, &'static Faction), With<Health>>,
+    healths: Query<'w, 's, (&'static mut Health, &'static MaxHealth)>,
+    faction_table: Res<'w, FactionTable>,
+}
+
+//What a cast spell sends out
+#[derive(SystemParam)]
+pub struct SpellOutcomes<'w> {
+    attack_events: EventWriter<'w, AttackEvent>,
+    fire_events: EventWriter<'w, FireProjectileEvent>,
+    status_events: EventWriter<'w, ApplyStatusEvent>,
+    log: ResMut<'w, MessageLog>,
+}
+
+pub fn cast_spells(
+    keys: Res<Input<KeyCode>>,
+    mut players: Query<Caster, With<Player>>,
+    targets: SpellTargets,
+    outcomes: SpellOutcomes,
+    spells: Res<Spells>,
+    game_world: Res<GameWorld>,
+    mut run_rng: ResMut<RunRng>,
+) {
+    let SpellTargets {
+        creatures,
+        mut healths,
+        faction_table,
+    } = targets;
+    let SpellOutcomes {
+        mut attack_events,
+        mut fire_events,
+        mut status_events,
+        mut log,
+    } = outcomes;
+    let Ok((player, pos, aim, mut spellbook, mut resource, effects)) = players.get_single_mut() else {
+        return;
+    };
+    if resource.kind != ResourceKind::Mana || is_stunned(effects) {
+        return;
+    }
+    for (index, key) in SPELL_KEYS.iter().enumerate() {
+        if !keys.just_pressed(*key) {
+            continue;
+        }
+        let Some(id) = spellbook.spells.get(index).cloned() else {
+            continue;
+        };
+        let Some(spell) = spells.get(&id) else {
+            continue;
+        };
+        if !spellbook.is_ready(&id) || resource.current < spell.mana_cost {
+            continue;
+        }
+        //townsfolk and shopkeepers are safe from the player's spells, like from their swings
+        let others : Vec<(Entity, Position)> = creatures
+            .iter()
+            .filter(|(entity, _, faction)| {
+                *entity != player && faction_table.stance(Faction::Player, **faction) != Stance::Friendly
+            })
+            .map(|(entity, pos, _)| (entity, pos.clone()))
+            .collect();
+        let Some(targets) = spell_targets(spell, game_world.grid(pos.2), player, pos, aim, &others) else {
+            continue;
+        };
+        resource.spend(spell.mana_cost);
+        spellbook.cooldowns.insert(id, spell.cooldown);
+        log.add(MessageKind::Info, format!("You cast {}", spell.name));
+        if let (Targeting::Bolt { projectile }, SpellEffect::Damage { rolls, die, damage_type }) = (&spell.targeting, spell.effect) {
+            fire_events.send(FireProjectileEvent {
+                owner: player,
+                from: pos.clone(),
+                direction: aim.direction,
+                //the spell decides what kind of damage its bolt does
+                stats: ProjectileStats {
+                    damage_type,
+                    status: spell.status.or(projectile.status),
+                    ..projectile.clone()
+                },
+                max_range: spell.range,
+                damage: Some(roll_damage(rolls, die, &mut run_rng.rng)),
+            });
+        }
+        for target in targets {
+            if let Some(status) = spell.status {
+                status_events.send(ApplyStatusEvent {
+                    target,
+                    source: Some(player),
+                    status,
+                });
+            }
+            match spell.effect {
+                SpellEffect::Damage { rolls, die, damage_type } => {
+                    attack_events.send(AttackEvent {
+                        attacker: player,
+                        target,
+                        damage: Some(roll_damage(rolls, die, &mut run_rng.rng)),
+                        damage_type,
+                    });
+                }
+                SpellEffect::Heal { amount } => {
+                    if let Ok((mut health, max_health)) = healths.get_mut(target) {
+                        health.0 = (health.0 + amount).min(max_health.0);
+                    }
+                }
+            }
+        }
+    }
+}
+
+pub fn tick_spellbooks(time: Res<Time>, mut spellbooks: Query<&mut Spellbook>) {
+    let dt = time.delta_seconds();
+    for mut spellbook in spellbooks.iter_mut() {
+        for cooldown in spellbook.cooldowns.values_mut() {
+            *cooldown = (*cooldown - dt).max(0.0);
+        }
+    }
+}
+
+pub struct SpellsPlugin;
+
+impl Plugin for SpellsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_asset::<SpellsAsset>()
+            .init_asset_loader::<RonAssetLoader<SpellsAsset>>()
+            .add_systems(Startup, load_spells)
+            .add_systems(Update, refresh_ron_resource::<Spells>)
+            .add_systems(
+                Update,
+                (tick_spellbooks, cast_spells)
+                    .chain()
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}
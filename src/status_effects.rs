@@ -0,0 +1,455 @@
+use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
+
+use crate::combat::{resolve_attacks, AttackEvent};
+use crate::combat_stats::DamageType;
+use crate::creature::{Health, MaxHealth};
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::player::Player;
+use crate::position::Position;
+use crate::tiles::TileType;
+use crate::util::despawn_screen;
+
+//seconds between two ticks of damage or healing
+pub const STATUS_TICK: f32 = 1.0;
+//how long standing in lava keeps a creature burning, and how hard
+pub const LAVA_BURN: StatusApplication = StatusApplication {
+    kind: StatusKind::Burning,
+    duration: 3.0,
+    magnitude: 3.0,
+};
+//how much an invisible creature shows
+const INVISIBLE_ALPHA: f32 = 0.35;
+const STATUS_ICON_SIZE: f32 = 24.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum StatusKind {
+    //damage every tick, stacks up
+    Poison,
+    //fire damage every tick
+    Burning,
+    //moves slower by magnitude (a fraction)
+    Slowed,
+    //moves faster by magnitude (a fraction)
+    Hasted,
+    //can't move, attack or cast
+    Stunned,
+    //heals magnitude every tick
+    Regenerating,
+    //creatures only notice it when right next to it
+    Invisible,
+}
+
+//What happens when an effect is applied to a creature that already has it
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Stacking {
+    //keeps the longest duration and the strongest magnitude
+    Refresh,
+    //adds the durations
+    Extend,
+    //adds the magnitudes, keeps the longest duration
+    Intensify,
+    //the effect already there stays as it is
+    Ignore,
+}
+
+impl StatusKind {
+    pub const ALL: [StatusKind; 7] = [
+        StatusKind::Poison,
+        StatusKind::Burning,
+        StatusKind::Slowed,
+        StatusKind::Hasted,
+        StatusKind::Stunned,
+        StatusKind::Regenerating,
+        StatusKind::Invisible,
+    ];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "Poisoned",
+            StatusKind::Burning => "Burning",
+            StatusKind::Slowed => "Slowed",
+            StatusKind::Hasted => "Hasted",
+            StatusKind::Stunned => "Stunned",
+            StatusKind::Regenerating => "Regenerating",
+            StatusKind::Invisible => "Invisible",
+        }
+    }
+
+    pub fn stacking(&self) -> Stacking {
+        match self {
+            StatusKind::Poison => Stacking::Intensify,
+            StatusKind::Regenerating => Stacking::Extend,
+            //no stun locks
+            StatusKind::Stunned => Stacking::Ignore,
+            _ => Stacking::Refresh,
+        }
+    }
+
+    //the color the sprite of an affected creature is tinted with
+    pub fn tint(&self) -> Color {
+        match self {
+            StatusKind::Poison => Color::rgb(0.5, 1.0, 0.5),
+            StatusKind::Burning => Color::rgb(1.0, 0.6, 0.3),
+            StatusKind::Slowed => Color::rgb(0.6, 0.7, 1.0),
+            StatusKind::Hasted => Color::rgb(1.0, 1.0, 0.5),
+            StatusKind::Stunned => Color::rgb(0.6, 0.6, 0.6),
+            StatusKind::Regenerating => Color::rgb(1.0, 0.7, 0.8),
+            StatusKind::Invisible => Color::WHITE,
+        }
+    }
+
+    pub fn icon(&self) -> &'static str {
+        match self {
+            StatusKind::Poison => "ui/status/poison.png",
+            StatusKind::Burning => "ui/status/burning.png",
+            StatusKind::Slowed => "ui/status/slowed.png",
+            StatusKind::Hasted => "ui/status/hasted.png",
+            StatusKind::Stunned => "ui/status/stunned.png",
+            StatusKind::Regenerating => "ui/status/regenerating.png",
+            StatusKind::Invisible => "ui/status/invisible.png",
+        }
+    }
+}
+
+//An effect as spells, items, traps and projectiles describe it in the data files
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize)]
+pub struct StatusApplication {
+    pub kind: StatusKind,
+    //in seconds
+    pub duration: f32,
+    //damage or healing per tick, or the speed fraction
+    #[serde(default = "default_magnitude")]
+    pub magnitude: f32,
+}
+
+fn default_magnitude() -> f32 {
+    1.0
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct StatusEffect {
+    pub kind: StatusKind,
+    pub remaining: f32,
+    pub magnitude: f32,
+    //who applied it, credited with the damage it does
+    pub source: Option<Entity>,
+    //seconds until the next tick
+    pub tick_timer: f32,
+}
+
+//The effects currently on a creature. Only flagged as changed when an effect starts or ends,
+//so the combat stats, tint and icons aren't recomputed every frame
+#[derive(Component, Debug, Clone, Default)]
+pub struct StatusEffects {
+    pub effects: Vec<StatusEffect>,
+}
+
+impl StatusEffects {
+    pub fn get(&self, kind : StatusKind) -> Option<&StatusEffect> {
+        self.effects.iter().find(|effect| effect.kind == kind)
+    }
+
+    pub fn has(&self, kind : StatusKind) -> bool {
+        self.get(kind).is_some()
+    }
+
+    pub fn magnitude(&self, kind : StatusKind) -> f32 {
+        self.get(kind).map_or(0.0, |effect| effect.magnitude)
+    }
+
+    //fraction added to the movement speed
+    pub fn speed_bonus(&self) -> f32 {
+        self.magnitude(StatusKind::Hasted) - self.magnitude(StatusKind::Slowed)
+    }
+
+    //returns whether the effect started or got stronger, a longer duration alone doesn't count
+    pub fn apply(&mut self, status : StatusApplication, source : Option<Entity>) -> bool {
+        let Some(existing) = self.effects.iter_mut().find(|effect| effect.kind == status.kind) else {
+            self.effects.push(StatusEffect {
+                kind: status.kind,
+                remaining: status.duration,
+                magnitude: status.magnitude,
+                source,
+                tick_timer: STATUS_TICK,
+            });
+            return true;
+        };
+        let magnitude = existing.magnitude;
+        match status.kind.stacking() {
+            Stacking::Refresh => {
+                existing.remaining = existing.remaining.max(status.duration);
+                existing.magnitude = existing.magnitude.max(status.magnitude);
+            }
+            Stacking::Extend => existing.remaining += status.duration,
+            Stacking::Intensify => {
+                existing.remaining = existing.remaining.max(status.duration);
+                existing.magnitude += status.magnitude;
+            }
+            Stacking::Ignore => return false,
+        }
+        if source.is_some() {
+            existing.source = source;
+        }
+        existing.magnitude != magnitude
+    }
+}
+
+//whether the creature can't act this frame
+pub fn is_stunned(effects : Option<&StatusEffects>) -> bool {
+    effects.is_some_and(|effects| effects.has(StatusKind::Stunned))
+}
+
+//sent to put an effect on a creature
+#[derive(Event)]
+pub struct ApplyStatusEvent {
+    pub target: Entity,
+    pub source: Option<Entity>,
+    pub status: StatusApplication,
+}
+
+pub fn apply_status_effects(mut status_events: EventReader<ApplyStatusEvent>, mut creatures: Query<&mut StatusEffects>) {
+    for event in status_events.iter() {
+        if let Ok(mut effects) = creatures.get_mut(event.target) {
+            //lava reapplies burning every frame, that alone shouldn't count as a change
+            if effects.bypass_change_detection().apply(event.status, event.source) {
+                effects.set_changed();
+            }
+        }
+    }
+}
+
+//counts the effects down and does their damage and healing every tick
+pub fn tick_status_effects(
+    time: Res<Time>,
+    mut creatures: Query<(Entity, &mut StatusEffects, &mut Health, &MaxHealth)>,
+    sources: Query<(), With<Health>>,
+    mut attack_events: EventWriter<AttackEvent>,
+) {
+    let dt = time.delta_seconds();
+    for (entity, mut effects, mut health, max_health) in creatures.iter_mut() {
+        if effects.effects.is_empty() {
+            continue;
+        }
+        let mut ended = false;
+        for effect in effects.bypass_change_detection().effects.iter_mut() {
+            effect.remaining -= dt;
+            effect.tick_timer -= dt;
+            ended |= effect.remaining <= 0.0;
+            if effect.tick_timer > 0.0 {
+                continue;
+            }
+            effect.tick_timer += STATUS_TICK;
+            let amount = effect.magnitude.round().max(1.0) as u32;
+            let damage_type = match effect.kind {
+                StatusKind::Poison => DamageType::Poison,
+                StatusKind::Burning => DamageType::Fire,
+                StatusKind::Regenerating => {
+                    health.0 = (health.0 + amount).min(max_health.0);
+                    continue;
+                }
+                _ => continue,
+            };
+            //damage without a living source is self inflicted
+            let attacker = effect.source.filter(|source| sources.contains(*source)).unwrap_or(entity);
+            attack_events.send(AttackEvent {
+                attacker,
+                target: entity,
+                damage: Some(amount),
+                damage_type,
+            });
+        }
+        if ended {
+            effects.effects.retain(|effect| effect.remaining > 0.0);
+        }
+    }
+}
+
+//standing in lava sets creatures on fire
+pub fn burn_in_lava(
+    creatures: Query<(Entity, &Position), With<StatusEffects>>,
+    game_world: Res<GameWorld>,
+    mut status_events: EventWriter<ApplyStatusEvent>,
+) {
+    for (entity, pos) in creatures.iter() {
+        if game_world.grid(pos.2).get(pos.to_tile()) == TileType::Lava {
+            status_events.send(ApplyStatusEvent {
+                target: entity,
+                source: None,
+                status: LAVA_BURN,
+            });
+        }
+    }
+}
+
+//tints affected creatures with the color of their latest effect, invisible ones are see-through
+pub fn tint_affected_sprites(mut creatures: Query<(&StatusEffects, &mut Sprite), Changed<StatusEffects>>) {
+    for (effects, mut sprite) in creatures.iter_mut() {
+        let mut color = effects
+            .effects
+            .iter()
+            .rev()
+            .find(|effect| effect.kind != StatusKind::Invisible)
+            .map_or(Color::WHITE, |effect| effect.kind.tint());
+        if effects.has(StatusKind::Invisible) {
+            color.set_a(INVISIBLE_ALPHA);
+        }
+        sprite.color = color;
+    }
+}
+
+// Tag component used to tag the row of status icons
+#[derive(Component)]
+pub struct StatusIcons;
+
+//the player's effects as a row of icons in the top left corner
+pub fn update_status_icons(
+    players: Query<Ref<StatusEffects>, With<Player>>,
+    rows: Query<Entity, With<StatusIcons>>,
+    asset_server: Res<AssetServer>,
+    mut commands: Commands,
+) {
+    let Ok(effects) = players.get_single() else {
+        return;
+    };
+    if !effects.is_changed() && !rows.is_empty() {
+        return;
+    }
+    for row in rows.iter() {
+        commands.entity(row).despawn_recursive();
+    }
+    commands
+        .spawn((
+            NodeBundle {
+                style: Style {
+                    position_type: PositionType::Absolute,
+                    top: Val::Px(20.0),
+                    left: Val::Px(20.0),
+                    ..default()
+                },
+                ..default()
+            },
+            StatusIcons,
+        ))
+        .with_children(|parent| {
+            for kind in StatusKind::ALL.iter().filter(|kind| effects.has(**kind)) {
+                parent.spawn(ImageBundle {
+                    style: Style {
+                        width: Val::Px(STATUS_ICON_SIZE),
+                        height: Val::Px(STATUS_ICON_SIZE),
+                        margin: UiRect::right(Val::Px(4.0)),
+                        ..default()
+                    },
+                    image: UiImage::new(asset_server.load(kind.icon())),
+                    background_color: kind.tint().into(),
+                    ..default()
+                });
+            }
+        });
+}
+
+pub struct StatusEffectsPlugin;
+
+impl Plugin for StatusEffectsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<ApplyStatusEvent>()
+            .add_systems(
+                Update,
+                (
+                    burn_in_lava,
+                    apply_status_effects,
+                    tick_status_effects,
+                    tint_affected_sprites,
+                    update_status_icons,
+                )
+                    .chain()
+                    .before(resolve_attacks)
+                    .run_if(in_state(GameState::Playing)),
+            )
+            .add_systems(OnEnter(GameState::Menu), despawn_screen::<StatusIcons>);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn status(kind : StatusKind, duration : f32, magnitude : f32) -> StatusApplication {
+        StatusApplication {
+            kind,
+            duration,
+            magnitude,
+        }
+    }
+
+    #[test]
+    fn a_new_effect_starts() {
+        let mut effects = StatusEffects::default();
+        assert!(effects.apply(status(StatusKind::Burning, 3.0, 2.0), None));
+        let burning = effects.get(StatusKind::Burning).unwrap();
+        assert_eq!(burning.remaining, 3.0);
+        assert_eq!(burning.magnitude, 2.0);
+        assert_eq!(burning.tick_timer, STATUS_TICK);
+    }
+
+    #[test]
+    fn refresh_keeps_the_longest_and_strongest() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Burning, 3.0, 2.0), None);
+        //longer but weaker, not stronger
+        assert!(!effects.apply(status(StatusKind::Burning, 5.0, 1.0), None));
+        let burning = effects.get(StatusKind::Burning).unwrap();
+        assert_eq!(burning.remaining, 5.0);
+        assert_eq!(burning.magnitude, 2.0);
+        //shorter but stronger
+        assert!(effects.apply(status(StatusKind::Burning, 1.0, 4.0), None));
+        let burning = effects.get(StatusKind::Burning).unwrap();
+        assert_eq!(burning.remaining, 5.0);
+        assert_eq!(burning.magnitude, 4.0);
+        assert_eq!(effects.effects.len(), 1);
+    }
+
+    #[test]
+    fn extend_adds_the_durations() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Regenerating, 4.0, 1.0), None);
+        assert!(!effects.apply(status(StatusKind::Regenerating, 3.0, 5.0), None));
+        let regenerating = effects.get(StatusKind::Regenerating).unwrap();
+        assert_eq!(regenerating.remaining, 7.0);
+        assert_eq!(regenerating.magnitude, 1.0);
+    }
+
+    #[test]
+    fn intensify_adds_the_magnitudes() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Poison, 4.0, 1.0), None);
+        assert!(effects.apply(status(StatusKind::Poison, 2.0, 2.0), None));
+        let poison = effects.get(StatusKind::Poison).unwrap();
+        assert_eq!(poison.remaining, 4.0);
+        assert_eq!(poison.magnitude, 3.0);
+    }
+
+    #[test]
+    fn a_stun_is_not_stacked() {
+        let mut effects = StatusEffects::default();
+        effects.apply(status(StatusKind::Stunned, 1.0, 1.0), None);
+        assert!(!effects.apply(status(StatusKind::Stunned, 5.0, 3.0), Some(Entity::from_raw(1))));
+        let stunned = effects.get(StatusKind::Stunned).unwrap();
+        assert_eq!(stunned.remaining, 1.0);
+        assert_eq!(stunned.magnitude, 1.0);
+        assert_eq!(stunned.source, None);
+    }
+
+    #[test]
+    fn the_last_known_source_is_credited() {
+        let mut effects = StatusEffects::default();
+        let first = Entity::from_raw(1);
+        let second = Entity::from_raw(2);
+        effects.apply(status(StatusKind::Poison, 4.0, 1.0), Some(first));
+        effects.apply(status(StatusKind::Poison, 4.0, 1.0), None);
+        assert_eq!(effects.get(StatusKind::Poison).unwrap().source, Some(first));
+        effects.apply(status(StatusKind::Poison, 4.0, 1.0), Some(second));
+        assert_eq!(effects.get(StatusKind::Poison).unwrap().source, Some(second));
+    }
+}
@@ -1,6 +1,7 @@
 use bevy::prelude::*;
+use serde::{Deserialize, Serialize};
 
-#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash)]
+#[derive(Copy, Clone, PartialEq, Debug, Eq, Hash, Serialize, Deserialize)]
 pub enum TileType {
     Wall,
     Floor,
@@ -12,6 +13,33 @@ pub enum TileType {
 }
 
 impl TileType {
+    pub const ALL: [TileType; 7] = [
+        TileType::Wall,
+        TileType::Floor,
+        TileType::DownStairs,
+        TileType::UpStairs,
+        TileType::Fountain,
+        TileType::Water,
+        TileType::Lava,
+    ];
+
+    //how the tile is written in saves, the same as Grid::pretty_print_grid
+    pub fn to_char(self) -> char {
+        match self {
+            TileType::Wall => '#',
+            TileType::Floor => '.',
+            TileType::DownStairs => '>',
+            TileType::UpStairs => '<',
+            TileType::Fountain => 'F',
+            TileType::Water => ' ',
+            TileType::Lava => 'L',
+        }
+    }
+
+    pub fn from_char(c : char) -> Option<TileType> {
+        TileType::ALL.into_iter().find(|tile_type| tile_type.to_char() == c)
+    }
+
     pub fn is_walkable(self) -> bool {
         match self {
             TileType::Wall => false,
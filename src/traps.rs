@@ -0,0 +1,193 @@
+use bevy::prelude::*;
+use rand::seq::SliceRandom;
+use serde::{Deserialize, Serialize};
+
+use crate::actors::Actor;
+use crate::floor::FloorType;
+use crate::game_plugin::game::FloorGenerated;
+use crate::game_state::GameState;
+use crate::game_world::GameWorld;
+use crate::position::Position;
+use crate::run_rng::RunRng;
+use crate::spawning::{populate_new_floors, spawn_candidates, MIN_DISTANCE_FROM_STAIRS};
+use crate::status_effects::{apply_status_effects, ApplyStatusEvent, StatusApplication, StatusEffects, StatusKind};
+use crate::tiles::TileType;
+
+//traps on the first floor, one more every other floor
+pub const BASE_TRAPS_PER_FLOOR: usize = 2;
+pub const MAX_TRAPS_PER_FLOOR: usize = 10;
+//seconds before a trap can go off again
+pub const TRAP_REARM_TIME: f32 = 5.0;
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum TrapKind {
+    PoisonDart,
+    FireVent,
+    Snare,
+    FrostRune,
+}
+
+impl TrapKind {
+    pub const ALL: [TrapKind; 4] = [TrapKind::PoisonDart, TrapKind::FireVent, TrapKind::Snare, TrapKind::FrostRune];
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            TrapKind::PoisonDart => "poison dart trap",
+            TrapKind::FireVent => "fire vent",
+            TrapKind::Snare => "snare",
+            TrapKind::FrostRune => "frost rune",
+        }
+    }
+
+    pub fn status(&self) -> StatusApplication {
+        match self {
+            TrapKind::PoisonDart => StatusApplication {
+                kind: StatusKind::Poison,
+                duration: 6.0,
+                magnitude: 1.0,
+            },
+            TrapKind::FireVent => StatusApplication {
+                kind: StatusKind::Burning,
+                duration: 3.0,
+                magnitude: 2.0,
+            },
+            TrapKind::Snare => StatusApplication {
+                kind: StatusKind::Stunned,
+                duration: 1.5,
+                magnitude: 1.0,
+            },
+            TrapKind::FrostRune => StatusApplication {
+                kind: StatusKind::Slowed,
+                duration: 5.0,
+                magnitude: 0.5,
+            },
+        }
+    }
+
+    pub fn sprite(&self) -> &'static str {
+        match self {
+            TrapKind::PoisonDart => "dungeon/traps/dart.png",
+            TrapKind::FireVent => "dungeon/traps/fire.png",
+            TrapKind::Snare => "dungeon/traps/net.png",
+            TrapKind::FrostRune => "dungeon/traps/zot.png",
+        }
+    }
+}
+
+//A trap on a floor tile, hidden until it goes off for the first time
+#[derive(Component, Debug, Clone)]
+pub struct Trap {
+    pub kind: TrapKind,
+    //seconds before it can go off again
+    pub rearm: f32,
+}
+
+//traps stay hidden until revealed, see trigger_traps
+pub fn spawn_trap(commands : &mut Commands, asset_server : &AssetServer, trap : Trap, position : Position, revealed : bool) -> Entity {
+    commands
+        .spawn((
+            Actor::default(),
+            SpriteBundle {
+                texture: asset_server.load(trap.kind.sprite()),
+                //under the items
+                transform: Transform::from_xyz(position.0, position.1, 0.25),
+                visibility: if revealed { Visibility::Inherited } else { Visibility::Hidden },
+                ..default()
+            },
+            position,
+            trap,
+        ))
+        .id()
+}
+
+//sent when a creature walks on a trap
+#[derive(Event)]
+pub struct TrapTriggeredEvent {
+    pub trap: TrapKind,
+    pub victim: Entity,
+}
+
+//hide a few traps on every newly generated dungeon floor, away from the stairs the player arrives on
+pub fn place_traps(
+    mut ev_floor_generated: EventReader<FloorGenerated>,
+    game_world: Res<GameWorld>,
+    asset_server: Res<AssetServer>,
+    mut run_rng: ResMut<RunRng>,
+    mut commands: Commands,
+) {
+    for event in ev_floor_generated.iter() {
+        let floor = &game_world.floors[event.level];
+        if floor.floor_type == FloorType::Town {
+            continue;
+        }
+        let mut avoid = Vec::new();
+        if let Some(upstairs) = floor.grid.find_tile(TileType::UpStairs) {
+            avoid.push((upstairs, MIN_DISTANCE_FROM_STAIRS));
+        }
+        let candidates = spawn_candidates(&floor.grid, &avoid);
+        let count = (BASE_TRAPS_PER_FLOOR + event.level / 2).min(MAX_TRAPS_PER_FLOOR);
+        for tile in candidates.choose_multiple(&mut run_rng.rng, count) {
+            let Some(kind) = TrapKind::ALL.choose(&mut run_rng.rng) else {
+                continue;
+            };
+            let trap = Trap {
+                kind: *kind,
+                rearm: 0.0,
+            };
+            spawn_trap(&mut commands, &asset_server, trap, Position::from_tile(*tile, event.level), false);
+        }
+    }
+}
+
+//creatures stepping on an armed trap get its effect, and the trap shows itself
+pub fn trigger_traps(
+    time: Res<Time>,
+    mut traps: Query<(&mut Trap, &Position, &mut Visibility)>,
+    creatures: Query<(Entity, &Position), With<StatusEffects>>,
+    mut status_events: EventWriter<ApplyStatusEvent>,
+    mut trap_events: EventWriter<TrapTriggeredEvent>,
+) {
+    for (mut trap, trap_pos, mut visibility) in traps.iter_mut() {
+        trap.rearm = (trap.rearm - time.delta_seconds()).max(0.0);
+        if trap.rearm > 0.0 {
+            continue;
+        }
+        let tile = trap_pos.to_tile();
+        let Some((victim, _)) = creatures.iter().find(|(_, pos)| pos.2 == trap_pos.2 && pos.to_tile() == tile) else {
+            continue;
+        };
+        status_events.send(ApplyStatusEvent {
+            target: victim,
+            source: None,
+            status: trap.kind.status(),
+        });
+        trap_events.send(TrapTriggeredEvent {
+            trap: trap.kind,
+            victim,
+        });
+        trap.rearm = TRAP_REARM_TIME;
+        if *visibility == Visibility::Hidden {
+            *visibility = Visibility::Inherited;
+        }
+    }
+}
+
+pub struct TrapsPlugin;
+
+impl Plugin for TrapsPlugin {
+    fn build(&self, app: &mut App) {
+        app.add_event::<TrapTriggeredEvent>()
+            .add_systems(
+                Update,
+                place_traps
+                    .after(populate_new_floors)
+                    .run_if(on_event::<FloorGenerated>()),
+            )
+            .add_systems(
+                Update,
+                trigger_traps
+                    .before(apply_status_effects)
+                    .run_if(in_state(GameState::Playing)),
+            );
+    }
+}